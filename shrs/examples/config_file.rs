@@ -24,7 +24,7 @@ fn main() {
     let mut env = Env::new();
     env.load();
     for (ref k, ref v) in myconfig.envs {
-        env.set(k, v);
+        env.set(k, v).unwrap();
     }
 
     let myshell = ShellConfigBuilder::default()
@@ -2,6 +2,7 @@
 extern crate derive_builder;
 
 pub use shrs_core::*;
+pub use shrs_lang as lang;
 pub use shrs_line as line;
 
 mod shell;
@@ -18,7 +19,7 @@ pub mod crossterm {
 
 pub mod prelude {
     pub use shrs_core::{builtin::*, hooks::*, prompt::*, *};
-    pub use shrs_lang::PosixLang;
+    pub use shrs_lang::{ParsedCommandCtx, PosixLang};
     pub use shrs_line::{completion::*, *};
 
     pub use crate::{crossterm::*, plugin::*, shell::*};
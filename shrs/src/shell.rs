@@ -1,24 +1,26 @@
 use std::{
     cell::RefCell,
-    collections::HashMap,
-    fs::File,
-    io::{stdout, BufRead, BufWriter, Write},
-    path::Path,
-    process::{Child, Stdio},
+    ffi::OsString,
+    fs,
+    io::{stdin, stdout, BufWriter, IsTerminal, Write},
+    path::PathBuf,
     time::Instant,
 };
 
-use lazy_static::lazy_static;
+use clap::Parser;
 use shrs_core::{
     builtin::Builtins,
-    dummy_child,
     hooks::{BeforeCommandCtx, Hooks, JobExitCtx, StartupCtx},
-    Alias, Context, Env, ExitStatus, Jobs, Lang, Runtime, Shell, Signals, State, Theme,
+    trap::{run_trap, Condition, Traps},
+    Alias, Context, Env, ExitStatus, Functions, Jobs, Lang, Options, Runtime, Shell, Signals,
+    State, Theme,
+};
+use shrs_job::{initialize_job_control, JobManager};
+use shrs_lang::{
+    builtin::{FmtBuiltin, LintBuiltin},
+    PosixError, PosixLang,
 };
-use shrs_job::JobManager;
-use shrs_lang::PosixLang;
 use shrs_line::{DefaultPrompt, Line, Prompt};
-use thiserror::Error;
 
 use crate::plugin::Plugin;
 
@@ -30,7 +32,7 @@ pub struct ShellConfig {
     #[builder(default = "Hooks::default()")]
     pub hooks: Hooks,
 
-    #[builder(default = "Builtins::default()")]
+    #[builder(default = "default_builtins()")]
     pub builtins: Builtins,
 
     #[builder(default = "Line::default()")]
@@ -39,8 +41,8 @@ pub struct ShellConfig {
     #[builder(default = "Alias::new()")]
     pub alias: Alias,
 
-    /// Environment variables
-    #[builder(default = "Env::new()")]
+    /// Environment variables, by default the ones shrs was started with
+    #[builder(default = "default_env()")]
     pub env: Env,
 
     // /// List of defined functions
@@ -50,6 +52,10 @@ pub struct ShellConfig {
     #[builder(default = "Theme::default()")]
     pub theme: Theme,
 
+    /// Shell options
+    #[builder(default = "Options::default()")]
+    pub options: Options,
+
     /// Command language
     #[builder(default = "Box::new(PosixLang::new())")]
     #[builder(setter(custom))]
@@ -66,6 +72,21 @@ pub struct ShellConfig {
     pub state: State,
 }
 
+/// Variables of the environment shrs was started with, exported so that commands get them too
+pub fn default_env() -> Env {
+    let mut env = Env::new();
+    env.load();
+    env
+}
+
+/// Builtins from `shrs_core` together with the ones that need the command language
+pub fn default_builtins() -> Builtins {
+    let mut builtins = Builtins::default();
+    builtins.insert("fmt", FmtBuiltin::default());
+    builtins.insert("lint", LintBuiltin::default());
+    builtins
+}
+
 impl ShellConfigBuilder {
     pub fn with_plugin(mut self, plugin: impl Plugin + 'static) -> Self {
         let mut cur_plugin = self.plugins.unwrap_or(vec![]);
@@ -85,8 +106,114 @@ impl ShellConfigBuilder {
     }
 }
 
+/// Commands to run without the line editor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Script {
+    /// Read the commands from a file
+    File(PathBuf),
+    /// Run the commands in the string, like `sh -c`
+    Command(String),
+}
+
+/// Run shell commands interactively, from a script file or from a string
+///
+/// Without arguments an interactive shell is started.
+#[derive(Parser, Debug)]
+struct Cli {
+    /// Read commands from COMMAND instead of a file
+    #[arg(short = 'c', value_name = "COMMAND")]
+    command: Option<String>,
+    /// Script to run followed by its arguments. With -c, the value of $0 followed by the arguments
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+}
+
 impl ShellConfig {
-    pub fn run(mut self) -> anyhow::Result<()> {
+    /// Start the interactive shell
+    pub fn run(self) -> anyhow::Result<()> {
+        self.run_interactive()
+    }
+
+    /// Start the shell according to command line arguments, such as the ones from
+    /// [std::env::args]
+    ///
+    /// The first argument is the name of the program. `shrs FILE [ARGS...]` runs a script and
+    /// `shrs -c COMMAND [NAME [ARGS...]]` runs a string, both exit the process with the status of
+    /// the script. Without arguments an interactive shell is started.
+    pub fn run_from_args<I, T>(self, args: I) -> anyhow::Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let cli = Cli::parse_from(args);
+        let mut args = cli.args.into_iter();
+        let (script, name) = match cli.command {
+            Some(command) => (
+                Script::Command(command),
+                args.next().unwrap_or_else(|| "shrs".into()),
+            ),
+            None => match args.next() {
+                Some(file) => (Script::File(PathBuf::from(&file)), file),
+                None => return self.run_interactive(),
+            },
+        };
+
+        let status = match self.run_script(script, name.clone(), args.collect()) {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("{name}: {e}");
+                127
+            },
+        };
+        std::process::exit(status)
+    }
+
+    /// Start the interactive shell, reading commands with the line editor
+    pub fn run_interactive(self) -> anyhow::Result<()> {
+        // only take control of the terminal if there is one
+        if stdin().is_terminal() {
+            initialize_job_control()?;
+        }
+
+        let (sh, mut ctx, mut rt, mut readline) = self.into_shell("shrs".into(), vec![]);
+        run_shell(&sh, &mut ctx, &mut rt, &mut readline)
+    }
+
+    /// Run a whole script without the line editor and return its exit status
+    ///
+    /// `name` and `args` become `$0` and the positional parameters. Startup hooks are not run.
+    /// Fails only if the script could not be read, errors raised by the script itself are printed
+    /// and turned into a non-zero exit status.
+    pub fn run_script(
+        self,
+        script: Script,
+        name: String,
+        args: Vec<String>,
+    ) -> anyhow::Result<i32> {
+        let source = match script {
+            Script::File(path) => fs::read_to_string(path)?,
+            Script::Command(command) => command,
+        };
+
+        let (sh, mut ctx, mut rt, _readline) = self.into_shell(name, args);
+        let status = match sh.lang.eval(&sh, &mut ctx, &mut rt, source) {
+            Ok(_) => rt.exit_status,
+            Err(e) => {
+                eprintln!("{}: {e}", rt.name);
+                match e.downcast_ref::<PosixError>() {
+                    Some(PosixError::Parse { .. }) => 2,
+                    _ => 1,
+                }
+            },
+        };
+        rt.exit_status = status;
+        run_trap(&sh, &mut ctx, &mut rt, Condition::Exit);
+        ctx.out.flush()?;
+        Ok(rt.exit_status)
+    }
+
+    /// Run the plugins and assemble the shell along with its initial context and runtime
+    fn into_shell(mut self, name: String, args: Vec<String>) -> (Shell, Context, Runtime, Line) {
         // TODO some default values for Context and Runtime are duplicated by the #[builder(default = "...")]
         // calls in ShellConfigBuilder, so we are sort of defining the full default here. Maybe end
         // up implementing Default for Context and Runtime
@@ -97,22 +224,32 @@ impl ShellConfig {
             plugin.init(&mut self);
         }
 
-        let mut ctx = Context {
+        let ctx = Context {
             alias: self.alias,
             out: BufWriter::new(stdout()),
             state: self.state,
             jobs: Jobs::new(),
             startup_time: Instant::now(),
+            proc_substs: vec![],
         };
-        let mut rt = Runtime {
+        let rt = Runtime {
             env: self.env,
             working_dir: std::env::current_dir().unwrap(),
-            // TODO currently hardcoded
-            name: "shrs".into(),
-            // TODO currently unused (since we have not implemented functions etc)
-            args: vec![],
+            name,
+            args,
             exit_status: 0,
-            // functions: self.functions,
+            pipe_status: vec![],
+            rematch: vec![],
+            pid: std::process::id(),
+            last_background: None,
+            options: self.options,
+            functions: Functions::new(),
+            frames: vec![],
+            control_flow: None,
+            loops: 0,
+            sources: 0,
+            conditions: 0,
+            traps: Traps::new(),
         };
         let sh = Shell {
             job_manager: RefCell::new(JobManager::default()),
@@ -122,9 +259,8 @@ impl ShellConfig {
             hooks: self.hooks,
             signals: Signals::new().unwrap(),
         };
-        let mut readline = self.readline;
 
-        run_shell(&sh, &mut ctx, &mut rt, &mut readline)
+        (sh, ctx, rt, self.readline)
     }
 }
 
@@ -151,20 +287,6 @@ fn run_shell(
     loop {
         let line = readline.read_line(sh, ctx, rt);
 
-        // attempt to expand alias
-        // TODO IFS
-        let mut words = line
-            .split(' ')
-            .map(|s| s.trim_start_matches("\\\n").trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect::<Vec<_>>();
-        if let Some(first) = words.get_mut(0) {
-            if let Some(expanded) = ctx.alias.get(&first) {
-                *first = expanded.to_owned().to_string();
-            }
-        }
-        let line = words.join(" ");
-
         // TODO not sure if hook should run here (since not all vars are expanded yet)
         let hook_ctx = BeforeCommandCtx {
             raw_command: line.clone(),
@@ -0,0 +1,55 @@
+use super::{BuiltinCmd, BuiltinStatus};
+use crate::{
+    shell::{Context, ControlFlow, Runtime},
+    Shell,
+};
+
+/// Leave the enclosing loop, or start its next iteration when invoked as `continue`
+///
+/// ```sh
+/// break [n]
+/// continue [n]
+/// ```
+/// `n` is the number of enclosing loops to apply to, if there are less than that the outermost
+/// one is used.
+#[derive(Default)]
+pub struct BreakBuiltin {
+    /// Invoked as `continue`
+    pub next: bool,
+}
+
+impl BuiltinCmd for BreakBuiltin {
+    fn run(
+        &self,
+        _sh: &Shell,
+        _ctx: &mut Context,
+        rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        let name = if self.next { "continue" } else { "break" };
+        if rt.loops == 0 {
+            eprintln!("{name}: only meaningful in a `for', `while', `until' or `select' loop");
+            return Ok(BuiltinStatus::error());
+        }
+
+        let n = match args.first().map(|n| n.parse::<usize>()) {
+            None => 1,
+            Some(Ok(n)) if n > 0 => n,
+            Some(Ok(_)) => {
+                eprintln!("{name}: {}: loop count out of range", args[0]);
+                return Ok(BuiltinStatus::error());
+            },
+            Some(Err(_)) => {
+                eprintln!("{name}: {}: numeric argument required", args[0]);
+                return Ok(BuiltinStatus(2));
+            },
+        };
+        let n = n.min(rt.loops);
+        rt.control_flow = Some(match self.next {
+            true => ControlFlow::Continue(n),
+            false => ControlFlow::Break(n),
+        });
+
+        Ok(BuiltinStatus::success())
+    }
+}
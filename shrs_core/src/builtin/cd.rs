@@ -39,7 +39,7 @@ impl BuiltinCmd for CdBuiltin {
         };
 
         let old_pwd = env::current_dir().unwrap();
-        rt.env.set("OLDPWD", &old_pwd.display().to_string());
+        rt.env.set("OLDPWD", &old_pwd.display().to_string())?;
 
         env::set_current_dir(path.clone())?; // TODO should env current dir remain as the directory the shell was started in?
 
@@ -51,7 +51,7 @@ impl BuiltinCmd for CdBuiltin {
         sh.hooks.run::<ChangeDirCtx>(sh, ctx, rt, hook_ctx);
 
         rt.working_dir = path.clone();
-        rt.env.set("PWD", path.to_str().unwrap());
+        rt.env.set("PWD", path.to_str().unwrap())?;
 
         // return a dummy command
         Ok(BuiltinStatus::success())
@@ -0,0 +1,87 @@
+use std::{os::unix::fs::PermissionsExt, path::PathBuf};
+
+use super::{BuiltinCmd, BuiltinStatus};
+use crate::{
+    shell::{Context, Runtime},
+    Shell,
+};
+
+/// Describe how a command name would be interpreted
+///
+/// ```sh
+/// command -v name...
+/// command -V name...
+/// ```
+/// Running `command name args...` skips aliases and functions, that form is handled by the
+/// evaluator and only reaches the builtin when no command is given.
+#[derive(Default)]
+pub struct CommandBuiltin {}
+
+impl BuiltinCmd for CommandBuiltin {
+    fn run(
+        &self,
+        sh: &Shell,
+        ctx: &mut Context,
+        rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        let (verbose, names) = match args.split_first() {
+            Some((flag, names)) if flag == "-v" => (false, names),
+            Some((flag, names)) if flag == "-V" => (true, names),
+            Some((flag, _)) if flag.starts_with('-') && flag != "--" => {
+                eprintln!("command: {flag}: invalid option");
+                return Ok(BuiltinStatus(2));
+            },
+            _ => return Ok(BuiltinStatus::success()),
+        };
+
+        let mut status = BuiltinStatus::success();
+        for name in names {
+            let desc = if let Some(value) = ctx.alias.get(name) {
+                match verbose {
+                    true => format!("{name} is aliased to `{value}'"),
+                    false => format!("alias {name}='{value}'"),
+                }
+            } else if rt.functions.get(name).is_some() {
+                match verbose {
+                    true => format!("{name} is a function"),
+                    false => name.clone(),
+                }
+            } else if sh.builtins.get(name).is_some() {
+                match verbose {
+                    true => format!("{name} is a shell builtin"),
+                    false => name.clone(),
+                }
+            } else if let Some(path) = find_executable(rt, name) {
+                match verbose {
+                    true => format!("{name} is {}", path.display()),
+                    false => path.display().to_string(),
+                }
+            } else {
+                if verbose {
+                    eprintln!("command: {name}: not found");
+                }
+                status = BuiltinStatus::error();
+                continue;
+            };
+            println!("{desc}");
+        }
+        Ok(status)
+    }
+}
+
+/// Search `PATH` for an executable file, names containing a slash are not searched
+fn find_executable(rt: &Runtime, name: &str) -> Option<PathBuf> {
+    let is_executable = |path: &PathBuf| {
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    };
+    if name.contains('/') {
+        return Some(rt.working_dir.join(name)).filter(is_executable);
+    }
+    rt.env
+        .get("PATH")?
+        .split(':')
+        .map(|dir| rt.working_dir.join(dir).join(name))
+        .find(is_executable)
+}
@@ -0,0 +1,187 @@
+use super::{BuiltinCmd, BuiltinStatus};
+use crate::{
+    env::{EnvError, Var},
+    shell::{Context, Runtime},
+    util::{is_name, quote},
+    Shell,
+};
+
+/// Set variables and their attributes
+///
+/// ```sh
+/// declare [-irx] [+irx] [name[=value]...]
+/// declare -p [name...]
+/// ```
+/// `-i` gives the integer attribute, `-r` the readonly one and `-x` the exported one, using `+`
+/// instead takes them away. Without names, or with `-p`, the variables are printed as commands
+/// that recreate them.
+#[derive(Default)]
+pub struct DeclareBuiltin {}
+
+impl BuiltinCmd for DeclareBuiltin {
+    fn run(
+        &self,
+        sh: &Shell,
+        _ctx: &mut Context,
+        rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        let mut attrs = Attrs::default();
+        let mut print = false;
+        let mut it = args.iter().peekable();
+        while let Some(arg) = it.next_if(|arg| arg.len() > 1 && arg.starts_with(['-', '+'])) {
+            if arg == "--" {
+                break;
+            }
+            let on = arg.starts_with('-');
+            for flag in arg[1..].chars() {
+                match flag {
+                    'p' if on => print = true,
+                    flag if attrs.set(flag, on) => {},
+                    _ => {
+                        eprintln!("declare: {}{flag}: invalid option", &arg[..1]);
+                        return Ok(BuiltinStatus(2));
+                    },
+                }
+            }
+        }
+        let names = it.cloned().collect::<Vec<_>>();
+
+        if !print && !names.is_empty() {
+            return Ok(declare(sh, rt, "declare", &names, attrs));
+        }
+        if names.is_empty() {
+            print_vars(rt, |var| Some(declaration(var)));
+            return Ok(BuiltinStatus::success());
+        }
+        let mut status = BuiltinStatus::success();
+        for name in names {
+            match rt.env.var(&name) {
+                Some(var) => println!("{}", command(&declaration(var), &name, var)),
+                None => {
+                    eprintln!("declare: {name}: not found");
+                    status = BuiltinStatus::error();
+                },
+            }
+        }
+        Ok(status)
+    }
+}
+
+/// Attributes to give to variables or take away from them, [None] leaves an attribute alone
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct Attrs {
+    pub exported: Option<bool>,
+    pub readonly: Option<bool>,
+    pub integer: Option<bool>,
+}
+
+impl Attrs {
+    /// Record the attribute of an option such as `-i` or `+x`, returns false for other options
+    pub fn set(&mut self, flag: char, on: bool) -> bool {
+        let attr = match flag {
+            'i' => &mut self.integer,
+            'r' => &mut self.readonly,
+            'x' => &mut self.exported,
+            _ => return false,
+        };
+        *attr = Some(on);
+        true
+    }
+}
+
+/// Change the attributes of every variable in `args`, which are given as `name` or `name=value`
+pub(super) fn declare(
+    sh: &Shell,
+    rt: &mut Runtime,
+    builtin: &str,
+    args: &[String],
+    attrs: Attrs,
+) -> BuiltinStatus {
+    let mut status = BuiltinStatus::success();
+    for arg in args {
+        let (var, val) = match arg.split_once('=') {
+            Some((var, val)) => (var, Some(val)),
+            None => (arg.as_str(), None),
+        };
+        if !is_name(var) {
+            eprintln!("{builtin}: {arg}: not a valid identifier");
+            status = BuiltinStatus::error();
+            continue;
+        }
+        if let Err(e) = apply(sh, rt, var, val, attrs) {
+            eprintln!("{builtin}: {e}");
+            status = BuiltinStatus::error();
+        }
+    }
+    status
+}
+
+/// Give a variable the attributes and assign it the value, if any
+///
+/// The value of a variable with the integer attribute is evaluated as an arithmetic expression,
+/// the same way as in an assignment.
+pub(super) fn apply(
+    sh: &Shell,
+    rt: &mut Runtime,
+    var: &str,
+    val: Option<&str>,
+    attrs: Attrs,
+) -> anyhow::Result<()> {
+    // the readonly attribute can not be taken away
+    let readonly = rt.env.var(var).is_some_and(|v| v.readonly);
+    if readonly && (attrs.readonly == Some(false) || val.is_some()) {
+        return Err(EnvError::Readonly(var.to_string()).into());
+    }
+    if let Some(integer) = attrs.integer {
+        rt.env.set_integer(var, integer)?;
+    }
+    if let Some(val) = val {
+        match rt.env.var(var).is_some_and(|v| v.integer) {
+            true => {
+                let val = sh.lang.eval_arith(rt, val)?;
+                rt.env.set(var, &val.to_string())?;
+            },
+            false => rt.env.set(var, val)?,
+        }
+    }
+    if let Some(exported) = attrs.exported {
+        rt.env.export(var, exported);
+    }
+    if attrs.readonly == Some(true) {
+        rt.env.set_readonly(var);
+    }
+    Ok(())
+}
+
+/// Print the variables for which `prefix` returns a command, sorted by name
+pub(super) fn print_vars(rt: &Runtime, prefix: impl Fn(&Var) -> Option<String>) {
+    let mut vars = rt
+        .env
+        .vars()
+        .filter_map(|(name, var)| Some((name, var, prefix(var)?)))
+        .collect::<Vec<_>>();
+    vars.sort_by_key(|(name, ..)| *name);
+    for (name, var, prefix) in vars {
+        println!("{}", command(&prefix, name, var));
+    }
+}
+
+/// Command that recreates a variable, `prefix` is the builtin along with its options
+fn command(prefix: &str, name: &str, var: &Var) -> String {
+    match &var.value {
+        Some(val) => format!("{prefix} {name}={}", quote(val)),
+        None => format!("{prefix} {name}"),
+    }
+}
+
+fn declaration(var: &Var) -> String {
+    let flags = [(var.integer, 'i'), (var.readonly, 'r'), (var.exported, 'x')]
+        .into_iter()
+        .filter_map(|(set, flag)| set.then_some(flag))
+        .collect::<String>();
+    match flags.is_empty() {
+        true => String::from("declare --"),
+        false => format!("declare -{flags}"),
+    }
+}
@@ -1,9 +1,13 @@
 use super::{BuiltinCmd, BuiltinStatus};
 use crate::{
     shell::{Context, Runtime},
+    trap::exit_shell,
     Shell,
 };
 
+/// Exit the shell, running the `EXIT` trap first
+///
+/// The exit status is given by the argument, or the status of the last command if there is none.
 #[derive(Default)]
 pub struct ExitBuiltin {}
 
@@ -15,6 +19,14 @@ impl BuiltinCmd for ExitBuiltin {
         rt: &mut Runtime,
         args: &Vec<String>,
     ) -> anyhow::Result<BuiltinStatus> {
-        std::process::exit(0)
+        let status = match args.first().map(|n| n.parse::<i32>()) {
+            None => rt.exit_status,
+            Some(Ok(n)) => n & 0xff,
+            Some(Err(_)) => {
+                eprintln!("exit: {}: numeric argument required", args[0]);
+                2
+            },
+        };
+        exit_shell(sh, ctx, rt, status)
     }
 }
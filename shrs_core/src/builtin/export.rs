@@ -1,22 +1,17 @@
-use std::io::{stdout, Write};
-
-use clap::{Parser, Subcommand};
-
-use super::{BuiltinCmd, BuiltinStatus};
+use super::{
+    declare::{declare, print_vars, Attrs},
+    BuiltinCmd, BuiltinStatus,
+};
 use crate::shell::{Context, Runtime, Shell};
 
-#[derive(Parser)]
-struct Cli {
-    vars: Vec<String>,
-    #[arg(short)]
-    p: bool,
-    #[arg(short)]
-    n: bool,
-}
-
-#[derive(Subcommand)]
-enum Commands {}
-
+/// Give variables the exported attribute, so they are passed to the environment of commands
+///
+/// ```sh
+/// export [-n] name[=value]...
+/// export -p
+/// ```
+/// `-n` takes the attribute away instead. Without names, or with `-p`, the exported variables are
+/// printed as commands that recreate them.
 #[derive(Default)]
 pub struct ExportBuiltin {}
 
@@ -24,36 +19,34 @@ impl BuiltinCmd for ExportBuiltin {
     fn run(
         &self,
         sh: &Shell,
-        ctx: &mut Context,
+        _ctx: &mut Context,
         rt: &mut Runtime,
         args: &Vec<String>,
     ) -> anyhow::Result<BuiltinStatus> {
-        let cli = Cli::parse_from(vec!["export".to_string()].iter().chain(args.iter()));
-
-        // remove arg
-        if cli.n {
-            for var in cli.vars {
-                rt.env.remove(&var);
+        let mut exported = true;
+        let mut print = false;
+        let mut it = args.iter().peekable();
+        while let Some(arg) = it.next_if(|arg| arg.len() > 1 && arg.starts_with('-')) {
+            match arg.as_str() {
+                "--" => break,
+                "-n" => exported = false,
+                "-p" => print = true,
+                _ => {
+                    eprintln!("export: {arg}: invalid option");
+                    return Ok(BuiltinStatus(2));
+                },
             }
-            return Ok(BuiltinStatus::success());
         }
+        let names = it.cloned().collect::<Vec<_>>();
 
-        // print all env vars
-        if cli.p {
-            for (var, val) in rt.env.all().iter() {
-                println!("export {}={}", var, val);
-            }
+        if print || names.is_empty() {
+            print_vars(rt, |var| var.exported.then(|| String::from("export")));
             return Ok(BuiltinStatus::success());
         }
-
-        for var in cli.vars {
-            let mut it = var.splitn(2, "=");
-            let var = it.next().unwrap();
-            let val = it.next().unwrap_or_default();
-
-            rt.env.set(var, val);
-        }
-
-        Ok(BuiltinStatus::success())
+        let attrs = Attrs {
+            exported: Some(exported),
+            ..Attrs::default()
+        };
+        Ok(declare(sh, rt, "export", &names, attrs))
     }
 }
@@ -0,0 +1,72 @@
+use super::{
+    declare::{apply, Attrs},
+    BuiltinCmd, BuiltinStatus,
+};
+use crate::{
+    shell::{Context, Runtime},
+    Shell,
+};
+
+/// Declare variables that are only visible until the current function returns
+///
+/// ```sh
+/// local [-irx] [+irx] name[=value] ...
+/// ```
+/// The options give the variables attributes the same way as `declare` does.
+#[derive(Default)]
+pub struct LocalBuiltin {}
+
+impl BuiltinCmd for LocalBuiltin {
+    fn run(
+        &self,
+        sh: &Shell,
+        _ctx: &mut Context,
+        rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        if rt.frames.is_empty() {
+            eprintln!("local: can only be used in a function");
+            return Ok(BuiltinStatus::error());
+        }
+
+        let mut attrs = Attrs::default();
+        let mut it = args.iter().peekable();
+        while let Some(arg) = it.next_if(|arg| arg.len() > 1 && arg.starts_with(['-', '+'])) {
+            if arg == "--" {
+                break;
+            }
+            let on = arg.starts_with('-');
+            for flag in arg[1..].chars() {
+                if !attrs.set(flag, on) {
+                    eprintln!("local: {}{flag}: invalid option", &arg[..1]);
+                    return Ok(BuiltinStatus(2));
+                }
+            }
+        }
+
+        let mut status = BuiltinStatus::success();
+        for arg in it {
+            let (var, val) = match arg.split_once('=') {
+                Some((var, val)) => (var, Some(val)),
+                None => (arg.as_str(), None),
+            };
+            if rt.env.var(var).is_some_and(|v| v.readonly) {
+                eprintln!("local: {var}: readonly variable");
+                status = BuiltinStatus::error();
+                continue;
+            }
+            // the local variable starts out without the attributes of the outer one
+            let saved = rt.env.var(var).cloned();
+            if let Some(frame) = rt.frames.last_mut() {
+                frame.save(var, saved);
+            }
+            rt.env.restore(var, None);
+            if let Err(e) = apply(sh, rt, var, val, attrs) {
+                eprintln!("local: {e}");
+                status = BuiltinStatus::error();
+            }
+        }
+
+        Ok(status)
+    }
+}
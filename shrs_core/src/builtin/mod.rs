@@ -1,20 +1,34 @@
 mod alias;
+mod r#break;
 mod cd;
+mod command;
 mod debug;
+mod declare;
 mod exit;
 mod export;
 mod help;
 mod history;
 mod jobs;
+mod local;
+mod readonly;
+mod r#return;
+mod set;
+mod shift;
 mod source;
+pub mod test;
+mod trap;
 mod unalias;
+mod unset;
 
 use std::collections::{hash_map::Iter, HashMap};
 
 use self::{
-    alias::AliasBuiltin, cd::CdBuiltin, debug::DebugBuiltin, exit::ExitBuiltin,
-    export::ExportBuiltin, help::HelpBuiltin, history::HistoryBuiltin, jobs::JobsBuiltin,
-    source::SourceBuiltin, unalias::UnaliasBuiltin,
+    alias::AliasBuiltin, cd::CdBuiltin, command::CommandBuiltin, debug::DebugBuiltin,
+    declare::DeclareBuiltin, exit::ExitBuiltin, export::ExportBuiltin, help::HelpBuiltin,
+    history::HistoryBuiltin, jobs::JobsBuiltin, local::LocalBuiltin, r#break::BreakBuiltin,
+    r#return::ReturnBuiltin, readonly::ReadonlyBuiltin, set::SetBuiltin, shift::ShiftBuiltin,
+    source::SourceBuiltin, test::TestBuiltin, trap::TrapBuiltin, unalias::UnaliasBuiltin,
+    unset::UnsetBuiltin,
 };
 use crate::{
     shell::{Context, Runtime},
@@ -47,6 +61,20 @@ impl BuiltinStatus {
     }
 }
 
+/// Names of the POSIX special builtins
+///
+/// Variable assignments in front of a special builtin stay set once it has run, unlike the ones
+/// in front of other commands.
+const SPECIAL_BUILTINS: &[&str] = &[
+    "break", ":", "continue", ".", "eval", "exec", "exit", "export", "readonly", "return", "set",
+    "shift", "times", "trap", "unset",
+];
+
+/// Check if the name is that of a special builtin
+pub fn is_special(name: &str) -> bool {
+    SPECIAL_BUILTINS.contains(&name)
+}
+
 // TODO could prob just be a map, to support arbritrary (user defined even) number of builtin commands
 // just provide an easy way to override the default ones
 pub struct Builtins {
@@ -67,6 +95,11 @@ impl Builtins {
     pub fn iter(&self) -> Iter<'_, &str, Box<dyn BuiltinCmd>> {
         self.builtins.iter()
     }
+
+    /// Look up a builtin by name
+    pub fn get(&self, name: &str) -> Option<&dyn BuiltinCmd> {
+        self.builtins.get(name).map(|builtin| builtin.as_ref())
+    }
 }
 
 impl Default for Builtins {
@@ -90,6 +123,18 @@ impl Default for Builtins {
                     "export",
                     Box::new(ExportBuiltin::default()) as Box<dyn BuiltinCmd>,
                 ),
+                (
+                    "readonly",
+                    Box::new(ReadonlyBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
+                (
+                    "unset",
+                    Box::new(UnsetBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
+                (
+                    "declare",
+                    Box::new(DeclareBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
                 (
                     "alias",
                     Box::new(AliasBuiltin::default()) as Box<dyn BuiltinCmd>,
@@ -98,10 +143,18 @@ impl Default for Builtins {
                     "unalias",
                     Box::new(UnaliasBuiltin::default()) as Box<dyn BuiltinCmd>,
                 ),
+                (
+                    "command",
+                    Box::new(CommandBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
                 (
                     "source",
                     Box::new(SourceBuiltin::default()) as Box<dyn BuiltinCmd>,
                 ),
+                (
+                    ".",
+                    Box::new(SourceBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
                 (
                     "jobs",
                     Box::new(JobsBuiltin::default()) as Box<dyn BuiltinCmd>,
@@ -110,6 +163,42 @@ impl Default for Builtins {
                     "help",
                     Box::new(HelpBuiltin::default()) as Box<dyn BuiltinCmd>,
                 ),
+                (
+                    "shift",
+                    Box::new(ShiftBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
+                (
+                    "local",
+                    Box::new(LocalBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
+                (
+                    "return",
+                    Box::new(ReturnBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
+                (
+                    "break",
+                    Box::new(BreakBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
+                (
+                    "continue",
+                    Box::new(BreakBuiltin { next: true }) as Box<dyn BuiltinCmd>,
+                ),
+                (
+                    "set",
+                    Box::new(SetBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
+                (
+                    "trap",
+                    Box::new(TrapBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
+                (
+                    "test",
+                    Box::new(TestBuiltin::default()) as Box<dyn BuiltinCmd>,
+                ),
+                (
+                    "[",
+                    Box::new(TestBuiltin { bracket: true }) as Box<dyn BuiltinCmd>,
+                ),
             ]),
         }
     }
@@ -0,0 +1,50 @@
+use super::{
+    declare::{declare, print_vars, Attrs},
+    BuiltinCmd, BuiltinStatus,
+};
+use crate::shell::{Context, Runtime, Shell};
+
+/// Give variables the readonly attribute, after which they can not be assigned or unset
+///
+/// ```sh
+/// readonly name[=value]...
+/// readonly -p
+/// ```
+/// Without names, or with `-p`, the readonly variables are printed as commands that recreate
+/// them.
+#[derive(Default)]
+pub struct ReadonlyBuiltin {}
+
+impl BuiltinCmd for ReadonlyBuiltin {
+    fn run(
+        &self,
+        sh: &Shell,
+        _ctx: &mut Context,
+        rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        let mut print = false;
+        let mut it = args.iter().peekable();
+        while let Some(arg) = it.next_if(|arg| arg.len() > 1 && arg.starts_with('-')) {
+            match arg.as_str() {
+                "--" => break,
+                "-p" => print = true,
+                _ => {
+                    eprintln!("readonly: {arg}: invalid option");
+                    return Ok(BuiltinStatus(2));
+                },
+            }
+        }
+        let names = it.cloned().collect::<Vec<_>>();
+
+        if print || names.is_empty() {
+            print_vars(rt, |var| var.readonly.then(|| String::from("readonly")));
+            return Ok(BuiltinStatus::success());
+        }
+        let attrs = Attrs {
+            readonly: Some(true),
+            ..Attrs::default()
+        };
+        Ok(declare(sh, rt, "readonly", &names, attrs))
+    }
+}
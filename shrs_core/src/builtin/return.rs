@@ -0,0 +1,38 @@
+use super::{BuiltinCmd, BuiltinStatus};
+use crate::{
+    shell::{Context, ControlFlow, Runtime},
+    Shell,
+};
+
+/// Return from the current function, or stop reading the file being sourced
+///
+/// The exit status is given by the argument, or the status of the last command if there is none.
+#[derive(Default)]
+pub struct ReturnBuiltin {}
+
+impl BuiltinCmd for ReturnBuiltin {
+    fn run(
+        &self,
+        _sh: &Shell,
+        _ctx: &mut Context,
+        rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        if rt.frames.is_empty() && rt.sources == 0 {
+            eprintln!("return: can only `return' from a function or sourced script");
+            return Ok(BuiltinStatus::error());
+        }
+
+        let status = match args.first().map(|n| n.parse::<i32>()) {
+            None => rt.exit_status,
+            Some(Ok(n)) => n & 0xff,
+            Some(Err(_)) => {
+                eprintln!("return: {}: numeric argument required", args[0]);
+                2
+            },
+        };
+        rt.control_flow = Some(ControlFlow::Return(status));
+
+        Ok(BuiltinStatus(status as isize))
+    }
+}
@@ -0,0 +1,99 @@
+use super::{BuiltinCmd, BuiltinStatus};
+use crate::{
+    options::OPTION_NAMES,
+    shell::{Context, Runtime},
+    Shell,
+};
+
+/// Change shell options and positional parameters
+///
+/// ```sh
+/// set [-euxfC] [-o option] [+euxfC] [+o option] [--] [arg ...]
+/// ```
+/// Options are turned on with `-` and off with `+`. Any remaining arguments replace the
+/// positional parameters. Without arguments the variables are listed, `set -o` lists the options
+/// and `set +o` prints them as commands that restore the current settings.
+#[derive(Default)]
+pub struct SetBuiltin {}
+
+impl BuiltinCmd for SetBuiltin {
+    fn run(
+        &self,
+        _sh: &Shell,
+        _ctx: &mut Context,
+        rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        if args.is_empty() {
+            let mut vars = rt.env.iter().collect::<Vec<_>>();
+            vars.sort();
+            for (var, val) in vars {
+                println!("{var}={val}");
+            }
+            return Ok(BuiltinStatus::success());
+        }
+
+        let mut it = args.iter().peekable();
+        let mut positional = false;
+        while let Some(arg) = it.next_if(|arg| arg.starts_with(['-', '+'])) {
+            let on = arg.starts_with('-');
+            match arg.as_str() {
+                "--" => {
+                    positional = true;
+                    break;
+                },
+                // a lone `-` also ends the options, `xtrace` is turned off the same as `+x`
+                "-" => {
+                    rt.options.xtrace = false;
+                    positional = true;
+                    break;
+                },
+                _ => {
+                    for flag in arg.chars().skip(1) {
+                        // `-o` takes the name of the option from the next argument
+                        let name = if flag == 'o' {
+                            match it.next() {
+                                Some(name) => name.as_str(),
+                                None => {
+                                    print_options(rt, on);
+                                    continue;
+                                },
+                            }
+                        } else {
+                            match OPTION_NAMES.iter().find(|(_, f)| *f == Some(flag)) {
+                                Some((name, _)) => name,
+                                None => {
+                                    eprintln!("set: {}{flag}: invalid option", &arg[..1]);
+                                    return Ok(BuiltinStatus(2));
+                                },
+                            }
+                        };
+                        if !rt.options.set(name, on) {
+                            eprintln!("set: {name}: invalid option name");
+                            return Ok(BuiltinStatus(2));
+                        }
+                    }
+                },
+            }
+        }
+
+        let rest = it.cloned().collect::<Vec<_>>();
+        if positional || !rest.is_empty() {
+            rt.args = rest;
+        }
+
+        Ok(BuiltinStatus::success())
+    }
+}
+
+/// List the options, or print them as commands that can be run again if `readable` is false
+fn print_options(rt: &Runtime, readable: bool) {
+    for (name, _) in OPTION_NAMES {
+        let on = rt.options.get(name) == Some(true);
+        if readable {
+            println!("{name:<15}{}", if on { "on" } else { "off" });
+        } else {
+            println!("set {}o {name}", if on { '-' } else { '+' });
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use super::{BuiltinCmd, BuiltinStatus};
+use crate::{
+    shell::{Context, Runtime},
+    Shell,
+};
+
+/// Shift the positional parameters to the left, `$2` becomes `$1` and so on
+#[derive(Default)]
+pub struct ShiftBuiltin {}
+
+impl BuiltinCmd for ShiftBuiltin {
+    fn run(
+        &self,
+        _sh: &Shell,
+        _ctx: &mut Context,
+        rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        let n = match args.first().map(|n| n.parse::<usize>()) {
+            None => 1,
+            Some(Ok(n)) => n,
+            Some(Err(_)) => {
+                eprintln!("shift: {}: numeric argument required", args[0]);
+                return Ok(BuiltinStatus::error());
+            },
+        };
+
+        if n > rt.args.len() {
+            eprintln!("shift: shift count out of range");
+            return Ok(BuiltinStatus::error());
+        }
+        rt.args.drain(..n);
+
+        Ok(BuiltinStatus::success())
+    }
+}
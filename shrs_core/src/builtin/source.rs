@@ -1,23 +1,12 @@
-use std::{
-    env,
-    fs::read_to_string,
-    path::{Path, PathBuf},
-    process::Command,
-};
-
-use lazy_static::lazy_static;
-use regex::Regex;
+use std::{fs::read_to_string, path::PathBuf};
 
 use super::{BuiltinCmd, BuiltinStatus};
 use crate::{
-    shell::{Context, Runtime},
+    shell::{Context, ControlFlow, Runtime},
+    trap::{run_trap, Condition},
     Shell,
 };
 
-lazy_static! {
-    static ref SHBANG_REGEX: Regex = Regex::new(r"#!(?P<interp>.+)").unwrap();
-}
-
 #[derive(Default)]
 pub struct SourceBuiltin {}
 
@@ -29,40 +18,33 @@ impl BuiltinCmd for SourceBuiltin {
         rt: &mut Runtime,
         args: &Vec<String>,
     ) -> anyhow::Result<BuiltinStatus> {
-        if args.len() != 1 {
+        let Some((file_path_str, script_args)) = args.split_first() else {
             return Ok(BuiltinStatus::error());
-        }
+        };
 
-        let file_path_str = args.get(0).unwrap();
         let file_path = PathBuf::from(file_path_str);
         let file_contents = read_to_string(file_path)?;
 
-        // read shbang from first line
-        let mut it = file_contents.lines();
+        // evaluate in the current shell, so that variables and functions defined by the file
+        // stay around
+        let saved_args = (!script_args.is_empty())
+            .then(|| std::mem::replace(&mut rt.args, script_args.to_vec()));
 
-        let interp = it
-            .next()
-            .and_then(|first_line| SHBANG_REGEX.captures(first_line))
-            .and_then(|capture| capture.name("interp"));
+        rt.sources += 1;
+        let res = sh.lang.eval(sh, ctx, rt, file_contents);
+        rt.sources -= 1;
 
-        match interp {
-            Some(interp) => {
-                println!("using interp {} at {}", interp.as_str(), file_path_str);
-                let mut child = Command::new(interp.as_str())
-                    .args(vec![file_path_str])
-                    .spawn()?;
-
-                // need command output here
-                // TODO temp disable this
-                // command_output(sh, ctx, rt, &mut child)?;
-
-                Ok(BuiltinStatus::success())
-            },
-            None => {
-                // otherwise evaluate with self
-
-                todo!()
-            },
+        if let Some(saved_args) = saved_args {
+            rt.args = saved_args;
         }
+        // `return` at the top level of the file only stops the file
+        if let Some(ControlFlow::Return(status)) = rt.control_flow {
+            rt.control_flow = None;
+            rt.exit_status = status;
+        }
+        run_trap(sh, ctx, rt, Condition::Return);
+        res?;
+
+        Ok(BuiltinStatus(rt.exit_status as isize))
     }
 }
@@ -0,0 +1,269 @@
+//! Conditional expressions of the `test` and `[` builtins
+//!
+//! The operators are also used by the `[[ ... ]]` compound command, which is why [unary] and
+//! [binary] are public.
+
+use std::{
+    fs::{self, Metadata},
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::PathBuf,
+};
+
+use nix::unistd::{self, AccessFlags};
+
+use super::{BuiltinCmd, BuiltinStatus};
+use crate::{
+    shell::{Context, Runtime},
+    Shell,
+};
+
+/// Evaluate a conditional expression
+///
+/// ```sh
+/// test expression
+/// [ expression ]
+/// ```
+/// Exits with `0` if the expression is true, `1` if it is false and `2` if it is malformed. With
+/// four arguments or less the rules in POSIX are followed, longer expressions are parsed with
+/// `!`, `-a`, `-o` and parentheses.
+#[derive(Default)]
+pub struct TestBuiltin {
+    /// Invoked as `[`, which requires a closing `]`
+    pub bracket: bool,
+}
+
+impl BuiltinCmd for TestBuiltin {
+    fn run(
+        &self,
+        _sh: &Shell,
+        _ctx: &mut Context,
+        rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        let (name, args) = if self.bracket {
+            match args.split_last() {
+                Some((last, args)) if last == "]" => ("[", args),
+                _ => {
+                    eprintln!("[: missing `]'");
+                    return Ok(BuiltinStatus(2));
+                },
+            }
+        } else {
+            ("test", &args[..])
+        };
+
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        match eval(rt, &args) {
+            Ok(true) => Ok(BuiltinStatus::success()),
+            Ok(false) => Ok(BuiltinStatus::error()),
+            Err(e) => {
+                eprintln!("{name}: {e}");
+                Ok(BuiltinStatus(2))
+            },
+        }
+    }
+}
+
+/// Unary operators, besides the file tests this includes `-n`, `-z`, `-o` and `-v`
+pub const UNARY_OPS: &[&str] = &[
+    "-b", "-c", "-d", "-e", "-f", "-g", "-h", "-k", "-L", "-n", "-o", "-O", "-G", "-p", "-r", "-s",
+    "-S", "-t", "-u", "-v", "-w", "-x", "-z",
+];
+
+/// Binary operators comparing strings, integers and files
+pub const BINARY_OPS: &[&str] = &[
+    "=", "==", "!=", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+/// Evaluate the arguments of `test`
+fn eval(rt: &Runtime, args: &[&str]) -> Result<bool, String> {
+    match args {
+        [] => Ok(false),
+        [arg] => Ok(!arg.is_empty()),
+        [lhs, op, rhs] if BINARY_OPS.contains(op) => binary(rt, lhs, op, rhs),
+        ["!", rest @ ..] if args.len() <= 4 => eval(rt, rest).map(|res| !res),
+        [op, arg] => unary(rt, op, arg).ok_or_else(|| format!("{op}: unary operator expected")),
+        [lhs, "-a", rhs] => Ok(!lhs.is_empty() && !rhs.is_empty()),
+        [lhs, "-o", rhs] => Ok(!lhs.is_empty() || !rhs.is_empty()),
+        ["(", inner @ .., ")"] if args.len() <= 4 => eval(rt, inner),
+        [_, op, _] => Err(format!("{op}: binary operator expected")),
+        _ => {
+            let mut parser = Parser { rt, args, pos: 0 };
+            let res = parser.or()?;
+            match parser.args.get(parser.pos) {
+                Some(arg) => Err(format!("{arg}: unexpected argument")),
+                None => Ok(res),
+            }
+        },
+    }
+}
+
+/// Recursive descent parser for expressions longer than four arguments
+///
+/// `-o` has a lower precedence than `-a`, which is lower than `!`.
+struct Parser<'a> {
+    rt: &'a Runtime,
+    args: &'a [&'a str],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        let arg = self.args.get(self.pos).copied();
+        self.pos += 1;
+        arg
+    }
+
+    fn peek(&self, offset: usize) -> Option<&'a str> {
+        self.args.get(self.pos + offset).copied()
+    }
+
+    fn or(&mut self) -> Result<bool, String> {
+        let mut res = self.and()?;
+        while self.peek(0) == Some("-o") {
+            self.pos += 1;
+            res |= self.and()?;
+        }
+        Ok(res)
+    }
+
+    fn and(&mut self) -> Result<bool, String> {
+        let mut res = self.not()?;
+        while self.peek(0) == Some("-a") {
+            self.pos += 1;
+            res &= self.not()?;
+        }
+        Ok(res)
+    }
+
+    fn not(&mut self) -> Result<bool, String> {
+        if self.peek(0) == Some("!") {
+            self.pos += 1;
+            return self.not().map(|res| !res);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<bool, String> {
+        let Some(arg) = self.next() else {
+            return Err(String::from("argument expected"));
+        };
+        match (self.peek(0), self.peek(1)) {
+            (Some(op), Some(rhs)) if BINARY_OPS.contains(&op) => {
+                self.pos += 2;
+                binary(self.rt, arg, op, rhs)
+            },
+            _ if arg == "(" => {
+                let res = self.or()?;
+                match self.next() {
+                    Some(")") => Ok(res),
+                    _ => Err(String::from("`)' expected")),
+                }
+            },
+            (Some(operand), _) if UNARY_OPS.contains(&arg) => {
+                self.pos += 1;
+                Ok(unary(self.rt, arg, operand).unwrap_or(false))
+            },
+            _ => Ok(!arg.is_empty()),
+        }
+    }
+}
+
+/// Evaluate a unary operator, returns [None] if `op` is not one
+pub fn unary(rt: &Runtime, op: &str, arg: &str) -> Option<bool> {
+    let res = match op {
+        "-n" => !arg.is_empty(),
+        "-z" => arg.is_empty(),
+        "-o" => rt.options.get(arg) == Some(true),
+        "-v" => rt.env.get(arg).is_some(),
+        "-t" => arg
+            .parse()
+            .is_ok_and(|fd| unistd::isatty(fd).unwrap_or(false)),
+        "-h" | "-L" => symlink_metadata(rt, arg).is_some_and(|m| m.file_type().is_symlink()),
+        "-r" => access(rt, arg, AccessFlags::R_OK),
+        "-w" => access(rt, arg, AccessFlags::W_OK),
+        "-x" => access(rt, arg, AccessFlags::X_OK),
+        _ => {
+            let test: fn(&Metadata) -> bool = match op {
+                "-b" => |m| m.file_type().is_block_device(),
+                "-c" => |m| m.file_type().is_char_device(),
+                "-d" => |m| m.is_dir(),
+                "-e" => |_| true,
+                "-f" => |m| m.is_file(),
+                "-g" => |m| m.mode() & 0o2000 != 0,
+                "-k" => |m| m.mode() & 0o1000 != 0,
+                "-p" => |m| m.file_type().is_fifo(),
+                "-s" => |m| m.len() > 0,
+                "-S" => |m| m.file_type().is_socket(),
+                "-u" => |m| m.mode() & 0o4000 != 0,
+                "-O" => |m| m.uid() == unistd::geteuid().as_raw(),
+                "-G" => |m| m.gid() == unistd::getegid().as_raw(),
+                _ => return None,
+            };
+            metadata(rt, arg).is_some_and(|m| test(&m))
+        },
+    };
+    Some(res)
+}
+
+/// Evaluate a binary operator
+///
+/// Fails if `op` is not one of [BINARY_OPS] or an integer comparison is given something that is
+/// not an integer.
+pub fn binary(rt: &Runtime, lhs: &str, op: &str, rhs: &str) -> Result<bool, String> {
+    let res = match op {
+        "=" | "==" => lhs == rhs,
+        "!=" => lhs != rhs,
+        "<" => lhs < rhs,
+        ">" => lhs > rhs,
+        "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge" => {
+            let (lhs, rhs) = (integer(lhs)?, integer(rhs)?);
+            match op {
+                "-eq" => lhs == rhs,
+                "-ne" => lhs != rhs,
+                "-lt" => lhs < rhs,
+                "-le" => lhs <= rhs,
+                "-gt" => lhs > rhs,
+                _ => lhs >= rhs,
+            }
+        },
+        // a file that exists is newer than one that does not
+        "-nt" | "-ot" => {
+            let modified = |path| metadata(rt, path).and_then(|m| m.modified().ok());
+            let (lhs, rhs) = (modified(lhs), modified(rhs));
+            match op {
+                "-nt" => lhs.is_some() && (rhs.is_none() || lhs > rhs),
+                _ => rhs.is_some() && (lhs.is_none() || lhs < rhs),
+            }
+        },
+        "-ef" => match (metadata(rt, lhs), metadata(rt, rhs)) {
+            (Some(lhs), Some(rhs)) => lhs.dev() == rhs.dev() && lhs.ino() == rhs.ino(),
+            _ => false,
+        },
+        op => return Err(format!("{op}: binary operator expected")),
+    };
+    Ok(res)
+}
+
+fn integer(arg: &str) -> Result<i64, String> {
+    arg.trim()
+        .parse()
+        .map_err(|_| format!("{arg}: integer expression expected"))
+}
+
+/// Paths are relative to the working directory of the shell, an empty path never exists
+fn path(rt: &Runtime, arg: &str) -> Option<PathBuf> {
+    (!arg.is_empty()).then(|| rt.working_dir.join(arg))
+}
+
+fn metadata(rt: &Runtime, arg: &str) -> Option<Metadata> {
+    fs::metadata(path(rt, arg)?).ok()
+}
+
+fn symlink_metadata(rt: &Runtime, arg: &str) -> Option<Metadata> {
+    fs::symlink_metadata(path(rt, arg)?).ok()
+}
+
+fn access(rt: &Runtime, arg: &str, mode: AccessFlags) -> bool {
+    path(rt, arg).is_some_and(|path| unistd::access(&path, mode).is_ok())
+}
@@ -0,0 +1,97 @@
+use super::{BuiltinCmd, BuiltinStatus};
+use crate::{
+    shell::{Context, Runtime},
+    trap::{Condition, SIGNALS},
+    Shell,
+};
+
+/// Set commands to run when the shell receives a signal
+///
+/// ```sh
+/// trap [-p [condition ...]]
+/// trap -l
+/// trap action condition ...
+/// ```
+/// An empty action ignores the signal, an action of `-` or a first condition that is a number
+/// resets the conditions to their default behaviour.
+#[derive(Default)]
+pub struct TrapBuiltin {}
+
+impl BuiltinCmd for TrapBuiltin {
+    fn run(
+        &self,
+        sh: &Shell,
+        _ctx: &mut Context,
+        rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        let args = match args.first().map(String::as_str) {
+            Some("--") => &args[1..],
+            _ => &args[..],
+        };
+
+        match args.first().map(String::as_str) {
+            None => return Ok(print_traps(rt, &[])),
+            Some("-p") => return Ok(print_traps(rt, &args[1..])),
+            Some("-l") => {
+                for (name, signal) in SIGNALS {
+                    println!("{signal:2}) SIG{name}");
+                }
+                return Ok(BuiltinStatus::success());
+            },
+            _ => {},
+        }
+
+        let (action, conditions) = match args.split_first() {
+            Some((action, conditions)) if action == "-" => (None, conditions),
+            Some((first, _)) if first.parse::<u32>().is_ok() => (None, args),
+            Some((action, conditions)) => (Some(action.as_str()), conditions),
+            None => unreachable!(),
+        };
+        if conditions.is_empty() {
+            eprintln!("trap: usage: trap [-lp] [[action] condition ...]");
+            return Ok(BuiltinStatus(2));
+        }
+
+        let mut status = BuiltinStatus::success();
+        for name in conditions {
+            let Some(condition) = Condition::parse(name) else {
+                eprintln!("trap: {name}: invalid signal specification");
+                status = BuiltinStatus::error();
+                continue;
+            };
+            match action {
+                Some(action) => {
+                    if let Condition::Signal(signal) = condition {
+                        sh.signals.catch(signal)?;
+                    }
+                    rt.traps.set(condition, action);
+                },
+                None => rt.traps.remove(condition),
+            }
+        }
+        Ok(status)
+    }
+}
+
+/// Print traps as commands that set them again, only the given conditions if there are any
+fn print_traps(rt: &Runtime, names: &[String]) -> BuiltinStatus {
+    let mut conditions = vec![];
+    for name in names {
+        match Condition::parse(name) {
+            Some(condition) => conditions.push(condition),
+            None => {
+                eprintln!("trap: {name}: invalid signal specification");
+                return BuiltinStatus::error();
+            },
+        }
+    }
+
+    for (condition, action) in rt.traps.iter() {
+        if conditions.is_empty() || conditions.contains(&condition) {
+            let action = action.replace('\'', r"'\''");
+            println!("trap -- '{action}' {}", condition.name());
+        }
+    }
+    BuiltinStatus::success()
+}
@@ -0,0 +1,54 @@
+use super::{BuiltinCmd, BuiltinStatus};
+use crate::{
+    shell::{Context, Runtime},
+    util::is_name,
+    Shell,
+};
+
+/// Remove variables or functions
+///
+/// ```sh
+/// unset [-v] name...
+/// unset -f name...
+/// ```
+/// Names that are not set are ignored, readonly variables can not be unset.
+#[derive(Default)]
+pub struct UnsetBuiltin {}
+
+impl BuiltinCmd for UnsetBuiltin {
+    fn run(
+        &self,
+        _sh: &Shell,
+        _ctx: &mut Context,
+        rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        let mut functions = false;
+        let mut it = args.iter().peekable();
+        while let Some(arg) = it.next_if(|arg| arg.len() > 1 && arg.starts_with('-')) {
+            match arg.as_str() {
+                "--" => break,
+                "-f" => functions = true,
+                "-v" => functions = false,
+                _ => {
+                    eprintln!("unset: {arg}: invalid option");
+                    return Ok(BuiltinStatus(2));
+                },
+            }
+        }
+
+        let mut status = BuiltinStatus::success();
+        for name in it {
+            if functions {
+                rt.functions.remove(name);
+            } else if !is_name(name) {
+                eprintln!("unset: {name}: not a valid identifier");
+                status = BuiltinStatus::error();
+            } else if let Err(e) = rt.env.remove(name) {
+                eprintln!("unset: {e}");
+                status = BuiltinStatus::error();
+            }
+        }
+        Ok(status)
+    }
+}
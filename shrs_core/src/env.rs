@@ -1,12 +1,37 @@
-//! Environment variables
+//! Shell variables
+//!
+//! Every variable has a value and a set of attributes. Only variables with the exported attribute
+//! are passed to the environment of commands started by the shell. Names are case sensitive.
 
 use std::collections::HashMap;
 
-/// Set and query environment variables
-// currently just wrapper around hashmap
-#[derive(Clone)]
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum EnvError {
+    #[error("{0}: readonly variable")]
+    Readonly(String),
+    #[error("{0}: {1}: integer expression expected")]
+    NotInteger(String, String),
+}
+
+/// Value and attributes of a variable
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Var {
+    /// Value of the variable, [None] if it only has attributes, such as after `export name`
+    pub value: Option<String>,
+    /// Passed to the environment of commands
+    pub exported: bool,
+    /// Can not be assigned or unset
+    pub readonly: bool,
+    /// Only holds integers
+    pub integer: bool,
+}
+
+/// Set and query shell variables
+#[derive(Clone, Default)]
 pub struct Env {
-    vars: HashMap<String, String>,
+    vars: HashMap<String, Var>,
 }
 
 impl Env {
@@ -19,44 +44,171 @@ impl Env {
 
     /// Load environment variables into shrs
     ///
-    /// Useful if calling shrs from another shell and some environment variables are already set
-    // could inherit all from calling shell for now
+    /// Useful if calling shrs from another shell and some environment variables are already set.
+    /// The variables are exported so they are passed on to commands.
     pub fn load(&mut self) {
         for (var, val) in std::env::vars() {
-            self.set(&var, &val);
+            self.vars.insert(
+                var,
+                Var {
+                    value: Some(val),
+                    exported: true,
+                    ..Var::default()
+                },
+            );
         }
     }
 
-    /// Query environment variable
+    /// Query the value of a variable
     pub fn get(&self, var: &str) -> Option<&String> {
-        self.vars.get(&var.to_ascii_uppercase())
+        self.vars.get(var)?.value.as_ref()
     }
 
-    /// Set an environment variable
-    ///
-    /// If the variable was already set it is overridden. Environment variables are case
-    /// insensitive
-    pub fn set(&mut self, var: &str, val: &str) {
-        self.vars.insert(var.to_ascii_uppercase(), val.into());
+    /// Query the value and attributes of a variable
+    pub fn var(&self, var: &str) -> Option<&Var> {
+        self.vars.get(var)
     }
 
-    /// Obtain a hashmap of all the environment variables
-    pub fn all(&self) -> &HashMap<String, String> {
-        &self.vars
+    /// Set a variable
+    ///
+    /// If the variable was already set it is overridden and keeps its attributes. Fails if the
+    /// variable is readonly, or if it is an integer variable and the value is not an integer.
+    pub fn set(&mut self, var: &str, val: &str) -> Result<(), EnvError> {
+        let entry = self.vars.entry(var.to_string()).or_default();
+        if entry.readonly {
+            return Err(EnvError::Readonly(var.to_string()));
+        }
+        let val = if entry.integer {
+            val.trim()
+                .parse::<i64>()
+                .map_err(|_| EnvError::NotInteger(var.to_string(), val.to_string()))?
+                .to_string()
+        } else {
+            val.to_string()
+        };
+        entry.value = Some(val);
+        Ok(())
     }
 
-    /// Unset an environment variable
+    /// Unset a variable, removing its attributes as well
     ///
-    /// If the environment variable was already not set, it is a NOOP
-    pub fn remove(&mut self, var: &str) {
+    /// If the variable was already not set, it is a NOOP. Fails if the variable is readonly.
+    pub fn remove(&mut self, var: &str) -> Result<(), EnvError> {
+        if self.vars.get(var).is_some_and(|v| v.readonly) {
+            return Err(EnvError::Readonly(var.to_string()));
+        }
         self.vars.remove(var);
+        Ok(())
+    }
+
+    /// Give a variable the exported attribute or take it away
+    pub fn export(&mut self, var: &str, exported: bool) {
+        if exported || self.vars.contains_key(var) {
+            self.vars.entry(var.to_string()).or_default().exported = exported;
+        }
+    }
+
+    /// Give a variable the readonly attribute, which can not be taken away
+    pub fn set_readonly(&mut self, var: &str) {
+        self.vars.entry(var.to_string()).or_default().readonly = true;
+    }
+
+    /// Give a variable the integer attribute or take it away
+    ///
+    /// The current value is kept as is, the attribute only applies to the values assigned after.
+    pub fn set_integer(&mut self, var: &str, integer: bool) -> Result<(), EnvError> {
+        let entry = self.vars.entry(var.to_string()).or_default();
+        if entry.readonly && entry.integer != integer {
+            return Err(EnvError::Readonly(var.to_string()));
+        }
+        entry.integer = integer;
+        Ok(())
+    }
+
+    /// Put back a variable as it was saved by [Env::var], ignoring the readonly attribute
+    ///
+    /// Used to restore variables that were only changed temporarily, such as local variables.
+    pub fn restore(&mut self, var: &str, saved: Option<Var>) {
+        match saved {
+            Some(saved) => self.vars.insert(var.to_string(), saved),
+            None => self.vars.remove(var),
+        };
+    }
+
+    /// Iterate over the variables that are set along with their values
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.vars
+            .iter()
+            .filter_map(|(var, v)| Some((var, v.value.as_ref()?)))
+    }
+
+    /// Iterate over every variable, including the ones that only have attributes
+    pub fn vars(&self) -> impl Iterator<Item = (&String, &Var)> {
+        self.vars.iter()
+    }
+
+    /// Variables that are passed to the environment of commands
+    pub fn exported(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.vars
+            .iter()
+            .filter(|(_, v)| v.exported)
+            .filter_map(|(var, v)| Some((var, v.value.as_ref()?)))
     }
 }
 
+/// The variables are exported, like the ones read by [Env::load]
 impl FromIterator<(&'static str, &'static str)> for Env {
     fn from_iter<T: IntoIterator<Item = (&'static str, &'static str)>>(iter: T) -> Self {
+        let vars = iter.into_iter().map(|(k, v)| {
+            let var = Var {
+                value: Some(v.to_owned()),
+                exported: true,
+                ..Var::default()
+            };
+            (k.to_owned(), var)
+        });
         Env {
-            vars: HashMap::from_iter(iter.into_iter().map(|(k, v)| (k.to_owned(), v.to_owned()))),
+            vars: HashMap::from_iter(vars),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Env, EnvError};
+
+    #[test]
+    fn attributes() {
+        let mut env = Env::new();
+        env.set("path", "a").unwrap();
+        env.set("PATH", "b").unwrap();
+        assert_eq!(env.get("path").unwrap(), "a");
+        assert_eq!(env.get("PATH").unwrap(), "b");
+
+        env.export("path", true);
+        env.export("UNSET", true);
+        assert_eq!(
+            env.exported().collect::<Vec<_>>(),
+            vec![(&String::from("path"), &String::from("a"))]
+        );
+
+        env.set("N", "x").unwrap();
+        env.set_integer("N", true).unwrap();
+        assert_eq!(env.get("N").unwrap(), "x");
+        env.set("N", " 12").unwrap();
+        assert_eq!(env.get("N").unwrap(), "12");
+        assert!(matches!(env.set("N", "x"), Err(EnvError::NotInteger(..))));
+
+        env.set_readonly("N");
+        assert_eq!(
+            env.set("N", "1"),
+            Err(EnvError::Readonly(String::from("N")))
+        );
+        assert!(env.remove("N").is_err());
+        let saved = env.var("N").cloned();
+        env.restore("N", None);
+        assert!(env.get("N").is_none());
+        env.restore("N", saved);
+        assert_eq!(env.get("N").unwrap(), "12");
+    }
+}
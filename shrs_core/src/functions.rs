@@ -0,0 +1,71 @@
+//! Shell functions
+
+use std::{any::Any, collections::HashMap, rc::Rc};
+
+use crate::env::Var;
+
+/// Functions defined while the shell is running
+///
+/// The body of a function is stored type erased, since how it is represented depends on the
+/// [crate::Lang] that defined it. Cloning is cheap as bodies are reference counted, which lets
+/// subshells inherit the functions of their parent.
+#[derive(Clone, Default)]
+pub struct Functions {
+    functions: HashMap<String, Rc<dyn Any>>,
+}
+
+impl Functions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetch the body of a function by name
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Any>> {
+        self.functions.get(name).cloned()
+    }
+
+    /// Define a function, replacing any previous definition with the same name
+    pub fn insert(&mut self, name: &str, body: Rc<dyn Any>) {
+        self.functions.insert(name.to_string(), body);
+    }
+
+    /// Remove a function
+    ///
+    /// NOOP if the function was not defined
+    pub fn remove(&mut self, name: &str) {
+        self.functions.remove(name);
+    }
+
+    /// Names of all defined functions
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.functions.keys()
+    }
+}
+
+/// Scope of a function call
+///
+/// Variables declared with `local` are stored in the same place as every other variable, the
+/// frame remembers the values they had before so they can be restored once the function returns.
+#[derive(Clone, Default)]
+pub struct Frame {
+    saved: HashMap<String, Option<Var>>,
+}
+
+impl Frame {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember the value and attributes a variable had before it was made local
+    ///
+    /// Only the first call for a given variable has an effect, so the value from outside the
+    /// function is kept.
+    pub fn save(&mut self, var: &str, val: Option<Var>) {
+        self.saved.entry(var.to_string()).or_insert(val);
+    }
+
+    /// Values to restore once the function returns
+    pub fn saved(self) -> HashMap<String, Option<Var>> {
+        self.saved
+    }
+}
@@ -14,4 +14,14 @@ pub trait Lang {
         rt: &mut Runtime,
         cmd: String,
     ) -> anyhow::Result<()>;
+
+    /// Evaluate an arithmetic expression, used by builtins such as `declare` to assign variables
+    /// with the integer attribute
+    ///
+    /// Languages without arithmetic only accept plain integers.
+    fn eval_arith(&self, _rt: &mut Runtime, expr: &str) -> anyhow::Result<i64> {
+        expr.trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("{expr}: integer expression expected"))
+    }
 }
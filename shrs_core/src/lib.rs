@@ -16,27 +16,37 @@ pub mod builtin;
 pub use builtin::BuiltinStatus;
 
 mod env;
-pub use env::Env;
+pub use env::{Env, EnvError, Var};
 
 pub mod hooks;
 
 pub mod prompt;
 
 mod shell;
-pub use shell::{dummy_child, Context, Runtime, Shell};
+pub use shell::{dummy_child, Context, ControlFlow, Runtime, Shell};
 
 mod signal;
 pub use signal::Signals;
 
+pub mod trap;
+
 mod theme;
 pub use theme::Theme;
 
 mod state;
 pub use state::State;
 
+mod options;
+pub use options::{EditMode, Options, OPTION_NAMES};
+
+mod functions;
+pub use functions::{Frame, Functions};
+
 mod lang;
 pub use lang::Lang;
 
+pub mod util;
+
 mod jobs;
 // TODO temp re-export anyhow
 pub use anyhow;
@@ -0,0 +1,126 @@
+//! Shell options that change how commands are evaluated
+
+/// Options that can be toggled while the shell is running
+///
+/// Like the rest of [crate::Runtime], options are inherited by subshells but changes made in a
+/// subshell do not affect the parent.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Exit as soon as a command fails, unless its status is being tested
+    pub errexit: bool,
+    /// Expanding a parameter that is not set is an error
+    pub nounset: bool,
+    /// Print each command to stderr, prefixed by `$PS4`, before running it
+    pub xtrace: bool,
+    /// The status of a pipeline is that of the last command to fail instead of the last command
+    pub pipefail: bool,
+    /// Redirecting with `>` does not overwrite existing files, `>|` still does
+    pub noclobber: bool,
+    /// Disable pathname expansion
+    pub noglob: bool,
+    /// Patterns that match no files expand to nothing instead of being left as is
+    pub nullglob: bool,
+    /// Patterns that match no files are an error, takes priority over `nullglob`
+    pub failglob: bool,
+    /// `**` in a pattern matches any number of directories
+    pub globstar: bool,
+    /// Key bindings used by the line editor
+    pub edit_mode: EditMode,
+}
+
+/// Style of key bindings for the line editor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EditMode {
+    /// Modal editing, starting in insert mode
+    #[default]
+    Vi,
+    /// Single mode with `Ctrl` based bindings
+    Emacs,
+}
+
+/// Options that can be toggled with `set -o`, along with the single letter flag if there is one
+pub const OPTION_NAMES: &[(&str, Option<char>)] = &[
+    ("errexit", Some('e')),
+    ("nounset", Some('u')),
+    ("xtrace", Some('x')),
+    ("noglob", Some('f')),
+    ("noclobber", Some('C')),
+    ("pipefail", None),
+    ("nullglob", None),
+    ("failglob", None),
+    ("globstar", None),
+    ("vi", None),
+    ("emacs", None),
+];
+
+impl Options {
+    /// Look up an option by the name used with `set -o`
+    pub fn get(&self, name: &str) -> Option<bool> {
+        let value = match name {
+            "errexit" => self.errexit,
+            "nounset" => self.nounset,
+            "xtrace" => self.xtrace,
+            "noglob" => self.noglob,
+            "noclobber" => self.noclobber,
+            "pipefail" => self.pipefail,
+            "nullglob" => self.nullglob,
+            "failglob" => self.failglob,
+            "globstar" => self.globstar,
+            "vi" => self.edit_mode == EditMode::Vi,
+            "emacs" => self.edit_mode == EditMode::Emacs,
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Change an option by the name used with `set -o`, returns false if there is no such option
+    ///
+    /// Turning off the current edit mode leaves it unchanged, since one of them is always used.
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        match name {
+            "errexit" => self.errexit = value,
+            "nounset" => self.nounset = value,
+            "xtrace" => self.xtrace = value,
+            "noglob" => self.noglob = value,
+            "noclobber" => self.noclobber = value,
+            "pipefail" => self.pipefail = value,
+            "nullglob" => self.nullglob = value,
+            "failglob" => self.failglob = value,
+            "globstar" => self.globstar = value,
+            "vi" if value => self.edit_mode = EditMode::Vi,
+            "emacs" if value => self.edit_mode = EditMode::Emacs,
+            "vi" | "emacs" => {},
+            _ => return false,
+        }
+        true
+    }
+
+    /// Single letter flags of the options that are on, the value of `$-`
+    pub fn flags(&self) -> String {
+        OPTION_NAMES
+            .iter()
+            .filter_map(|(name, flag)| flag.filter(|_| self.get(name) == Some(true)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EditMode, Options};
+
+    #[test]
+    fn set_by_name() {
+        let mut options = Options::default();
+        assert!(options.set("errexit", true));
+        assert!(options.set("noclobber", true));
+        assert!(!options.set("nonexistent", true));
+        assert_eq!(options.get("errexit"), Some(true));
+        assert_eq!(options.get("xtrace"), Some(false));
+        assert_eq!(options.flags(), "eC");
+
+        assert!(options.set("emacs", true));
+        assert_eq!(options.edit_mode, EditMode::Emacs);
+        assert!(options.set("emacs", false));
+        assert_eq!(options.get("emacs"), Some(true));
+    }
+}
@@ -1,33 +1,21 @@
 //! Implementation and runtime for POSIX shell
 
-use std::{
-    cell::RefCell,
-    collections::HashMap,
-    env,
-    fs::File,
-    io::{stdin, stdout, BufRead, BufReader, BufWriter, Write},
-    os::unix::process::CommandExt,
-    path::{Path, PathBuf},
-    process::{Child, Output, Stdio},
-    rc::Rc,
-    time::Instant,
-};
+use std::{cell::RefCell, fs::File, io::BufWriter, path::PathBuf, process::Child, time::Instant};
 
-use anyhow::anyhow;
-use crossterm::{style::Print, QueueableCommand};
-use lazy_static::lazy_static;
-use shrs_job::JobManager;
-use thiserror::Error;
+use shrs_job::{JobManager, Process};
 
 use crate::{
     alias::Alias,
     builtin::Builtins,
     env::Env,
-    hooks::{AfterCommandCtx, BeforeCommandCtx, Hooks, JobExitCtx, StartupCtx},
-    jobs::{ExitStatus, Jobs},
+    functions::{Frame, Functions},
+    hooks::Hooks,
+    jobs::Jobs,
+    options::Options,
     signal::Signals,
     state::State,
     theme::Theme,
+    trap::Traps,
     Lang,
 };
 
@@ -59,6 +47,9 @@ pub struct Context {
     pub state: State,
     pub jobs: Jobs,
     pub startup_time: Instant,
+    /// Process substitutions made for the commands being evaluated, as the shell's end of the
+    /// pipe along with the process at the other end
+    pub proc_substs: Vec<(File, Box<dyn Process>)>,
 }
 
 /// Runtime context for the shell
@@ -77,8 +68,69 @@ pub struct Runtime {
     pub args: Vec<String>,
     /// Exit status of most recent pipeline
     pub exit_status: i32,
-    // /// List of defined functions
-    // pub functions: HashMap<String, Box<ast::Command>>,
+    /// Exit status of every process in the most recent foreground pipeline, the value of
+    /// `PIPESTATUS`
+    pub pipe_status: Vec<i32>,
+    /// Text matched by the most recent `=~` followed by the text matched by each of its groups,
+    /// the elements of `BASH_REMATCH`
+    pub rematch: Vec<String>,
+    /// Process id of the shell, subshells keep the value of their parent
+    pub pid: u32,
+    /// Process id of the most recent background job
+    pub last_background: Option<u32>,
+    /// Shell options
+    pub options: Options,
+    /// List of defined functions
+    pub functions: Functions,
+    /// Scopes of the functions currently being executed, innermost last
+    pub frames: Vec<Frame>,
+    /// Set by builtins such as `return` to stop executing the current list of commands
+    pub control_flow: Option<ControlFlow>,
+    /// Number of loops the current command is nested in, not counting the ones outside of the
+    /// current function
+    pub loops: usize,
+    /// Number of files being read by `source`
+    pub sources: usize,
+    /// Number of conditions, such as the one of an `if`, that are being evaluated. `errexit` is
+    /// ignored while this is non zero
+    pub conditions: usize,
+    /// Commands to run on signals and other events
+    pub traps: Traps,
+}
+
+/// Request to leave the commands currently being executed early
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlFlow {
+    /// Return from the current function or sourced file with the given exit status
+    Return(i32),
+    /// Leave the given number of enclosing loops
+    Break(usize),
+    /// Start the next iteration of the enclosing loop the given number of levels out, the
+    /// innermost loop being `1`
+    Continue(usize),
+    /// Exit the shell with the given exit status
+    Exit(i32),
+}
+
+impl Runtime {
+    /// Enter a new function scope, the positional parameters are replaced by `args`
+    ///
+    /// Returns the positional parameters of the caller, which should be passed to
+    /// [Runtime::pop_frame].
+    pub fn push_frame(&mut self, args: Vec<String>) -> Vec<String> {
+        self.frames.push(Frame::new());
+        std::mem::replace(&mut self.args, args)
+    }
+
+    /// Leave the current function scope, restoring local variables and positional parameters
+    pub fn pop_frame(&mut self, args: Vec<String>) {
+        self.args = args;
+        if let Some(frame) = self.frames.pop() {
+            for (var, saved) in frame.saved() {
+                self.env.restore(&var, saved);
+            }
+        }
+    }
 }
 
 // some utilitiy commands that should be cleaned up or moved later
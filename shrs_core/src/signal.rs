@@ -1,9 +1,18 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use signal_hook::{consts::*, flag};
 
 pub struct Signals {
     pub int: Arc<AtomicBool>,
+    /// Flags of the signals caught because a trap was set for them, raised when the signal arrives
+    caught: RefCell<HashMap<i32, Arc<AtomicBool>>>,
 }
 
 impl Signals {
@@ -12,6 +21,35 @@ impl Signals {
 
         flag::register(SIGINT, Arc::clone(&int))?;
 
-        Ok(Self { int })
+        Ok(Self {
+            int,
+            caught: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// Start catching a signal, so it is reported by [Signals::take_pending] instead of taking
+    /// its default action
+    ///
+    /// Catching a signal that is already caught does nothing.
+    pub fn catch(&self, signal: i32) -> Result<(), std::io::Error> {
+        if let Entry::Vacant(entry) = self.caught.borrow_mut().entry(signal) {
+            let pending = Arc::new(AtomicBool::new(false));
+            flag::register(signal, Arc::clone(&pending))?;
+            entry.insert(pending);
+        }
+        Ok(())
+    }
+
+    /// Caught signals that arrived since the last call, in increasing order
+    pub fn take_pending(&self) -> Vec<i32> {
+        let mut pending = self
+            .caught
+            .borrow()
+            .iter()
+            .filter(|(_, flag)| flag.swap(false, Ordering::Relaxed))
+            .map(|(signal, _)| *signal)
+            .collect::<Vec<_>>();
+        pending.sort();
+        pending
     }
 }
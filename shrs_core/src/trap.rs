@@ -0,0 +1,170 @@
+//! Commands that are run when the shell receives a signal or reaches certain points
+//!
+//! Besides signals, traps can be set on the pseudo-signals `EXIT` (the shell exits), `ERR` (a
+//! command fails in a place where `errexit` would apply), `DEBUG` (before every simple command)
+//! and `RETURN` (a function or sourced file finishes).
+
+use std::{collections::BTreeMap, io::Write};
+
+use signal_hook::{consts::*, low_level::emulate_default_handler};
+
+use crate::{
+    shell::{Context, Runtime},
+    Shell,
+};
+
+/// Signals that traps can be set for, by name without the `SIG` prefix
+pub const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", SIGHUP),
+    ("INT", SIGINT),
+    ("QUIT", SIGQUIT),
+    ("USR1", SIGUSR1),
+    ("USR2", SIGUSR2),
+    ("TERM", SIGTERM),
+    ("WINCH", SIGWINCH),
+];
+
+/// Condition that triggers a trap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Condition {
+    Exit,
+    Signal(i32),
+    Err,
+    Debug,
+    Return,
+}
+
+impl Condition {
+    /// Parse a condition as accepted by the `trap` builtin
+    ///
+    /// Signals can be given by name, with or without the `SIG` prefix, or by number. `0` is the
+    /// same as `EXIT`.
+    pub fn parse(name: &str) -> Option<Condition> {
+        let upper = name.to_ascii_uppercase();
+        let upper = upper.strip_prefix("SIG").unwrap_or(&upper);
+        match upper {
+            "EXIT" | "0" => return Some(Condition::Exit),
+            "ERR" => return Some(Condition::Err),
+            "DEBUG" => return Some(Condition::Debug),
+            "RETURN" => return Some(Condition::Return),
+            _ => {},
+        }
+        SIGNALS
+            .iter()
+            .find(|(sig_name, signal)| *sig_name == upper || signal.to_string() == upper)
+            .map(|(_, signal)| Condition::Signal(*signal))
+    }
+
+    /// Name of the condition, as printed by `trap -p`
+    pub fn name(&self) -> String {
+        match self {
+            Condition::Exit => String::from("EXIT"),
+            Condition::Err => String::from("ERR"),
+            Condition::Debug => String::from("DEBUG"),
+            Condition::Return => String::from("RETURN"),
+            Condition::Signal(signal) => SIGNALS
+                .iter()
+                .find(|(_, s)| s == signal)
+                .map_or_else(|| signal.to_string(), |(name, _)| format!("SIG{name}")),
+        }
+    }
+}
+
+/// Traps that are currently set
+///
+/// An empty action means the signal is ignored.
+#[derive(Debug, Clone, Default)]
+pub struct Traps {
+    actions: BTreeMap<Condition, String>,
+    /// Set while a trap is running, traps are not triggered by the commands of another trap
+    running: bool,
+}
+
+impl Traps {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, condition: Condition) -> Option<&str> {
+        self.actions.get(&condition).map(String::as_str)
+    }
+
+    pub fn set(&mut self, condition: Condition, action: &str) {
+        self.actions.insert(condition, action.to_string());
+    }
+
+    /// Reset the condition to its default behaviour
+    pub fn remove(&mut self, condition: Condition) {
+        self.actions.remove(&condition);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Condition, &str)> {
+        self.actions.iter().map(|(c, a)| (*c, a.as_str()))
+    }
+
+    /// Traps of a subshell, signals that are ignored stay ignored and every other trap is reset
+    pub fn subshell(&self) -> Traps {
+        let actions = self
+            .actions
+            .iter()
+            .filter(|(c, a)| matches!(c, Condition::Signal(_)) && a.is_empty())
+            .map(|(c, a)| (*c, a.clone()))
+            .collect();
+        Traps {
+            actions,
+            running: false,
+        }
+    }
+}
+
+/// Run the trap set for a condition, if there is one
+///
+/// The exit status of the last command is preserved, unless the trap exits the shell. Errors are
+/// reported but do not stop the commands that were interrupted by the trap.
+pub fn run_trap(sh: &Shell, ctx: &mut Context, rt: &mut Runtime, condition: Condition) {
+    if rt.traps.running {
+        return;
+    }
+    let Some(action) = rt.traps.get(condition).filter(|a| !a.is_empty()) else {
+        return;
+    };
+
+    let action = action.to_string();
+    let status = rt.exit_status;
+    rt.traps.running = true;
+    let res = sh.lang.eval(sh, ctx, rt, action);
+    rt.traps.running = false;
+    rt.exit_status = status;
+
+    if let Err(e) = res {
+        eprintln!("trap: {e}");
+    }
+}
+
+/// Run the traps of the signals that arrived since the last call
+///
+/// Signals whose trap was removed take their default action, except for the ones an interactive
+/// shell ignores.
+pub fn dispatch_signals(sh: &Shell, ctx: &mut Context, rt: &mut Runtime) {
+    for signal in sh.signals.take_pending() {
+        if rt.traps.get(Condition::Signal(signal)).is_some() {
+            run_trap(sh, ctx, rt, Condition::Signal(signal));
+            continue;
+        }
+        let interactive = shrs_job::job_control_enabled();
+        let ignored =
+            signal == SIGWINCH || (interactive && [SIGINT, SIGQUIT, SIGTERM].contains(&signal));
+        if !ignored {
+            let _ = ctx.out.flush();
+            let _ = emulate_default_handler(signal);
+        }
+    }
+}
+
+/// Exit the shell after running the `EXIT` trap
+pub fn exit_shell(sh: &Shell, ctx: &mut Context, rt: &mut Runtime, status: i32) -> ! {
+    rt.exit_status = status;
+    run_trap(sh, ctx, rt, Condition::Exit);
+    let _ = ctx.out.flush();
+    std::process::exit(rt.exit_status)
+}
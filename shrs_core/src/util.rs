@@ -0,0 +1,39 @@
+//! Helpers for reading and writing shell words, shared by the builtins and the shell language
+
+/// Check if the name is a valid variable name, that is it can be assigned to
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(ch) if ch == '_' || ch.is_ascii_alphabetic())
+        && chars.all(|ch| ch == '_' || ch.is_ascii_alphanumeric())
+}
+
+/// Quote a word so that it is read back as a single word with the same value
+pub fn quote(word: &str) -> String {
+    let plain = |ch: char| ch.is_ascii_alphanumeric() || "_-+=./:,@%".contains(ch);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_name, quote};
+
+    #[test]
+    fn names() {
+        assert!(is_name("_a1"));
+        assert!(!is_name("1a"));
+        assert!(!is_name("a-b"));
+        assert!(!is_name(""));
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("a/b.rs"), "a/b.rs");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+}
@@ -67,9 +67,9 @@ fn main() {
     // Load environment variables from calling shell
     let mut env = Env::new();
     env.load();
-    env.set("SHELL_NAME", "shrs_example");
+    env.set("SHELL_NAME", "shrs_example").unwrap();
 
-    let builtins = Builtins::default();
+    let builtins = default_builtins();
 
     // =-=-= Completion =-=-=
     // Get list of binaries in path and initialize the completer to autocomplete command names
@@ -165,5 +165,5 @@ a rusty POSIX shell | build {}"#,
         .build()
         .expect("Could not construct shell");
 
-    myshell.run();
+    myshell.run_from_args(std::env::args());
 }
@@ -1,12 +1,19 @@
 use std::{
     ffi::OsStr,
     fmt, iter,
-    os::fd::AsRawFd,
+    os::{fd::AsRawFd, unix::process::ExitStatusExt},
     process::{Child, ChildStdout, Command, ExitStatus, Stdio},
 };
 
 use log::*;
-use nix::libc::{STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO};
+use nix::{
+    libc::{STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO},
+    sys::{
+        signal::{self, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::Pid,
+};
 
 use super::{io::Stdin, pid_t, util, Output};
 use crate::log_if_err;
@@ -20,6 +27,12 @@ impl From<u32> for ProcessId {
     }
 }
 
+impl From<ProcessId> for u32 {
+    fn from(value: ProcessId) -> Self {
+        value.0
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ProcessStatus {
     Running,
@@ -57,7 +70,8 @@ pub struct ProcessGroup {
     pub foreground: bool,
 }
 
-struct BuiltinProcess {
+/// Process for a command that runs inside the shell, such as a builtin
+pub struct BuiltinProcess {
     argv: Vec<String>,
     status_code: ExitStatus,
     stdout: Option<Stdin>,
@@ -188,9 +202,100 @@ impl Process for ExternalProcess {
     }
 }
 
-pub fn run_external_command<S1, S2>(
+/// Process for a subshell, a copy of the shell created with fork(2) that runs part of a command
+pub struct ForkedProcess {
+    argv: Vec<String>,
+    pid: Pid,
+    status: ProcessStatus,
+    status_code: Option<ExitStatus>,
+}
+
+impl ForkedProcess {
+    pub fn new<S1, S2>(program: S1, args: &[S2], pid: pid_t) -> Self
+    where
+        S1: AsRef<str>,
+        S2: AsRef<str>,
+    {
+        Self {
+            argv: iter::once(&program)
+                .map(|p| p.as_ref().to_string())
+                .chain(args.iter().map(|arg| arg.as_ref().to_string()))
+                .collect(),
+            pid: Pid::from_raw(pid),
+            status: ProcessStatus::Running,
+            status_code: None,
+        }
+    }
+
+    fn update(&mut self, wait_status: WaitStatus) -> Option<ExitStatus> {
+        // encode the status the same way wait(2) does, so it can be decoded by [ExitStatus]
+        let raw = match wait_status {
+            WaitStatus::Exited(_, code) => (code & 0xff) << 8,
+            WaitStatus::Signaled(_, signal, _) => signal as i32,
+            WaitStatus::Stopped(..) => {
+                self.status = ProcessStatus::Stopped;
+                return None;
+            },
+            _ => return None,
+        };
+        let exit_status = ExitStatus::from_raw(raw);
+        self.status = ProcessStatus::Completed;
+        self.status_code = Some(exit_status);
+        Some(exit_status)
+    }
+}
+
+impl Process for ForkedProcess {
+    fn id(&self) -> Option<ProcessId> {
+        Some((self.pid.as_raw() as u32).into())
+    }
+
+    fn argv(&self) -> String {
+        self.argv[..].join(" ")
+    }
+
+    fn status(&self) -> ProcessStatus {
+        self.status
+    }
+
+    fn status_code(&self) -> Option<ExitStatus> {
+        self.status_code
+    }
+
+    fn stdout(&mut self) -> Option<Stdin> {
+        None
+    }
+
+    fn kill(&mut self) -> anyhow::Result<()> {
+        signal::kill(self.pid, Signal::SIGKILL)?;
+        Ok(())
+    }
+
+    fn wait(&mut self) -> anyhow::Result<ExitStatus> {
+        loop {
+            if let Some(exit_status) = self.update(waitpid(self.pid, None)?) {
+                return Ok(exit_status);
+            }
+        }
+    }
+
+    fn try_wait(&mut self) -> anyhow::Result<Option<ExitStatus>> {
+        if let Some(exit_status) = self.status_code {
+            return Ok(Some(exit_status));
+        }
+        let flags = WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED;
+        Ok(self.update(waitpid(self.pid, Some(flags))?))
+    }
+}
+
+/// Spawn a command
+///
+/// The command only receives the variables in `env`, none of the environment of the shell process
+/// is inherited.
+pub fn run_external_command<S1, S2, E, K, V>(
     program: S1,
     args: &[S2],
+    env: E,
     stdin: Stdin,
     stdout: Output,
     stderr: Output,
@@ -199,6 +304,9 @@ pub fn run_external_command<S1, S2>(
 where
     S1: AsRef<str>,
     S2: AsRef<str>,
+    E: IntoIterator<Item = (K, V)>,
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
 {
     use std::os::unix::process::CommandExt;
 
@@ -209,6 +317,7 @@ where
 
     let mut command = Command::new(OsStr::new(program.as_ref()));
     command.args(args.iter().map(AsRef::as_ref).map(OsStr::new));
+    command.env_clear().envs(env);
 
     // Configure stdout and stderr (e.g. pipe, redirect). Do not configure
     // stdin, as we need to do that manually in before_exec *after* we have
@@ -230,9 +339,7 @@ where
         None
     };
 
-    // TODO add abiliity to disable job control
-    // let job_control_is_enabled = shell.is_job_control_enabled();
-    let job_control_is_enabled = true;
+    let job_control_is_enabled = util::job_control_enabled();
     let shell_terminal = util::get_terminal();
     unsafe {
         command.pre_exec(move || {
@@ -1,4 +1,7 @@
-use std::os::fd::{AsRawFd, RawFd};
+use std::{
+    os::fd::{AsRawFd, RawFd},
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use nix::{
     sys::signal::{self, SigHandler, Signal},
@@ -26,10 +29,20 @@ macro_rules! log_if_err {
     }};
 }
 
+static JOB_CONTROL: AtomicBool = AtomicBool::new(false);
+
 pub fn get_terminal() -> RawFd {
     std::io::stdin().as_raw_fd()
 }
 
+/// Whether [initialize_job_control] has taken control of the terminal
+///
+/// Without job control, commands are run in the shell's own process group and the terminal is
+/// left alone, which is what scripts and shells without a terminal want.
+pub fn job_control_enabled() -> bool {
+    JOB_CONTROL.load(Ordering::Relaxed)
+}
+
 pub fn initialize_job_control() -> anyhow::Result<()> {
     let shell_terminal = get_terminal();
 
@@ -61,5 +74,6 @@ pub fn initialize_job_control() -> anyhow::Result<()> {
     let temp_result = unistd::tcsetpgrp(shell_terminal, shell_pgid);
     log_if_err!(temp_result, "failed to grab control of terminal");
 
+    JOB_CONTROL.store(true, Ordering::Relaxed);
     Ok(())
 }
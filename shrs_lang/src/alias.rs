@@ -0,0 +1,287 @@
+//! Alias substitution
+//!
+//! Implements POSIX 2.3.1. Every unquoted word in the position of a command name that matches an
+//! alias is replaced by the tokens of the value of the alias as the input is lexed. The value is
+//! itself checked for aliases, except for the ones it is part of, so an alias can refer to a
+//! command of the same name. If the value ends with a blank, the word after it is also checked.
+//!
+//! The tokens of a value take the location of the word they replace, so errors and the spans in
+//! the AST point at what was typed rather than at text that never appeared in the input.
+//!
+//! Quoting any part of the command name, as in `\ls`, or running it through `command` prevents it
+//! from being substituted.
+
+use shrs_core::{util::is_name, Alias};
+
+use crate::{
+    lexer::{self, Spanned},
+    Lexer, Token,
+};
+
+/// Lex the input, replacing the aliases with the tokens of their value
+pub fn expand_aliases<'a>(
+    lexer: &mut Lexer<'a>,
+    aliases: &'a Alias,
+) -> Vec<Spanned<Token<'a>, usize, lexer::Error>> {
+    let mut tokens = vec![];
+    expand(
+        lexer,
+        aliases,
+        &mut vec![],
+        &mut Position::default(),
+        None,
+        &mut tokens,
+    );
+    tokens
+}
+
+/// Compound command that changes how the words inside it are interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Paren,
+    /// `case` up to `in`
+    CaseWord,
+    /// Patterns of a case arm, up to the closing parenthesis
+    CasePattern,
+    /// Commands of a case arm
+    CaseBody,
+}
+
+/// Tracks whether the next word is a command name
+#[derive(Debug, Default)]
+struct Position {
+    command: bool,
+    /// The previous word was replaced by an alias ending with a blank
+    after_blank: bool,
+    /// The next word is the target of a redirection
+    redirect: bool,
+    blocks: Vec<Block>,
+}
+
+impl Position {
+    fn check_alias(&self) -> bool {
+        (self.command && !self.redirect) || self.after_blank
+    }
+
+    fn advance(&mut self, token: &Token) {
+        let redirect = std::mem::take(&mut self.redirect);
+        self.after_blank = false;
+        match token {
+            // alternatives of a case pattern
+            Token::PIPE if self.blocks.last() == Some(&Block::CasePattern) => {
+                self.command = false;
+            },
+            Token::NEWLINE
+            | Token::SEMI
+            | Token::AMP
+            | Token::AND_IF
+            | Token::OR_IF
+            | Token::PIPE
+            | Token::PIPEAMP
+            | Token::BANG
+            | Token::LBRACE
+            | Token::IF
+            | Token::THEN
+            | Token::ELSE
+            | Token::ELIF
+            | Token::DO
+            | Token::WHILE
+            | Token::UNTIL => self.command = true,
+            Token::LPAREN => {
+                if self.blocks.last() != Some(&Block::CasePattern) {
+                    self.blocks.push(Block::Paren);
+                    self.command = true;
+                }
+            },
+            Token::RPAREN => {
+                match self.blocks.last_mut() {
+                    Some(Block::Paren) => {
+                        self.blocks.pop();
+                        self.command = false;
+                    },
+                    Some(block @ Block::CasePattern) => {
+                        *block = Block::CaseBody;
+                        self.command = true;
+                    },
+                    _ => self.command = false,
+                };
+            },
+            Token::CASE => {
+                self.blocks.push(Block::CaseWord);
+                self.command = false;
+            },
+            Token::IN => {
+                if let Some(block @ Block::CaseWord) = self.blocks.last_mut() {
+                    *block = Block::CasePattern;
+                }
+                self.command = false;
+            },
+            Token::DSEMI => {
+                if let Some(block @ Block::CaseBody) = self.blocks.last_mut() {
+                    *block = Block::CasePattern;
+                }
+                self.command = false;
+            },
+            Token::ESAC => {
+                if matches!(
+                    self.blocks.last(),
+                    Some(Block::CasePattern | Block::CaseBody)
+                ) {
+                    self.blocks.pop();
+                }
+                self.command = false;
+            },
+            Token::LESS
+            | Token::GREAT
+            | Token::DLESS
+            | Token::DGREAT
+            | Token::LESSAND
+            | Token::GREATAND
+            | Token::LESSGREAT
+            | Token::DLESSDASH
+            | Token::CLOBBER
+            | Token::TLESS
+            | Token::ANDGREAT
+            | Token::ANDDGREAT => self.redirect = true,
+            // redirections and assignments before the command name leave it in command position
+            Token::IO_NUMBER(_) | Token::HEREDOC(_) => {},
+            Token::WORD(_) if redirect => {},
+            Token::WORD(word) if is_assignment(word) => {},
+            _ => self.command = false,
+        }
+    }
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| is_name(name))
+}
+
+/// Replace the aliases in the tokens of `lexer`, `active` holds the aliases that are currently
+/// being replaced and `span` the location of the outermost one, which is given to every token of
+/// its value
+fn expand<'a>(
+    lexer: &mut Lexer<'a>,
+    aliases: &'a Alias,
+    active: &mut Vec<String>,
+    pos: &mut Position,
+    span: Option<(usize, usize)>,
+    out: &mut Vec<Spanned<Token<'a>, usize, lexer::Error>>,
+) {
+    pos.command = true;
+    for token in lexer {
+        let (start, token, end) = match token {
+            Ok(token) => token,
+            // the parser reports the error
+            Err(lexer::Error::UnrecognizedChar(start, ch, end)) => {
+                let (start, end) = span.unwrap_or((start, end));
+                out.push(Err(lexer::Error::UnrecognizedChar(start, ch, end)));
+                continue;
+            },
+            Err(e) => {
+                out.push(Err(e));
+                continue;
+            },
+        };
+        let (start, end) = span.unwrap_or((start, end));
+        if let Token::WORD(word) = token {
+            let value = aliases
+                .get(word)
+                .filter(|_| !active.iter().any(|a| a == word));
+            if let Some(value) = value.filter(|_| pos.check_alias()) {
+                active.push(word.to_string());
+                let mut lexer = Lexer::new(value);
+                expand(&mut lexer, aliases, active, pos, Some((start, end)), out);
+                active.pop();
+                pos.after_blank = value.ends_with([' ', '\t']);
+                continue;
+            }
+        }
+        pos.advance(&token);
+        out.push(Ok((start, token, end)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shrs_core::Alias;
+
+    use super::expand_aliases;
+    use crate::{Lexer, Parser, Span, Token};
+
+    /// Tokens of the input with the aliases replaced, without their location
+    fn expand<'a>(input: &'a str, aliases: &'a Alias) -> Vec<Token<'a>> {
+        let tokens = expand_aliases(&mut Lexer::new(input), aliases);
+        tokens.into_iter().map(|token| token.unwrap().1).collect()
+    }
+
+    fn tokens(input: &str) -> Vec<Token<'_>> {
+        Lexer::new(input).map(|token| token.unwrap().1).collect()
+    }
+
+    #[test]
+    fn command_position() {
+        let aliases = Alias::from_iter([("ll", "ls -l"), ("g", "grep -n")]);
+        let expand = |input: &'static str| expand(input, &aliases);
+        assert_eq!(expand("ll a"), tokens("ls -l a"));
+        assert_eq!(
+            expand("ll ll; x=1 ll|g ll && (ll)"),
+            tokens("ls -l ll; x=1 ls -l|grep -n ll && (ls -l)")
+        );
+        assert_eq!(
+            expand("if ll; then >out ll; fi"),
+            tokens("if ls -l; then >out ls -l; fi")
+        );
+        assert_eq!(expand("echo ll >ll"), tokens("echo ll >ll"));
+        assert_eq!(
+            expand("case ll in ll) ll;; (g|ll) g;; esac"),
+            tokens("case ll in ll) ls -l;; (g|ll) grep -n;; esac")
+        );
+        assert_eq!(
+            expand("for ll in ll; do ll; done"),
+            tokens("for ll in ll; do ls -l; done")
+        );
+        // quoting or `command` bypasses the alias
+        assert_eq!(
+            expand("\\ll; 'll'; command ll"),
+            tokens("\\ll; 'll'; command ll")
+        );
+        assert_eq!(
+            expand("cat <<EOF; ll\nll\nEOF\n"),
+            tokens("cat <<EOF; ls -l\nll\nEOF\n")
+        );
+    }
+
+    #[test]
+    fn recursive() {
+        let aliases = Alias::from_iter([
+            ("ls", "ls --color"),
+            ("l", "ls -a"),
+            ("a", "b"),
+            ("b", "a"),
+            ("sudo", "sudo "),
+            ("both", "l; l"),
+        ]);
+        let expand = |input: &'static str| expand(input, &aliases);
+        assert_eq!(expand("l x"), tokens("ls --color -a x"));
+        assert_eq!(expand("a"), tokens("a"));
+        assert_eq!(expand("sudo l l"), tokens("sudo  ls --color -a l"));
+        assert_eq!(expand("both l"), tokens("ls --color -a; ls --color -a l"));
+    }
+
+    #[test]
+    fn spans() {
+        let aliases = Alias::from_iter([("ll", "ls -l"), ("l", "ll")]);
+        let tokens = expand_aliases(&mut Lexer::new("x; l a"), &aliases);
+        let spans: Vec<_> = tokens
+            .into_iter()
+            .map(|token| token.map(|(start, _, end)| (start, end)).unwrap())
+            .collect();
+        assert_eq!(spans, vec![(0, 1), (1, 2), (3, 4), (3, 4), (5, 6)]);
+
+        // errors point at the input as it was typed
+        let error = Parser::new()
+            .parse_with_aliases(Lexer::new("ll )"), &aliases)
+            .unwrap_err();
+        assert_eq!(error.span, Span::new(3, 4));
+    }
+}
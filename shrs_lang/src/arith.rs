@@ -0,0 +1,569 @@
+//! Arithmetic evaluation
+//!
+//! Implements the arithmetic expressions described in POSIX 2.6.4, which follow the C language
+//! rules for signed long integers. This is used by both `$((expression))` and the `((expression))`
+//! command. Expressions are expected to already have gone through parameter expansion, command
+//! substitution and quote removal.
+//!
+//! In addition to what POSIX requires, the increment and decrement operators and the comma
+//! operator are supported.
+
+use shrs_core::{EnvError, Runtime};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("{0}: syntax error in expression (error token is \"{1}\")")]
+    Syntax(String, String),
+    #[error("{0}: division by 0")]
+    DivisionByZero(String),
+    #[error("{0}: value too great for base")]
+    InvalidNumber(String),
+    #[error("{0}: attempted assignment to non-variable")]
+    NotAssignable(String),
+    #[error(transparent)]
+    Env(#[from] EnvError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Num(String),
+    Ident(String),
+    /// Any operator or parenthesis
+    Op(&'static str),
+}
+
+/// Operators ordered so that longer operators are matched first
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=",
+    "/=", "%=", "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~", "?",
+    ":", "=", "(", ")", ",",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+    Comma,
+}
+
+impl BinOp {
+    /// Look up binary operator and its precedence, higher binds tighter
+    fn from_token(op: &str) -> Option<(BinOp, u8)> {
+        let op = match op {
+            "*" => (BinOp::Mul, 10),
+            "/" => (BinOp::Div, 10),
+            "%" => (BinOp::Rem, 10),
+            "+" => (BinOp::Add, 9),
+            "-" => (BinOp::Sub, 9),
+            "<<" => (BinOp::Shl, 8),
+            ">>" => (BinOp::Shr, 8),
+            "<" => (BinOp::Lt, 7),
+            "<=" => (BinOp::Le, 7),
+            ">" => (BinOp::Gt, 7),
+            ">=" => (BinOp::Ge, 7),
+            "==" => (BinOp::Eq, 6),
+            "!=" => (BinOp::Ne, 6),
+            "&" => (BinOp::BitAnd, 5),
+            "^" => (BinOp::BitXor, 4),
+            "|" => (BinOp::BitOr, 3),
+            "&&" => (BinOp::And, 2),
+            "||" => (BinOp::Or, 1),
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Operator used by compound assignment such as `+=`
+    fn from_assign(op: &str) -> Option<Option<BinOp>> {
+        let op = match op {
+            "=" => None,
+            "*=" => Some(BinOp::Mul),
+            "/=" => Some(BinOp::Div),
+            "%=" => Some(BinOp::Rem),
+            "+=" => Some(BinOp::Add),
+            "-=" => Some(BinOp::Sub),
+            "<<=" => Some(BinOp::Shl),
+            ">>=" => Some(BinOp::Shr),
+            "&=" => Some(BinOp::BitAnd),
+            "^=" => Some(BinOp::BitXor),
+            "|=" => Some(BinOp::BitOr),
+            _ => return None,
+        };
+        Some(op)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnaryOp {
+    Plus,
+    Minus,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Num(i64),
+    Var(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    /// Ternary `cond ? then : else`
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// Assignment, with the operator for compound assignments
+    Assign(String, Option<BinOp>, Box<Expr>),
+    /// `++` and `--`, holds the amount to add and whether the new value is returned
+    IncDec(String, i64, bool),
+}
+
+/// Evaluate an arithmetic expression
+///
+/// Variables referenced by the expression are read from and assigned to the runtime. An empty
+/// expression evaluates to zero.
+pub fn eval(rt: &mut Runtime, expr: &str) -> Result<i64, Error> {
+    let tokens = tokenize(expr)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut parser = ExprParser {
+        expr,
+        tokens,
+        pos: 0,
+    };
+    let ast = parser.comma()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.syntax_error());
+    }
+    eval_expr(rt, expr, &ast)
+}
+
+/// Assign a variable, if it has the integer attribute the value is evaluated as an arithmetic
+/// expression first
+pub fn assign(rt: &mut Runtime, var: &str, val: &str) -> Result<(), Error> {
+    if rt.env.var(var).is_some_and(|v| v.integer && !v.readonly) {
+        let val = eval(rt, val)?;
+        rt.env.set(var, &val.to_string())?;
+    } else {
+        rt.env.set(var, val)?;
+    }
+    Ok(())
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut rest = expr.trim_start();
+    while let Some(ch) = rest.chars().next() {
+        let len = if ch.is_ascii_digit() {
+            let len = rest
+                .find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Num(rest[..len].to_string()));
+            len
+        } else if ch == '_' || ch.is_ascii_alphabetic() {
+            let len = rest
+                .find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            len
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            op.len()
+        } else {
+            return Err(Error::Syntax(expr.to_string(), rest.to_string()));
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Parse an integer constant, which may be octal with a leading `0` or hexadecimal with a
+/// leading `0x`
+pub fn parse_number(num: &str) -> Option<i64> {
+    let num = num.trim();
+    let (digits, negative) = match num.strip_prefix('-') {
+        Some(digits) => (digits, true),
+        None => (num.strip_prefix('+').unwrap_or(num), false),
+    };
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        u64::from_str_radix(&digits[1..], 8).ok()?
+    } else if !digits.is_empty() && digits.chars().all(|ch| ch.is_ascii_digit()) {
+        digits.parse::<u64>().ok()?
+    } else {
+        return None;
+    };
+    // values that overflow wrap around like they would in C
+    let value = value as i64;
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+struct ExprParser<'a> {
+    expr: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), Error> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(found)) if *found == op => {
+                self.pos += 1;
+                Ok(())
+            },
+            _ => Err(self.syntax_error()),
+        }
+    }
+
+    /// Error that reports the rest of the expression starting at the current token
+    fn syntax_error(&self) -> Error {
+        let rest = self.tokens[self.pos..]
+            .iter()
+            .map(|t| match t {
+                Token::Num(s) | Token::Ident(s) => s.as_str(),
+                Token::Op(op) => op,
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        Error::Syntax(self.expr.to_string(), rest)
+    }
+
+    fn comma(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.assign()?;
+        while self.peek_op() == Some(",") {
+            self.pos += 1;
+            let rhs = self.assign()?;
+            lhs = Expr::Binary(BinOp::Comma, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn assign(&mut self) -> Result<Expr, Error> {
+        if let (Some(Token::Ident(name)), Some(Token::Op(op))) =
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1))
+        {
+            if let Some(bin_op) = BinOp::from_assign(op) {
+                let name = name.clone();
+                self.pos += 2;
+                let value = self.assign()?;
+                return Ok(Expr::Assign(name, bin_op, Box::new(value)));
+            }
+        }
+
+        let cond = self.conditional()?;
+        match self.peek_op() {
+            Some(op) if BinOp::from_assign(op).is_some() => {
+                Err(Error::NotAssignable(self.expr.to_string()))
+            },
+            _ => Ok(cond),
+        }
+    }
+
+    fn conditional(&mut self) -> Result<Expr, Error> {
+        let cond = self.binary(1)?;
+        if self.peek_op() != Some("?") {
+            return Ok(cond);
+        }
+        self.pos += 1;
+        let then = self.comma()?;
+        self.expect(":")?;
+        let otherwise = self.conditional()?;
+        Ok(Expr::Cond(
+            Box::new(cond),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    /// Precedence climbing over all left associative binary operators
+    fn binary(&mut self, min_prec: u8) -> Result<Expr, Error> {
+        let mut lhs = self.unary()?;
+        while let Some((op, prec)) = self.peek_op().and_then(BinOp::from_token) {
+            if prec < min_prec {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(prec + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        let op = match self.peek_op() {
+            Some("+") => UnaryOp::Plus,
+            Some("-") => UnaryOp::Minus,
+            Some("!") => UnaryOp::Not,
+            Some("~") => UnaryOp::BitNot,
+            Some(op @ ("++" | "--")) => {
+                let delta = if op == "++" { 1 } else { -1 };
+                self.pos += 1;
+                return match self.tokens.get(self.pos) {
+                    Some(Token::Ident(name)) => {
+                        let name = name.clone();
+                        self.pos += 1;
+                        Ok(Expr::IncDec(name, delta, true))
+                    },
+                    // not followed by a variable, so this is two unary operators in a row
+                    _ => {
+                        let op = if delta == 1 {
+                            UnaryOp::Plus
+                        } else {
+                            UnaryOp::Minus
+                        };
+                        let inner = Expr::Unary(op, Box::new(self.unary()?));
+                        Ok(Expr::Unary(op, Box::new(inner)))
+                    },
+                };
+            },
+            _ => return self.postfix(),
+        };
+        self.pos += 1;
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn postfix(&mut self) -> Result<Expr, Error> {
+        let primary = self.primary()?;
+        if let Expr::Var(name) = &primary {
+            match self.peek_op() {
+                Some("++") => {
+                    self.pos += 1;
+                    return Ok(Expr::IncDec(name.clone(), 1, false));
+                },
+                Some("--") => {
+                    self.pos += 1;
+                    return Ok(Expr::IncDec(name.clone(), -1, false));
+                },
+                _ => {},
+            }
+        }
+        Ok(primary)
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let expr = match self.tokens.get(self.pos) {
+            Some(Token::Num(num)) => match parse_number(num) {
+                Some(num) => Expr::Num(num),
+                None => return Err(Error::InvalidNumber(num.clone())),
+            },
+            Some(Token::Ident(name)) => Expr::Var(name.clone()),
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let inner = self.comma()?;
+                self.expect(")")?;
+                return Ok(inner);
+            },
+            _ => return Err(self.syntax_error()),
+        };
+        self.pos += 1;
+        Ok(expr)
+    }
+}
+
+/// Value of a variable, unset and null variables are zero
+fn var_value(rt: &Runtime, name: &str) -> Result<i64, Error> {
+    match rt.env.get(name) {
+        None => Ok(0),
+        Some(val) if val.trim().is_empty() => Ok(0),
+        Some(val) => parse_number(val).ok_or_else(|| Error::InvalidNumber(val.clone())),
+    }
+}
+
+fn eval_expr(rt: &mut Runtime, text: &str, expr: &Expr) -> Result<i64, Error> {
+    let val = match expr {
+        Expr::Num(num) => *num,
+        Expr::Var(name) => var_value(rt, name)?,
+        Expr::Unary(op, inner) => {
+            let inner = eval_expr(rt, text, inner)?;
+            match op {
+                UnaryOp::Plus => inner,
+                UnaryOp::Minus => inner.wrapping_neg(),
+                UnaryOp::Not => (inner == 0) as i64,
+                UnaryOp::BitNot => !inner,
+            }
+        },
+        // logical operators only evaluate the right hand side when needed
+        Expr::Binary(BinOp::And, lhs, rhs) => {
+            (eval_expr(rt, text, lhs)? != 0 && eval_expr(rt, text, rhs)? != 0) as i64
+        },
+        Expr::Binary(BinOp::Or, lhs, rhs) => {
+            (eval_expr(rt, text, lhs)? != 0 || eval_expr(rt, text, rhs)? != 0) as i64
+        },
+        Expr::Binary(op, lhs, rhs) => {
+            let lhs = eval_expr(rt, text, lhs)?;
+            let rhs = eval_expr(rt, text, rhs)?;
+            apply(text, *op, lhs, rhs)?
+        },
+        Expr::Cond(cond, then, otherwise) => {
+            if eval_expr(rt, text, cond)? != 0 {
+                eval_expr(rt, text, then)?
+            } else {
+                eval_expr(rt, text, otherwise)?
+            }
+        },
+        Expr::Assign(name, op, value) => {
+            let value = eval_expr(rt, text, value)?;
+            let value = match op {
+                Some(op) => apply(text, *op, var_value(rt, name)?, value)?,
+                None => value,
+            };
+            rt.env.set(name, &value.to_string())?;
+            value
+        },
+        Expr::IncDec(name, delta, prefix) => {
+            let old = var_value(rt, name)?;
+            let new = old.wrapping_add(*delta);
+            rt.env.set(name, &new.to_string())?;
+            if *prefix {
+                new
+            } else {
+                old
+            }
+        },
+    };
+    Ok(val)
+}
+
+fn apply(text: &str, op: BinOp, lhs: i64, rhs: i64) -> Result<i64, Error> {
+    let val = match op {
+        BinOp::Mul => lhs.wrapping_mul(rhs),
+        BinOp::Div | BinOp::Rem if rhs == 0 => {
+            return Err(Error::DivisionByZero(text.to_string()));
+        },
+        BinOp::Div => lhs.wrapping_div(rhs),
+        BinOp::Rem => lhs.wrapping_rem(rhs),
+        BinOp::Add => lhs.wrapping_add(rhs),
+        BinOp::Sub => lhs.wrapping_sub(rhs),
+        BinOp::Shl => lhs.wrapping_shl(rhs as u32),
+        BinOp::Shr => lhs.wrapping_shr(rhs as u32),
+        BinOp::Lt => (lhs < rhs) as i64,
+        BinOp::Le => (lhs <= rhs) as i64,
+        BinOp::Gt => (lhs > rhs) as i64,
+        BinOp::Ge => (lhs >= rhs) as i64,
+        BinOp::Eq => (lhs == rhs) as i64,
+        BinOp::Ne => (lhs != rhs) as i64,
+        BinOp::BitAnd => lhs & rhs,
+        BinOp::BitXor => lhs ^ rhs,
+        BinOp::BitOr => lhs | rhs,
+        BinOp::And => (lhs != 0 && rhs != 0) as i64,
+        BinOp::Or => (lhs != 0 || rhs != 0) as i64,
+        BinOp::Comma => rhs,
+    };
+    Ok(val)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{eval, parse_number, Error};
+    use crate::test_utils::runtime;
+
+    #[test]
+    fn precedence() {
+        let mut rt = runtime();
+        assert_eq!(eval(&mut rt, "1 + 2 * 3"), Ok(7));
+        assert_eq!(eval(&mut rt, "(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval(&mut rt, "10 - 4 - 3"), Ok(3));
+        assert_eq!(eval(&mut rt, "-2 * -3"), Ok(6));
+        assert_eq!(eval(&mut rt, "1 << 2 + 1"), Ok(8));
+        assert_eq!(eval(&mut rt, "7 % 4 == 3 && 1 | 2 ^ 3"), Ok(1));
+        assert_eq!(eval(&mut rt, ""), Ok(0));
+    }
+
+    #[test]
+    fn comparison_and_logic() {
+        let mut rt = runtime();
+        assert_eq!(eval(&mut rt, "3 < 4"), Ok(1));
+        assert_eq!(eval(&mut rt, "3 >= 4"), Ok(0));
+        assert_eq!(eval(&mut rt, "!0 && !!5"), Ok(1));
+        assert_eq!(eval(&mut rt, "0 || 0"), Ok(0));
+        assert_eq!(eval(&mut rt, "~0"), Ok(-1));
+        assert_eq!(eval(&mut rt, "1 ? 2 : 3"), Ok(2));
+        assert_eq!(eval(&mut rt, "0 ? 2 : 1 ? 4 : 5"), Ok(4));
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse_number("0x1f"), Some(31));
+        assert_eq!(parse_number("010"), Some(8));
+        assert_eq!(parse_number("-12"), Some(-12));
+        assert_eq!(parse_number("09"), None);
+        assert_eq!(parse_number("abc"), None);
+        let mut rt = runtime();
+        assert!(matches!(eval(&mut rt, "08"), Err(Error::InvalidNumber(_))));
+    }
+
+    #[test]
+    fn variables() {
+        let mut rt = runtime();
+        rt.env.set("X", "5").unwrap();
+        assert_eq!(eval(&mut rt, "X * 2"), Ok(10));
+        assert_eq!(eval(&mut rt, "UNSET + EMPTY + 1"), Ok(1));
+        assert_eq!(eval(&mut rt, "Y = X += 2"), Ok(7));
+        assert_eq!(rt.env.get("X").map(String::as_str), Some("7"));
+        assert_eq!(rt.env.get("Y").map(String::as_str), Some("7"));
+        assert_eq!(eval(&mut rt, "X++ + ++X"), Ok(16));
+        assert_eq!(eval(&mut rt, "X <<= 1, X"), Ok(18));
+        assert!(matches!(
+            eval(&mut rt, "FILE + 1"),
+            Err(Error::InvalidNumber(_))
+        ));
+    }
+
+    #[test]
+    fn short_circuit() {
+        let mut rt = runtime();
+        assert_eq!(eval(&mut rt, "0 && (X = 1)"), Ok(0));
+        assert_eq!(eval(&mut rt, "1 || (X = 1)"), Ok(1));
+        assert_eq!(eval(&mut rt, "1 ? 2 : (X = 1)"), Ok(2));
+        assert_eq!(rt.env.get("X"), None);
+        // division by zero is only an error if it is evaluated
+        assert_eq!(eval(&mut rt, "0 && 1 / 0"), Ok(0));
+    }
+
+    #[test]
+    fn errors() {
+        let mut rt = runtime();
+        assert!(matches!(
+            eval(&mut rt, "1 / 0"),
+            Err(Error::DivisionByZero(_))
+        ));
+        assert!(matches!(eval(&mut rt, "1 +"), Err(Error::Syntax(..))));
+        assert!(matches!(eval(&mut rt, "(1"), Err(Error::Syntax(..))));
+        assert!(matches!(eval(&mut rt, "1 2"), Err(Error::Syntax(..))));
+        assert!(matches!(eval(&mut rt, "1 $ 2"), Err(Error::Syntax(..))));
+        assert!(matches!(
+            eval(&mut rt, "1 = 2"),
+            Err(Error::NotAssignable(_))
+        ));
+    }
+}
@@ -1,34 +1,160 @@
 //! Structs that make up the parsed AST of the POSIX shell language
 
+use std::{fmt, ops::Deref};
+
+use shrs_core::util::is_name;
+
+use crate::{
+    expand::{self, parse_here_doc, parse_word, WordPart},
+    Span,
+};
+
+/// Word as it was written in the source, along with the parts it is expanded from
+///
+/// The text keeps quotes and expansions as they were typed. The parts are what the evaluator
+/// expands, commands inside substitutions are parsed into them along with the rest of the AST.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+    pub text: String,
+    pub span: Span,
+    pub parts: Vec<WordPart>,
+}
+
+impl Word {
+    /// Parse a word, fails if a quote or expansion in it is not terminated or a substituted
+    /// command has a syntax error
+    pub fn parse(text: impl Into<String>, span: Span) -> Result<Self, expand::Error> {
+        let text = text.into();
+        let parts = parse_word(&text)?;
+        Ok(Word { text, span, parts })
+    }
+
+    /// Word for text that is known to be valid, such as a word added by a
+    /// [crate::visit::VisitorMut]
+    ///
+    /// Text that does not parse is kept as a literal instead, [Word::parse] reports the error.
+    pub fn new(text: impl Into<String>, span: Span) -> Self {
+        let text = text.into();
+        let parts = parse_word(&text).unwrap_or_else(|_| vec![WordPart::Literal(text.clone())]);
+        Word { text, span, parts }
+    }
+
+    /// Body of a here-document, whose parts depend on the delimiter, see [parse_here_doc]
+    pub fn here_doc(
+        text: impl Into<String>,
+        span: Span,
+        delimiter: &str,
+        strip_tabs: bool,
+    ) -> Result<Self, expand::Error> {
+        let text = text.into();
+        let parts = parse_here_doc(delimiter, &text, strip_tabs)?;
+        Ok(Word { text, span, parts })
+    }
+}
+
+impl Deref for Word {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl PartialEq<str> for Word {
+    fn eq(&self, other: &str) -> bool {
+        self.text == other
+    }
+}
+
+impl PartialEq<&str> for Word {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+
 /// File redirection
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Redirect {
     pub n: Option<usize>,
-    pub file: String,
+    /// File name, or the delimiter and word for here-documents and here-strings respectively
+    pub file: Word,
     pub mode: RedirectMode,
+    /// Span of the entire redirection, including the file descriptor and operator
+    pub span: Span,
 }
 
 /// File redirection modes
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RedirectMode {
     Read,
     Write,
-    ReadAppend,
+    /// Write even if `noclobber` is set, `>|`
+    Clobber,
+    /// Here-document
+    ///
+    /// ```sh
+    /// cat <<EOF
+    /// hello $USER
+    /// EOF
+    /// ```
+    /// The body is expanded unless any part of the delimiter is quoted. With `<<-` leading tabs
+    /// are stripped from each line.
+    HereDoc {
+        body: Word,
+        strip_tabs: bool,
+    },
+    /// Here-string
+    ///
+    /// ```sh
+    /// cat <<< "hello $USER"
+    /// ```
+    HereString,
     WriteAppend,
+    /// Duplicate a file descriptor for reading, `<&n`, or close it with `<&-`
     ReadDup,
+    /// Duplicate a file descriptor for writing, `>&n`, or close it with `>&-`
     WriteDup,
     ReadWrite,
+    /// Write both stdout and stderr to the same file, `&>`
+    WriteAll,
+    /// Append both stdout and stderr to the same file, `&>>`
+    AppendAll,
 }
 
 /// Assignment
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Assign {
     pub var: String,
-    pub val: String,
+    pub val: Word,
+    /// Span of the entire assignment, including the name
+    pub span: Span,
+}
+
+impl Assign {
+    /// Split a word of the form `name=value` into an assignment
+    ///
+    /// Returns [None] if the part before the first `=` is not a valid variable name.
+    pub fn from_word(word: &Word) -> Option<Assign> {
+        let (var, val) = word.split_once('=')?;
+        // the span of a word that came from an alias does not cover its text
+        let start = (word.span.start + var.len() + 1).min(word.span.end);
+        let val_span = Span::new(start, word.span.end);
+        is_name(var).then(|| Assign {
+            var: var.to_string(),
+            val: Word::new(val, val_span),
+            span: word.span,
+        })
+    }
 }
 
 /// Seperator character between commands
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SeperatorOp {
     /// Ampersand (&)
     Amp,
@@ -36,7 +162,7 @@ pub enum SeperatorOp {
     Semi,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Command {
     /// Basic command
     ///
@@ -46,7 +172,7 @@ pub enum Command {
     Simple {
         assigns: Vec<Assign>,
         redirects: Vec<Redirect>,
-        args: Vec<String>,
+        args: Vec<Word>,
     },
 
     /// Two commands joined by a pipe
@@ -106,31 +232,158 @@ pub enum Command {
 
     /// For loops
     For {
-        name: String,
-        wordlist: Vec<String>,
+        name: Word,
+        /// Words to loop over, the positional parameters are used if there is no `in` clause
+        wordlist: Option<Vec<Word>>,
+        body: Box<Command>,
+    },
+
+    /// Select loops, which repeatedly let the user pick one of the words from a menu
+    /// ```sh
+    /// select file in *.txt; do cat "$file"; break; done
+    /// ```
+    Select {
+        name: Word,
+        /// Words to choose from, the positional parameters are used if there is no `in` clause
+        wordlist: Option<Vec<Word>>,
         body: Box<Command>,
     },
 
     /// Case statements
-    Case { word: String, arms: Vec<CaseArm> },
+    Case { word: Word, arms: Vec<CaseArm> },
+
+    /// Arithmetic command, exits with zero if the expression is non-zero
+    /// ```sh
+    /// (( i += 1 ))
+    /// ```
+    Arith(String),
+
+    /// Conditional command, exits with zero if the expression is true
+    /// ```sh
+    /// [[ -f $file && $name == *.rs ]]
+    /// ```
+    Cond(String),
 
     /// Function definition
-    Fn { fname: String, body: Box<Command> },
+    Fn { fname: Word, body: Box<Command> },
+
+    /// Compound command with redirections that apply to the whole command
+    /// ```sh
+    /// { date; make; } > build.log 2>&1
+    /// ```
+    Redirected {
+        cmd: Box<Command>,
+        redirects: Vec<Redirect>,
+    },
 
     /// No op
     None,
 }
 
 /// Represents each match arm in case statement
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CaseArm {
-    pub pattern: Vec<String>,
+    pub pattern: Vec<Word>,
     pub body: Box<Command>,
 }
 
 /// Corresponds to a condition followed by a body to execute in an 'if' or 'elif' block
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Condition {
     pub cond: Box<Command>,
     pub body: Box<Command>,
 }
+
+impl Command {
+    /// Run the last and-or list of this list in the background, followed by `next`
+    ///
+    /// Lists are parsed from left to right, so in `a; b & c` the `&` only applies to `b` even
+    /// though `a; b` has already been parsed as a list.
+    pub fn in_background(self, next: Option<Command>) -> Command {
+        match self {
+            Command::SeqList(a, Some(b)) => {
+                Command::SeqList(a, Some(Box::new(b.in_background(next))))
+            },
+            Command::AsyncList(a, Some(b)) => {
+                Command::AsyncList(a, Some(Box::new(b.in_background(next))))
+            },
+            cmd => Command::AsyncList(Box::new(cmd), next.map(Box::new)),
+        }
+    }
+
+    /// Add a redirection that is applied after the ones the command already has
+    ///
+    /// For a pipeline the redirection applies to its last command.
+    pub fn with_redirect(self, redirect: Redirect) -> Command {
+        match self {
+            Command::Simple {
+                assigns,
+                mut redirects,
+                args,
+            } => {
+                redirects.push(redirect);
+                Command::Simple {
+                    assigns,
+                    redirects,
+                    args,
+                }
+            },
+            Command::Redirected { cmd, mut redirects } => {
+                redirects.push(redirect);
+                Command::Redirected { cmd, redirects }
+            },
+            Command::Pipeline(a, b) => Command::Pipeline(a, Box::new(b.with_redirect(redirect))),
+            cmd => Command::Redirected {
+                cmd: Box::new(cmd),
+                redirects: vec![redirect],
+            },
+        }
+    }
+
+    /// Arguments of every simple command nested in this command, in the order they appear
+    pub fn simple_commands(&self) -> Vec<&[Word]> {
+        let mut out = vec![];
+        self.collect_simple(&mut out);
+        out
+    }
+
+    fn collect_simple<'a>(&'a self, out: &mut Vec<&'a [Word]>) {
+        match self {
+            Command::Simple { args, .. } => out.push(args),
+            Command::Pipeline(a, b) | Command::And(a, b) | Command::Or(a, b) => {
+                a.collect_simple(out);
+                b.collect_simple(out);
+            },
+            Command::AsyncList(a, b) | Command::SeqList(a, b) => {
+                a.collect_simple(out);
+                if let Some(b) = b {
+                    b.collect_simple(out);
+                }
+            },
+            Command::Not(cmd)
+            | Command::Subshell(cmd)
+            | Command::Fn { body: cmd, .. }
+            | Command::Redirected { cmd, .. } => cmd.collect_simple(out),
+            Command::If { conds, else_part } => {
+                for Condition { cond, body } in conds {
+                    cond.collect_simple(out);
+                    body.collect_simple(out);
+                }
+                if let Some(else_part) = else_part {
+                    else_part.collect_simple(out);
+                }
+            },
+            Command::While { cond, body } | Command::Until { cond, body } => {
+                cond.collect_simple(out);
+                body.collect_simple(out);
+            },
+            Command::For { body, .. } | Command::Select { body, .. } => body.collect_simple(out),
+            Command::Case { arms, .. } => {
+                for arm in arms {
+                    arm.body.collect_simple(out);
+                }
+            },
+            Command::Arith(_) | Command::Cond(_) | Command::None => {},
+        }
+    }
+}
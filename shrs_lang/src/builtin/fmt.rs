@@ -0,0 +1,67 @@
+use std::fs;
+
+use shrs_core::{
+    builtin::{BuiltinCmd, BuiltinStatus},
+    Context, Runtime, Shell,
+};
+
+use crate::{format_source, FormatOptions};
+
+/// Format script files in place
+///
+/// ```sh
+/// fmt [-i WIDTH] FILE...
+/// ```
+/// `-i 0` indents with tabs.
+#[derive(Default)]
+pub struct FmtBuiltin {}
+
+impl BuiltinCmd for FmtBuiltin {
+    fn run(
+        &self,
+        _sh: &Shell,
+        _ctx: &mut Context,
+        _rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        let mut options = FormatOptions::default();
+        let mut files = args.as_slice();
+        if let [flag, width, rest @ ..] = files {
+            if flag == "-i" {
+                options.indent = match width.parse() {
+                    Ok(width) => width,
+                    Err(_) => {
+                        eprintln!("fmt: {width}: invalid indent width");
+                        return Ok(BuiltinStatus::error());
+                    },
+                };
+                files = rest;
+            }
+        }
+        if files.is_empty() {
+            eprintln!("fmt: usage: fmt [-i WIDTH] FILE...");
+            return Ok(BuiltinStatus::error());
+        }
+
+        let mut status = BuiltinStatus::success();
+        for file in files {
+            let source = match fs::read_to_string(file) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("fmt: {file}: {e}");
+                    status = BuiltinStatus::error();
+                    continue;
+                },
+            };
+            match format_source(&source, &options) {
+                Ok(formatted) if formatted == source => {},
+                Ok(formatted) => fs::write(file, formatted)?,
+                Err(e) => {
+                    eprintln!("fmt: {file}\n{}", e.render(&source));
+                    status = BuiltinStatus::error();
+                },
+            }
+        }
+        Ok(status)
+    }
+}
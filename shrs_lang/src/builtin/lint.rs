@@ -0,0 +1,59 @@
+use std::fs;
+
+use shrs_core::{
+    builtin::{BuiltinCmd, BuiltinStatus},
+    Context, Runtime, Shell,
+};
+
+use crate::lint::{lint, Severity};
+
+/// Report problems found by the linter in script files
+///
+/// ```sh
+/// lint FILE...
+/// ```
+/// Exits with an error if any warnings or errors were found.
+#[derive(Default)]
+pub struct LintBuiltin {}
+
+impl BuiltinCmd for LintBuiltin {
+    fn run(
+        &self,
+        _sh: &Shell,
+        _ctx: &mut Context,
+        _rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        if args.is_empty() {
+            eprintln!("lint: usage: lint FILE...");
+            return Ok(BuiltinStatus::error());
+        }
+
+        let mut status = BuiltinStatus::success();
+        for file in args {
+            let source = match fs::read_to_string(file) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("lint: {file}: {e}");
+                    status = BuiltinStatus::error();
+                    continue;
+                },
+            };
+            let diagnostics = match lint(&source) {
+                Ok(diagnostics) => diagnostics,
+                Err(e) => {
+                    eprintln!("lint: {file}\n{}", e.render(&source));
+                    status = BuiltinStatus::error();
+                    continue;
+                },
+            };
+            for diagnostic in diagnostics {
+                println!("{file}: {}\n", diagnostic.render(&source));
+                if diagnostic.rule.severity >= Severity::Warning {
+                    status = BuiltinStatus::error();
+                }
+            }
+        }
+        Ok(status)
+    }
+}
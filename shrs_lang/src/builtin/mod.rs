@@ -0,0 +1,7 @@
+//! Builtin commands that need the parser, these are registered by the shell on top of the ones
+//! from `shrs_core`
+
+mod fmt;
+mod lint;
+
+pub use self::{fmt::FmtBuiltin, lint::LintBuiltin};
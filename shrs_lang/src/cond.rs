@@ -0,0 +1,330 @@
+//! Conditional commands
+//!
+//! Implements `[[ expression ]]`, which accepts the operators of the `test` builtin combined with
+//! `&&`, `||`, `!` and parentheses. Unlike `test`, the words in the expression are expanded
+//! without field splitting or pathname expansion, the right side of `==` and `!=` is a pattern
+//! and `=~` matches against an extended regular expression. Quoted parts of a pattern or regular
+//! expression are matched literally.
+//!
+//! A successful `=~` stores the matched text and the text matched by each group in the list
+//! `BASH_REMATCH`, so `${BASH_REMATCH[1]}` is the text of the first group.
+
+use regex::Regex;
+use shrs_core::{
+    builtin::test::{self, BINARY_OPS, UNARY_OPS},
+    Context, Runtime, Shell,
+};
+use thiserror::Error;
+
+use crate::{
+    arith,
+    expand::{expand_pattern, expand_regex, expand_word, pattern::Pattern},
+    Lexer, Token,
+};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("syntax error in conditional expression: unexpected token `{0}'")]
+    Unexpected(String),
+    #[error("syntax error in conditional expression: unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("{0}: invalid regular expression")]
+    InvalidRegex(String),
+    #[error("{0}")]
+    Operator(String),
+}
+
+/// Parsed conditional expression, operands are kept unexpanded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    /// Unary operator and its operand
+    Unary(String, String),
+    /// Left operand, binary operator and right operand
+    Binary(String, String, String),
+    /// Word on its own, true if it expands to a non empty string
+    Word(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tok {
+    Word(String),
+    /// `&&`, `||`, `(` or `)`
+    Op(&'static str),
+}
+
+/// Split an expression into words and operators
+///
+/// The shell lexer is reused so that quotes and expansions are handled the same as elsewhere.
+/// Tokens that are not separated by whitespace are joined back into a single word, which undoes
+/// the splitting of words such as `==` and `!=`. The operand of `=~` also takes in parentheses
+/// and `|`, which are common in regular expressions.
+fn tokenize(expr: &str) -> Result<Vec<Tok>, Error> {
+    let mut lexer = Lexer::new(expr).peekable();
+    let mut toks = vec![];
+    // end of the last word, to know if the next token is joined to it
+    let mut word_end = None;
+    while let Some(token) = lexer.next() {
+        let (start, mut token, mut end) = token.map_err(|e| Error::Unexpected(e.to_string()))?;
+        let regex = toks.last() == Some(&Tok::Word(String::from("=~"))) && word_end != Some(start);
+        let op = match token {
+            _ if regex => None,
+            Token::NEWLINE => continue,
+            Token::AND_IF => Some("&&"),
+            Token::OR_IF => Some("||"),
+            Token::LPAREN => Some("("),
+            Token::RPAREN => Some(")"),
+            _ => None,
+        };
+        if let Some(op) = op {
+            toks.push(Tok::Op(op));
+            word_end = None;
+            continue;
+        }
+
+        if regex {
+            let mut depth = 0;
+            loop {
+                match token {
+                    Token::LPAREN => depth += 1,
+                    Token::RPAREN => depth -= 1,
+                    _ => {},
+                }
+                match lexer.peek() {
+                    Some(Ok((next_start, next, _)))
+                        if (*next_start == end || depth > 0)
+                            && !(depth == 0 && *next == Token::RPAREN) =>
+                    {
+                        let (_, next, next_end) = lexer.next().unwrap().unwrap();
+                        token = next;
+                        end = next_end;
+                    },
+                    _ => break,
+                }
+            }
+        }
+
+        match (toks.last_mut(), word_end) {
+            (Some(Tok::Word(word)), Some(prev_end)) if prev_end == start => {
+                word.push_str(&expr[start..end]);
+            },
+            _ => toks.push(Tok::Word(expr[start..end].to_string())),
+        }
+        word_end = Some(end);
+    }
+    Ok(toks)
+}
+
+/// Parse the expression of a conditional command
+pub fn parse(expr: &str) -> Result<Expr, Error> {
+    let mut parser = Parser {
+        toks: tokenize(expr)?,
+        pos: 0,
+    };
+    let expr = parser.or()?;
+    match parser.toks.get(parser.pos) {
+        Some(tok) => Err(Error::Unexpected(tok.to_string())),
+        None => Ok(expr),
+    }
+}
+
+impl std::fmt::Display for Tok {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tok::Word(word) => write!(f, "{word}"),
+            Tok::Op(op) => write!(f, "{op}"),
+        }
+    }
+}
+
+/// Recursive descent parser, `||` has a lower precedence than `&&`, which is lower than `!`
+struct Parser {
+    toks: Vec<Tok>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<Tok, Error> {
+        let tok = self
+            .toks
+            .get(self.pos)
+            .cloned()
+            .ok_or(Error::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    fn peek_word(&self, offset: usize) -> Option<&str> {
+        match self.toks.get(self.pos + offset) {
+            Some(Tok::Word(word)) => Some(word),
+            _ => None,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.and()?;
+        while self.toks.get(self.pos) == Some(&Tok::Op("||")) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.not()?;
+        while self.toks.get(self.pos) == Some(&Tok::Op("&&")) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, Error> {
+        if self.peek_word(0) == Some("!") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let word = match self.next()? {
+            Tok::Op("(") => {
+                let expr = self.or()?;
+                return match self.next()? {
+                    Tok::Op(")") => Ok(expr),
+                    tok => Err(Error::Unexpected(tok.to_string())),
+                };
+            },
+            Tok::Op(op) => return Err(Error::Unexpected(op.to_string())),
+            Tok::Word(word) => word,
+        };
+        match (self.peek_word(0), self.peek_word(1)) {
+            (Some(op), Some(rhs)) if op == "=~" || BINARY_OPS.contains(&op) => {
+                let expr = Expr::Binary(word, op.to_string(), rhs.to_string());
+                self.pos += 2;
+                Ok(expr)
+            },
+            (Some(operand), _) if UNARY_OPS.contains(&word.as_str()) => {
+                let expr = Expr::Unary(word, operand.to_string());
+                self.pos += 1;
+                Ok(expr)
+            },
+            _ => Ok(Expr::Word(word)),
+        }
+    }
+}
+
+/// Evaluate a conditional expression
+///
+/// The operands of `&&` and `||` are only expanded if they are needed to determine the result.
+pub fn eval(sh: &Shell, ctx: &mut Context, rt: &mut Runtime, expr: &Expr) -> anyhow::Result<bool> {
+    let res = match expr {
+        Expr::Not(expr) => !eval(sh, ctx, rt, expr)?,
+        Expr::And(a, b) => eval(sh, ctx, rt, a)? && eval(sh, ctx, rt, b)?,
+        Expr::Or(a, b) => eval(sh, ctx, rt, a)? || eval(sh, ctx, rt, b)?,
+        Expr::Word(word) => !expand_word(sh, ctx, rt, word)?.is_empty(),
+        Expr::Unary(op, operand) => {
+            let operand = expand_word(sh, ctx, rt, operand)?;
+            test::unary(rt, op, &operand).unwrap_or(false)
+        },
+        Expr::Binary(lhs, op, rhs) => {
+            let lhs = expand_word(sh, ctx, rt, lhs)?;
+            match op.as_str() {
+                "=" | "==" | "!=" => {
+                    let pattern = Pattern::new(&expand_pattern(sh, ctx, rt, rhs)?);
+                    pattern.matches(&lhs) == (op != "!=")
+                },
+                "=~" => {
+                    let rhs = expand_regex(sh, ctx, rt, rhs)?;
+                    let regex = Regex::new(&rhs).map_err(|_| Error::InvalidRegex(rhs))?;
+                    rematch(rt, &regex, &lhs)
+                },
+                // integer operands are arithmetic expressions
+                "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge" => {
+                    let lhs = arith::eval(rt, &lhs)?;
+                    let rhs = expand_word(sh, ctx, rt, rhs)?;
+                    let rhs = arith::eval(rt, &rhs)?;
+                    test::binary(rt, &lhs.to_string(), op, &rhs.to_string())
+                        .map_err(Error::Operator)?
+                },
+                _ => {
+                    let rhs = expand_word(sh, ctx, rt, rhs)?;
+                    test::binary(rt, &lhs, op, &rhs).map_err(Error::Operator)?
+                },
+            }
+        },
+    };
+    Ok(res)
+}
+
+/// Match a regular expression and store what was matched, the result of a previous match is
+/// cleared even if this one fails
+fn rematch(rt: &mut Runtime, regex: &Regex, s: &str) -> bool {
+    rt.rematch = match regex.captures(s) {
+        Some(captures) => captures
+            .iter()
+            .map(|group| group.map_or("", |m| m.as_str()).to_string())
+            .collect(),
+        None => vec![],
+    };
+    !rt.rematch.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Error, Expr};
+    use crate::test_utils::{context, runtime, shell};
+
+    fn word(w: &str) -> Box<Expr> {
+        Box::new(Expr::Word(w.to_string()))
+    }
+
+    #[test]
+    fn parse_expressions() {
+        assert_eq!(
+            parse(" ! -f $a && ( x || \"$b\" != *.rs ) ").unwrap(),
+            Expr::And(
+                Box::new(Expr::Not(Box::new(Expr::Unary("-f".into(), "$a".into())))),
+                Box::new(Expr::Or(
+                    word("x"),
+                    Box::new(Expr::Binary("\"$b\"".into(), "!=".into(), "*.rs".into()))
+                ))
+            )
+        );
+        assert_eq!(
+            parse("$x =~ ^(a|b c)+$ && y").unwrap(),
+            Expr::And(
+                Box::new(Expr::Binary("$x".into(), "=~".into(), "^(a|b c)+$".into())),
+                word("y")
+            )
+        );
+        assert_eq!(parse("a &&"), Err(Error::UnexpectedEnd));
+        assert_eq!(parse("( a b"), Err(Error::Unexpected("b".into())));
+    }
+
+    #[test]
+    fn evaluate() {
+        let sh = shell();
+        let mut ctx = context();
+        let mut rt = runtime();
+        let mut eval = |expr: &str| super::eval(&sh, &mut ctx, &mut rt, &parse(expr).unwrap());
+
+        assert!(eval("$FILE == *.tar.*").unwrap());
+        assert!(!eval("$FILE == '*.tar.*'").unwrap());
+        assert!(eval("$EMPTY || -n $FILE").unwrap());
+        assert!(eval("1+2 -eq 3 && b > a").unwrap());
+        assert!(eval("$FILE =~ ^([a-z]+)\\.(tar)").unwrap());
+        assert!(!eval("$FILE =~ '.*'").unwrap());
+        assert!(eval("$FILE =~ \\.(gz|zip)$").unwrap());
+        assert!(eval("abc =~ a(x)?").unwrap());
+        assert!(eval("x =~ (").is_err());
+        // the right side is never expanded if the left side decides the result
+        assert!(eval("-n x || $(exit 1)$((1/0))").unwrap());
+
+        // an unmatched group is empty
+        assert_eq!(rt.rematch, vec!["a", ""]);
+    }
+}
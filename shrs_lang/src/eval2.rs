@@ -1,12 +1,44 @@
 // Lot of code based off of https://github.com/nuta/nsh/blob/main/src/eval.rs
 
-use std::process::ExitStatus;
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read, Seek, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+        unix::{fs::OpenOptionsExt, process::ExitStatusExt},
+    },
+    process::ExitStatus,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use nix::unistd::{close, pipe, setpgid, Pid};
-use shrs_core::{Context, Runtime, Shell};
-use shrs_job::{run_external_command, JobManager, Output, Process, ProcessGroup, Stdin};
+use anyhow::{anyhow, bail};
+use nix::{
+    fcntl::{fcntl, FcntlArg, FdFlag, OFlag},
+    sys::{
+        signal::{signal, SigHandler, Signal},
+        wait::{waitpid, WaitStatus},
+    },
+    unistd::{close, dup2, fork, pipe, pipe2, setpgid, ForkResult, Pid},
+};
+use shrs_core::{
+    builtin::is_special,
+    trap::{dispatch_signals, exit_shell, run_trap, Condition},
+    util::quote,
+    Context, ControlFlow, Runtime, Shell,
+};
+use shrs_job::{
+    job_control_enabled, run_external_command, BuiltinProcess, ForkedProcess, JobManager, Output,
+    Process, ProcessGroup, Stdin,
+};
 
-use crate::ast;
+use crate::{
+    arith, ast, cond,
+    expand::{
+        expand_arith, expand_fields, expand_pattern, expand_word, pattern::Pattern, WordPart,
+    },
+};
 
 pub struct Os {
     job_manager: JobManager,
@@ -14,12 +46,19 @@ pub struct Os {
     last_exit_status: ExitStatus,
 }
 
+/// Processes started by a command, along with the process group they were put in
+type Procs = (Vec<Box<dyn Process>>, Option<u32>);
+
+/// Start a job, for a foreground job also wait for it to stop or complete
+///
+/// Returns the statuses of the processes of a foreground job that have exited, in the order they
+/// appear in the pipeline.
 pub fn run_job(
     job_manager: &mut JobManager,
     procs: Vec<Box<dyn Process>>,
     pgid: Option<u32>,
     foreground: bool,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<Option<Vec<ExitStatus>>> {
     let proc_group = ProcessGroup {
         id: pgid,
         processes: procs,
@@ -31,66 +70,1556 @@ pub fn run_job(
 
     if is_foreground {
         job_manager.put_job_in_foreground(Some(job_id), false)?;
+        let statuses = job_manager
+            .get_jobs()
+            .into_iter()
+            .find(|job| job.id() == job_id)
+            .map(|job| {
+                job.processes()
+                    .iter()
+                    .filter_map(|p| p.status_code())
+                    .collect()
+            });
+        Ok(statuses)
     } else {
         job_manager.put_job_in_background(Some(job_id), false)?;
+        Ok(None)
+    }
+}
+
+/// Move a file descriptor to 10 or above, out of the way of the ones redirected by scripts
+///
+/// The new file descriptor is close on exec.
+fn move_high(fd: RawFd) -> anyhow::Result<File> {
+    let high = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(10));
+    let _ = close(fd);
+    Ok(unsafe { File::from_raw_fd(high?) })
+}
+
+/// Pipe with both ends moved out of the way of redirections, returns the read and write ends
+fn pipe_files() -> anyhow::Result<(File, File)> {
+    // close on exec so that only the commands it is given to hold on to the pipe
+    let (read_fd, write_fd) = pipe2(OFlag::O_CLOEXEC)?;
+    Ok((move_high(read_fd)?, move_high(write_fd)?))
+}
+
+/// Close every file descriptor at 10 or above, where the shell keeps the pipes and files it
+/// holds on to
+fn close_high_fds() {
+    let Ok(entries) = std::fs::read_dir("/dev/fd") else {
+        return;
+    };
+    let fds = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<RawFd>().ok())
+        .filter(|fd| *fd >= 10)
+        .collect::<Vec<_>>();
+    for fd in fds {
+        let _ = close(fd);
+    }
+}
+
+/// File that reads the given text, used by here-documents and here-strings
+///
+/// The text is written to a temporary file that is removed right away, rather than to a pipe
+/// that would need another thread writing to it. Subshells forked while that thread runs would
+/// hold on to the write end, and never see the end of the text.
+fn text_file(text: String) -> anyhow::Result<File> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = std::env::temp_dir();
+    let (path, mut file) = loop {
+        let n = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("shrs-text-{}-{n}", std::process::id()));
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(file) => break (path, file),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    };
+    let _ = std::fs::remove_file(path);
+    file.write_all(text.as_bytes())?;
+    file.rewind()?;
+    move_high(file.into_raw_fd())
+}
+
+/// Process for a command that was run inside the shell and has already completed
+fn completed<S: AsRef<str>>(argv: &[S], code: i32) -> Box<dyn Process> {
+    let (program, args) = argv.split_first().expect("argv is not empty");
+    // exit code is stored in the high byte of the wait status
+    let status = ExitStatus::from_raw((code & 0xff) << 8);
+    Box::new(BuiltinProcess::new(program, args, status, None))
+}
+
+/// Convert the status of a finished job into the value of `$?`
+pub fn exit_code(status: ExitStatus) -> i32 {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => code,
+        (None, Some(signal)) => 128 + signal,
+        (None, None) => 1,
+    }
+}
+
+/// Run a command to completion in the foreground and update `$?`
+///
+/// If the command fails outside of a condition the `ERR` trap is run, and if `errexit` is set the
+/// shell is asked to exit through [ControlFlow::Exit]. Traps for signals that arrived while the
+/// command was running are run afterwards.
+pub fn run_foreground(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    cmd: &ast::Command,
+) -> anyhow::Result<i32> {
+    let substs = ctx.proc_substs.len();
+    let res = eval_command(sh, ctx, rt, cmd, None, None).and_then(|(procs, pgid)| {
+        match procs.is_empty() {
+            true => Ok(None),
+            false => run_job(&mut sh.job_manager.borrow_mut(), procs, pgid, true),
+        }
+    });
+    finish_proc_substs(ctx, substs);
+
+    if let Some(statuses) = res? {
+        let codes = statuses.into_iter().map(exit_code).collect::<Vec<_>>();
+        rt.pipe_status = codes.clone();
+        let failed = codes.iter().rev().find(|code| **code != 0);
+        let code = match failed {
+            Some(code) if rt.options.pipefail => Some(code),
+            _ => codes.last(),
+        };
+        if let Some(code) = code {
+            rt.exit_status = *code;
+        }
+    }
+
+    let checked = matches!(
+        cmd,
+        ast::Command::Simple { .. }
+            | ast::Command::Pipeline(..)
+            | ast::Command::Subshell(_)
+            | ast::Command::Arith(_)
+            | ast::Command::Cond(_)
+    );
+    if checked && rt.exit_status != 0 && rt.conditions == 0 && rt.control_flow.is_none() {
+        run_trap(sh, ctx, rt, Condition::Err);
+        if rt.options.errexit {
+            rt.control_flow = Some(ControlFlow::Exit(rt.exit_status));
+        }
+    }
+
+    dispatch_signals(sh, ctx, rt);
+    Ok(rt.exit_status)
+}
+
+/// Run a command whose exit status is tested, `errexit` does not apply to anything it runs
+fn run_condition(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    cmd: &ast::Command,
+) -> anyhow::Result<i32> {
+    rt.conditions += 1;
+    let res = run_foreground(sh, ctx, rt, cmd);
+    rt.conditions -= 1;
+    res
+}
+
+/// Run the last command of a compound command, whose status becomes that of the compound command
+fn run_tail(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    cmd: &ast::Command,
+) -> anyhow::Result<Procs> {
+    run_foreground(sh, ctx, rt, cmd)?;
+    Ok((vec![], None))
+}
+
+/// Print a command to stderr for `xtrace`, prefixed by the expansion of `$PS4`
+fn trace(sh: &Shell, ctx: &mut Context, rt: &mut Runtime, words: &[String]) {
+    let ps4 = rt
+        .env
+        .get("PS4")
+        .cloned()
+        .unwrap_or_else(|| String::from("+ "));
+    // commands run while expanding the prompt are not traced
+    rt.options.xtrace = false;
+    let prefix = expand_word(sh, ctx, rt, &ps4).unwrap_or(ps4);
+    rt.options.xtrace = true;
+
+    let words = words.iter().map(|w| quote(w)).collect::<Vec<_>>();
+    eprintln!("{prefix}{}", words.join(" "));
+}
+
+/// Call a shell function, the arguments become the positional parameters of the body
+fn call_function(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    body: &ast::Command,
+    args: &[String],
+) -> anyhow::Result<i32> {
+    let saved = rt.push_frame(args.to_vec());
+    // `break` and `continue` only apply to the loops in the body
+    let loops = std::mem::take(&mut rt.loops);
+    let res = run_foreground(sh, ctx, rt, body);
+    rt.loops = loops;
+    rt.pop_frame(saved);
+    run_trap(sh, ctx, rt, Condition::Return);
+
+    match rt.control_flow {
+        Some(ControlFlow::Return(code)) => {
+            rt.control_flow = None;
+            Ok(code)
+        },
+        Some(ControlFlow::Exit(code)) => Ok(code),
+        _ => res,
+    }
+}
+
+/// Run a loop, keeping track of how deeply loops are nested for `break` and `continue`
+fn run_loop<F>(sh: &Shell, ctx: &mut Context, rt: &mut Runtime, f: F) -> anyhow::Result<i32>
+where
+    F: FnOnce(&Shell, &mut Context, &mut Runtime) -> anyhow::Result<i32>,
+{
+    rt.loops += 1;
+    let res = f(sh, ctx, rt);
+    rt.loops -= 1;
+    res
+}
+
+/// Handle `break` and `continue` after part of a loop has run, returns whether the loop stops
+///
+/// The ones meant for an enclosing loop are left for it with one level less, other requests such
+/// as `return` stop the loop and are left as is.
+fn leave_loop(rt: &mut Runtime) -> bool {
+    match rt.control_flow {
+        Some(ControlFlow::Break(n)) => {
+            rt.control_flow = (n > 1).then_some(ControlFlow::Break(n - 1));
+            true
+        },
+        Some(ControlFlow::Continue(n)) if n > 1 => {
+            rt.control_flow = Some(ControlFlow::Continue(n - 1));
+            true
+        },
+        Some(ControlFlow::Continue(_)) => {
+            rt.control_flow = None;
+            false
+        },
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Show the menu of a `select` loop on stderr and read the reply from stdin
+///
+/// Returns [None] at the end of the input.
+fn select_reply(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    words: &[String],
+    show_menu: bool,
+) -> anyhow::Result<Option<String>> {
+    if show_menu {
+        let width = words.len().to_string().len();
+        for (i, word) in words.iter().enumerate() {
+            eprintln!("{:>width$}) {word}", i + 1);
+        }
+    }
+    let ps3 = rt
+        .env
+        .get("PS3")
+        .cloned()
+        .unwrap_or_else(|| String::from("#? "));
+    let prompt = expand_word(sh, ctx, rt, &ps3).unwrap_or(ps3);
+    eprint!("{prompt}");
+
+    // read a byte at a time, so nothing after the line is taken away from later commands
+    let mut line = vec![];
+    let mut byte = [0];
+    loop {
+        match nix::unistd::read(0, &mut byte) {
+            Ok(0) if line.is_empty() => return Ok(None),
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => line.push(byte[0]),
+            Err(nix::errno::Errno::EINTR) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+/// Close the shell's end of the pipes of the process substitutions made since there were `len`
+/// of them, and wait for their processes to finish
+fn finish_proc_substs(ctx: &mut Context, len: usize) {
+    let substs = ctx.proc_substs.split_off(len.min(ctx.proc_substs.len()));
+    // all pipes are closed first, a process could be waiting for the end of another one
+    let procs = substs.into_iter().map(|(_, proc)| proc).collect::<Vec<_>>();
+    for mut proc in procs {
+        let _ = proc.wait();
+    }
+}
+
+/// Start the command of a process substitution in a subshell connected to a pipe
+///
+/// The shell keeps the other end of the pipe open as long as the command that uses the
+/// substitution runs, returns the path through which that command reaches it.
+pub fn process_subst(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    cmd: &ast::Command,
+    input: bool,
+) -> anyhow::Result<String> {
+    let (read, write) = pipe_files()?;
+    let (ours, theirs) = match input {
+        true => (write, (0, Target::File(read))),
+        false => (read, (1, Target::File(write))),
+    };
+    let fd = ours.as_raw_fd();
+
+    let name = if input { ">( )" } else { "<( )" };
+    let (mut procs, _) = fork_subshell(sh, ctx, rt, &[name], vec![theirs], |sh, ctx, rt| {
+        // the command must not hold on to the end of the pipe it would wait for, nor to the
+        // pipes of the pipeline it was started from, whose other commands would never finish
+        ctx.proc_substs.clear();
+        close_high_fds();
+        run_foreground(sh, ctx, rt, cmd)
+    })?;
+    ctx.proc_substs.push((ours, procs.remove(0)));
+    Ok(format!("/dev/fd/{fd}"))
+}
+
+/// Run a command in a subshell and collect everything it writes to stdout
+///
+/// Returns the output along with the exit status of the subshell. Used to implement command
+/// substitution.
+pub fn capture_output(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    cmd: &ast::Command,
+) -> anyhow::Result<(String, i32)> {
+    // anything still buffered would otherwise be written by both processes
+    std::io::stdout().flush()?;
+    let (read_fd, write_fd) = pipe()?;
+
+    match unsafe { fork() }? {
+        ForkResult::Child => {
+            let _ = close(read_fd);
+            let _ = dup2(write_fd, 1);
+            let _ = close(write_fd);
+
+            rt.traps = rt.traps.subshell();
+            let status = match run_foreground(sh, ctx, rt, cmd) {
+                Ok(status) => status,
+                Err(e) => {
+                    eprintln!("{e}");
+                    1
+                },
+            };
+            let _ = std::io::stdout().flush();
+            exit_shell(sh, ctx, rt, status);
+        },
+        ForkResult::Parent { child } => {
+            close(write_fd)?;
+            let mut output = String::new();
+            // reading until EOF also waits for any process still holding on to the pipe
+            let mut reader = unsafe { File::from_raw_fd(read_fd) };
+            let read = reader.read_to_string(&mut output);
+
+            let status = match waitpid(child, None)? {
+                WaitStatus::Exited(_, code) => code,
+                WaitStatus::Signaled(_, signal, _) => 128 + signal as i32,
+                _ => 1,
+            };
+            read?;
+            Ok((output, status))
+        },
+    }
+}
+
+/// What a file descriptor is redirected to
+enum Target {
+    File(File),
+    /// Duplicate of another file descriptor
+    Dup(RawFd),
+    Close,
+}
+
+/// Redirections of file descriptors, in the order they are applied
+type Targets = Vec<(RawFd, Target)>;
+
+/// Open the files of all redirections, in the order they appear
+///
+/// The files are kept at file descriptors 10 and above, so that applying one redirection does
+/// not replace the file opened for another.
+fn open_redirects(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    redirects: &[ast::Redirect],
+) -> anyhow::Result<Targets> {
+    let mut targets = vec![];
+    for redirect in redirects {
+        let default_fd = match redirect.mode {
+            ast::RedirectMode::Write
+            | ast::RedirectMode::Clobber
+            | ast::RedirectMode::WriteAppend
+            | ast::RedirectMode::WriteDup
+            | ast::RedirectMode::WriteAll
+            | ast::RedirectMode::AppendAll => 1,
+            _ => 0,
+        };
+        let fd = redirect.n.map_or(default_fd, |n| n as RawFd);
+
+        let file = match &redirect.mode {
+            ast::RedirectMode::HereDoc { body, .. } => text_file(expand_word(sh, ctx, rt, body)?)?,
+            ast::RedirectMode::HereString => {
+                text_file(expand_word(sh, ctx, rt, &redirect.file)? + "\n")?
+            },
+            mode @ (ast::RedirectMode::ReadDup | ast::RedirectMode::WriteDup) => {
+                let word = expand_word(sh, ctx, rt, &redirect.file)?;
+                match word.parse::<RawFd>() {
+                    Ok(n) if n >= 0 => targets.push((fd, Target::Dup(n))),
+                    _ if word == "-" => targets.push((fd, Target::Close)),
+                    // `>&file` is the same as `&>file`
+                    _ if *mode == ast::RedirectMode::WriteDup && redirect.n.is_none() => {
+                        let file = open_file(rt, &word, &ast::RedirectMode::Write)?;
+                        targets.push((1, Target::File(file)));
+                        targets.push((2, Target::Dup(1)));
+                    },
+                    _ => bail!("{word}: bad file descriptor"),
+                }
+                continue;
+            },
+            mode => {
+                let file = expand_word(sh, ctx, rt, &redirect.file)?;
+                open_file(rt, &file, mode)?
+            },
+        };
+        targets.push((fd, Target::File(file)));
+        if matches!(
+            redirect.mode,
+            ast::RedirectMode::WriteAll | ast::RedirectMode::AppendAll
+        ) {
+            targets.push((2, Target::Dup(1)));
+        }
+    }
+    Ok(targets)
+}
+
+/// Open the file of a redirection that reads or writes a path, moved out of the way of other
+/// redirections
+fn open_file(rt: &Runtime, file: &str, mode: &ast::RedirectMode) -> anyhow::Result<File> {
+    let path = rt.working_dir.join(file);
+    let mut options = OpenOptions::new();
+    match mode {
+        ast::RedirectMode::Read => options.read(true),
+        ast::RedirectMode::Write | ast::RedirectMode::Clobber | ast::RedirectMode::WriteAll => {
+            options.write(true).create(true).truncate(true)
+        },
+        ast::RedirectMode::WriteAppend | ast::RedirectMode::AppendAll => {
+            options.append(true).create(true)
+        },
+        _ => options.read(true).write(true).create(true),
+    };
+    // only regular files are protected, so `>/dev/null` keeps working
+    if matches!(mode, ast::RedirectMode::Write | ast::RedirectMode::WriteAll)
+        && rt.options.noclobber
+        && path.metadata().is_ok_and(|m| m.is_file())
+    {
+        bail!("{file}: cannot overwrite existing file");
+    }
+    let opened = options.open(path).map_err(|e| anyhow!("{file}: {e}"))?;
+    move_high(opened.into_raw_fd())
+}
+
+/// Redirections that connect a command to the rest of its pipeline
+fn pipe_targets(stdin: Option<File>, stdout: Option<File>) -> Targets {
+    stdin
+        .map(|file| (0, Target::File(file)))
+        .into_iter()
+        .chain(stdout.map(|file| (1, Target::File(file))))
+        .collect()
+}
+
+/// Files a simple command reads from and writes to
+struct CommandIo {
+    /// Ends of the pipes connecting the command to the rest of its pipeline
+    stdin: Option<File>,
+    stdout: Option<File>,
+    /// Redirections of the command itself
+    targets: Targets,
+}
+
+impl CommandIo {
+    fn piped(&self) -> bool {
+        self.stdin.is_some() || self.stdout.is_some()
+    }
+
+    /// Every redirection, the pipes come first so the redirections of the command override them
+    fn into_targets(self) -> Targets {
+        let pipes = pipe_targets(self.stdin, self.stdout);
+        pipes.into_iter().chain(self.targets).collect()
+    }
+}
+
+/// Redirections applied to the file descriptors of the shell itself, undone when dropped
+struct SavedFds {
+    /// Redirected file descriptors along with a copy of what they originally pointed to and
+    /// their original flags
+    saved: Vec<(RawFd, Option<(RawFd, FdFlag)>)>,
+}
+
+impl SavedFds {
+    fn apply(ctx: &mut Context, targets: Targets) -> anyhow::Result<SavedFds> {
+        // anything still buffered belongs to the original files
+        std::io::stdout().flush()?;
+        ctx.out.flush()?;
+
+        let mut saved = SavedFds { saved: vec![] };
+        for (fd, target) in targets {
+            // keep the copy out of the way of the low file descriptors used by scripts
+            let copy = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(10)).ok().map(|copy| {
+                let flags = fcntl(fd, FcntlArg::F_GETFD).unwrap_or_default();
+                (copy, FdFlag::from_bits_truncate(flags))
+            });
+            saved.saved.push((fd, copy));
+            let src = match &target {
+                Target::File(file) => file.as_raw_fd(),
+                Target::Dup(n) => *n,
+                Target::Close => {
+                    let _ = close(fd);
+                    continue;
+                },
+            };
+            dup2(src, fd).map_err(|_| anyhow!("{src}: bad file descriptor"))?;
+            // a file descriptor redirected to itself keeps its close on exec flag, which would
+            // stop it from being passed on
+            fcntl(fd, FcntlArg::F_SETFD(FdFlag::empty()))?;
+        }
+        Ok(saved)
+    }
+}
+
+impl Drop for SavedFds {
+    fn drop(&mut self) {
+        let _ = std::io::stdout().flush();
+        for (fd, copy) in self.saved.drain(..).rev() {
+            match copy {
+                Some((copy, flags)) => {
+                    let _ = dup2(copy, fd);
+                    let _ = fcntl(fd, FcntlArg::F_SETFD(flags));
+                    let _ = close(copy);
+                },
+                None => {
+                    let _ = close(fd);
+                },
+            }
+        }
+    }
+}
+
+/// Run part of a command in a copy of the shell created with fork(2)
+///
+/// The redirections are applied in the child process, which exits with the status returned by
+/// `f`. Changes made to the runtime are not visible to the parent shell.
+fn fork_subshell<S, F>(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    argv: &[S],
+    targets: Targets,
+    f: F,
+) -> anyhow::Result<Procs>
+where
+    S: AsRef<str>,
+    F: FnOnce(&Shell, &mut Context, &mut Runtime) -> anyhow::Result<i32>,
+{
+    // anything still buffered would otherwise be written by both processes
+    std::io::stdout().flush()?;
+    ctx.out.flush()?;
+
+    match unsafe { fork() }? {
+        ForkResult::Child => {
+            // put the subshell in its own process group, the same way external commands are
+            if job_control_enabled() {
+                let _ = setpgid(Pid::from_raw(0), Pid::from_raw(0));
+            }
+            for sig in [Signal::SIGINT, Signal::SIGQUIT, Signal::SIGTSTP] {
+                let _ = unsafe { signal(sig, SigHandler::SigDfl) };
+            }
+            rt.traps = rt.traps.subshell();
+
+            let status = match SavedFds::apply(ctx, targets) {
+                Ok(_fds) => f(sh, ctx, rt),
+                Err(e) => Err(e),
+            };
+            let status = status.unwrap_or_else(|e| {
+                eprintln!("shrs: {e}");
+                1
+            });
+            let _ = std::io::stdout().flush();
+            exit_shell(sh, ctx, rt, status);
+        },
+        ForkResult::Parent { child } => {
+            // also done in the parent to avoid racing with the job manager
+            if job_control_enabled() {
+                let _ = setpgid(child, child);
+            }
+            let (program, args) = argv.split_first().expect("argv is not empty");
+            let proc = ForkedProcess::new(program, args, child.as_raw());
+            Ok((vec![Box::new(proc)], Some(child.as_raw() as u32)))
+        },
+    }
+}
+
+/// Run a command that is implemented by the shell itself, such as a builtin or a function
+///
+/// If the command is part of a pipeline it runs in a subshell so it can run at the same time as
+/// the other commands. Otherwise it runs in the current shell, with the redirections temporarily
+/// applied to the file descriptors of the shell.
+fn run_in_shell<S, F>(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    argv: &[S],
+    io: CommandIo,
+    f: F,
+) -> anyhow::Result<Procs>
+where
+    S: AsRef<str>,
+    F: FnOnce(&Shell, &mut Context, &mut Runtime) -> anyhow::Result<i32>,
+{
+    if io.piped() {
+        return fork_subshell(sh, ctx, rt, argv, io.into_targets(), f);
+    }
+
+    let fds = match SavedFds::apply(ctx, io.targets) {
+        Ok(fds) => fds,
+        Err(e) => return Ok(failed(rt, argv, e)),
+    };
+    let status = f(sh, ctx, rt);
+    ctx.out.flush()?;
+    drop(fds);
+    Ok((vec![completed(argv, status?)], None))
+}
+
+/// Start an external command, which gets the exported variables as its environment
+fn run_external(
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    program: &str,
+    args: &[String],
+    io: CommandIo,
+) -> anyhow::Result<Procs> {
+    let ([stdin, stdout, stderr], rest) = resolve_targets(io.into_targets())?;
+
+    let fds = match SavedFds::apply(ctx, rest) {
+        Ok(fds) => fds,
+        Err(e) => return Ok(failed(rt, &[program], e)),
+    };
+    let res = run_external_command(
+        program,
+        args,
+        rt.env.exported(),
+        stdin.map_or(Stdin::Inherit, Stdin::File),
+        stdout.map_or(Output::Inherit, Output::File),
+        stderr.map_or(Output::Inherit, Output::File),
+        None,
+    );
+    drop(fds);
+    let argv = || [&[program.to_string()], args].concat();
+    match res {
+        Ok((proc, pgid)) => Ok((vec![proc], pgid)),
+        Err(e) => match e.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+            Some(ErrorKind::NotFound) => {
+                eprintln!("shrs: {program}: command not found");
+                Ok((vec![completed(&argv(), 127)], None))
+            },
+            Some(ErrorKind::PermissionDenied) => {
+                eprintln!("shrs: {program}: permission denied");
+                Ok((vec![completed(&argv(), 126)], None))
+            },
+            _ => Err(e),
+        },
+    }
+}
+
+/// Work out what each file descriptor of a command that runs in another process points to
+///
+/// Returns the standard streams, where [None] means the stream is inherited from the shell, and
+/// the redirections of the other file descriptors. Those are applied to the shell itself while
+/// the command is started, so that they are inherited by it.
+fn resolve_targets(targets: Targets) -> anyhow::Result<([Option<File>; 3], Targets)> {
+    // `None` for a file descriptor that is closed
+    let mut fds: BTreeMap<RawFd, Option<File>> = BTreeMap::new();
+    for (fd, target) in targets {
+        let file = match target {
+            Target::File(file) => Some(file),
+            Target::Dup(n) => match fds.get(&n) {
+                Some(Some(file)) => Some(file.try_clone()?),
+                Some(None) => bail!("{n}: bad file descriptor"),
+                None => {
+                    let copy = fcntl(n, FcntlArg::F_DUPFD_CLOEXEC(10))
+                        .map_err(|_| anyhow!("{n}: bad file descriptor"))?;
+                    Some(unsafe { File::from_raw_fd(copy) })
+                },
+            },
+            Target::Close => None,
+        };
+        fds.insert(fd, file);
+    }
+
+    let mut streams = [None, None, None];
+    let mut rest = vec![];
+    for (fd, file) in fds {
+        match (fd, file) {
+            (0..=2, Some(file)) => streams[fd as usize] = Some(file),
+            // the terminal is handed to the command through its stdin, so a closed stdin reads
+            // nothing instead
+            (0, None) => streams[0] = Some(File::open("/dev/null")?),
+            (fd, Some(file)) => rest.push((fd, Target::File(file))),
+            (fd, None) => rest.push((fd, Target::Close)),
+        }
+    }
+    Ok((streams, rest))
+}
+
+/// Check if evaluating a word runs a command substitution
+fn has_command_subst(parts: &[WordPart]) -> bool {
+    parts.iter().any(|part| match part {
+        WordPart::CommandSubst(_) => true,
+        WordPart::DoubleQuoted(parts) | WordPart::Arith(parts) => has_command_subst(parts),
+        _ => false,
+    })
+}
+
+/// Report an error that only fails the command it was raised by, such as a redirection that
+/// can not be opened, rather than stopping the whole script
+fn failed<S: AsRef<str>>(rt: &mut Runtime, argv: &[S], e: anyhow::Error) -> Procs {
+    eprintln!("shrs: {e}");
+    rt.exit_status = 1;
+    (vec![completed(argv, 1)], None)
+}
+
+/// Check if an error only fails the command it was raised by, which is the case for arithmetic
+/// errors such as a division by zero or an assignment to a readonly variable
+fn fails_command(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<arith::Error>().is_some()
+}
+
+/// Name of a compound command, used when the command is listed as a job
+fn keyword(cmd: &ast::Command) -> &'static str {
+    match cmd {
+        ast::Command::Simple { .. } => "command",
+        ast::Command::Pipeline(..) => "|",
+        ast::Command::And(..) => "&&",
+        ast::Command::Or(..) => "||",
+        ast::Command::Not(_) => "!",
+        ast::Command::AsyncList(..) => "&",
+        ast::Command::SeqList(..) => ";",
+        ast::Command::Subshell(_) => "( )",
+        ast::Command::If { .. } => "if",
+        ast::Command::While { .. } => "while",
+        ast::Command::Until { .. } => "until",
+        ast::Command::For { .. } => "for",
+        ast::Command::Select { .. } => "select",
+        ast::Command::Case { .. } => "case",
+        ast::Command::Arith(_) => "(( ))",
+        ast::Command::Cond(_) => "[[ ]]",
+        ast::Command::Fn { .. } => "function",
+        ast::Command::Redirected { cmd, .. } => keyword(cmd),
+        ast::Command::None => "",
     }
-    Ok(())
 }
 
 /// Returns group of processes and also the pgid if it has one
+///
+/// Commands that are part of a pipeline are given the ends of the pipes to use as `stdin` and
+/// `stdout`. Commands that are run by the shell itself are put in a subshell in that case.
 pub fn eval_command(
-    job_manager: &mut JobManager,
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
     cmd: &ast::Command,
-    stdin: Option<Stdin>,
-    stdout: Option<Output>,
-) -> anyhow::Result<(Vec<Box<dyn Process>>, Option<u32>)> {
+    stdin: Option<File>,
+    stdout: Option<File>,
+) -> anyhow::Result<Procs> {
+    let piped = stdin.is_some() || stdout.is_some();
+    if piped
+        && !matches!(
+            cmd,
+            ast::Command::Simple { .. } | ast::Command::Pipeline(..)
+        )
+    {
+        let targets = pipe_targets(stdin, stdout);
+        return fork_subshell(sh, ctx, rt, &[keyword(cmd)], targets, |sh, ctx, rt| {
+            run_foreground(sh, ctx, rt, cmd)
+        });
+    }
+
     match cmd {
         ast::Command::Simple {
             assigns,
             redirects,
             args,
         } => {
-            let mut args_it = args.iter();
-            let program = args_it.next().unwrap();
-            let args = args_it.collect::<Vec<_>>();
-
-            let proc_stdin = stdin.unwrap_or(Stdin::Inherit);
-            let proc_stdout = stdout.unwrap_or(Output::Inherit);
-
-            let (proc, pgid) = run_external_command(
-                program,
-                &args,
-                proc_stdin,
-                proc_stdout,
-                Output::Inherit,
-                None,
-            )?;
-            Ok((vec![proc], pgid))
+            run_trap(sh, ctx, rt, Condition::Debug);
+
+            let substs = ctx.proc_substs.len();
+            let mut fields = vec![];
+            for arg in args {
+                match expand_fields(sh, ctx, rt, arg) {
+                    Ok(expanded) => fields.extend(expanded),
+                    Err(e) if fails_command(&e) => return Ok(failed(rt, &[keyword(cmd)], e)),
+                    Err(e) => return Err(e),
+                }
+            }
+            // the pipes of the process substitutions in the arguments are only passed on to
+            // this command
+            let mut targets = ctx.proc_substs[substs..]
+                .iter()
+                .map(|(file, _)| (file.as_raw_fd(), Target::Dup(file.as_raw_fd())))
+                .collect::<Targets>();
+            match open_redirects(sh, ctx, rt, redirects) {
+                Ok(redirects) => targets.extend(redirects),
+                Err(e) => return Ok(failed(rt, &[keyword(cmd)], e)),
+            }
+
+            // only assignments and redirections, the redirected files are still created
+            let Some((program, args)) = fields.split_first() else {
+                drop(targets);
+                let mut argv = vec![];
+                for assign in assigns {
+                    let res = expand_word(sh, ctx, rt, &assign.val).and_then(|val| {
+                        arith::assign(rt, &assign.var, &val)?;
+                        Ok(val)
+                    });
+                    let val = match res {
+                        Err(e) if fails_command(&e) => return Ok(failed(rt, &[keyword(cmd)], e)),
+                        res => res?,
+                    };
+                    argv.push(format!("{}={val}", assign.var));
+                }
+                if rt.options.xtrace && !argv.is_empty() {
+                    trace(sh, ctx, rt, &argv);
+                }
+                // the status is that of the last command substitution
+                let code = if assigns.iter().any(|a| has_command_subst(&a.val.parts)) {
+                    rt.exit_status
+                } else {
+                    0
+                };
+                if argv.is_empty() {
+                    argv.push(String::new());
+                }
+                return Ok((vec![completed(&argv, code)], None));
+            };
+
+            // `command name args` runs `name` as a builtin or external command, skipping functions
+            let command_args = match args.split_first() {
+                Some((first, rest)) if first == "--" => rest,
+                _ => args,
+            };
+            let bypass = program == "command"
+                && command_args
+                    .first()
+                    .is_some_and(|arg| !arg.starts_with('-'));
+            let (program, args) = match command_args.split_first() {
+                Some((program, args)) if bypass => (program, args),
+                _ => (program, args),
+            };
+
+            let mut vars = vec![];
+            for assign in assigns {
+                let val = match expand_word(sh, ctx, rt, &assign.val) {
+                    Err(e) if fails_command(&e) => return Ok(failed(rt, &fields, e)),
+                    res => res?,
+                };
+                vars.push((assign.var.as_str(), val));
+            }
+            if rt.options.xtrace {
+                let mut words = vars
+                    .iter()
+                    .map(|(var, val)| format!("{var}={val}"))
+                    .collect::<Vec<_>>();
+                words.extend(fields.iter().cloned());
+                trace(sh, ctx, rt, &words);
+            }
+
+            let function = rt
+                .functions
+                .get(program)
+                .filter(|_| !bypass)
+                .and_then(|body| body.downcast_ref::<ast::Command>().cloned());
+            let builtin = sh.builtins.get(program);
+
+            // assignments in front of a special builtin stay set, otherwise they only last for the
+            // duration of the command and are passed to its environment
+            let special = function.is_none() && builtin.is_some() && !bypass && is_special(program);
+            let mut saved = vec![];
+            let mut assigned = Ok(());
+            for (var, val) in &vars {
+                if !special {
+                    saved.push((*var, rt.env.var(var).cloned()));
+                }
+                assigned = arith::assign(rt, var, val);
+                if assigned.is_err() {
+                    break;
+                }
+                if !special {
+                    rt.env.export(var, true);
+                }
+            }
+            let io = CommandIo {
+                stdin,
+                stdout,
+                targets,
+            };
+            let res = match assigned {
+                Err(e) => Ok(failed(rt, &fields, e.into())),
+                Ok(_) if function.is_none() && builtin.is_none() => {
+                    run_external(ctx, rt, program, args, io)
+                },
+                Ok(_) => run_in_shell(sh, ctx, rt, &fields, io, |sh, ctx, rt| {
+                    match (&function, builtin) {
+                        (Some(body), _) => call_function(sh, ctx, rt, body, args),
+                        (None, Some(builtin)) => {
+                            Ok(builtin.run(sh, ctx, rt, &args.to_vec())?.0 as i32)
+                        },
+                        (None, None) => unreachable!(),
+                    }
+                }),
+            };
+            for (var, val) in saved.into_iter().rev() {
+                rt.env.restore(var, val);
+            }
+            // `$_` is the last argument of the previous command, so it is only changed once the
+            // command is done
+            let _ = rt.env.set("_", fields.last().unwrap());
+            res
         },
         ast::Command::Pipeline(a_cmd, b_cmd) => {
-            let (mut a_procs, a_pgid) =
-                eval_command(job_manager, a_cmd, stdin, Some(Output::CreatePipe))?;
-            let (b_procs, b_pgid) = eval_command(
-                job_manager,
-                b_cmd,
-                a_procs.last_mut().unwrap().stdout(),
-                stdout,
-            )?;
+            let (read, write) = pipe_files()?;
+
+            let (mut a_procs, _) = eval_command(sh, ctx, rt, a_cmd, stdin, Some(write))?;
+            let (b_procs, b_pgid) = eval_command(sh, ctx, rt, b_cmd, Some(read), stdout)?;
             a_procs.extend(b_procs);
             Ok((a_procs, b_pgid))
         },
+        ast::Command::And(a_cmd, b_cmd) | ast::Command::Or(a_cmd, b_cmd) => {
+            let code = run_condition(sh, ctx, rt, a_cmd)?;
+            let run_b = (code == 0) == matches!(cmd, ast::Command::And(..));
+            if run_b && rt.control_flow.is_none() {
+                run_tail(sh, ctx, rt, b_cmd)
+            } else {
+                Ok((vec![], None))
+            }
+        },
+        ast::Command::Not(cmd) => {
+            let code = run_condition(sh, ctx, rt, cmd)?;
+            Ok((vec![completed(&["!"], (code == 0) as i32)], None))
+        },
         ast::Command::AsyncList(a_cmd, b_cmd) => {
-            // TODO double check stdin and stdout
-            let (procs, pgid) = eval_command(job_manager, a_cmd, None, None)?;
-            run_job(job_manager, procs, pgid, false)?;
+            let (procs, pgid) = match **a_cmd {
+                ast::Command::Simple { .. } => {
+                    let substs = ctx.proc_substs.len();
+                    let (mut procs, pgid) = eval_command(sh, ctx, rt, a_cmd, None, None)?;
+                    // process substitutions become part of the job, so they are waited for with it
+                    let substs = ctx.proc_substs.split_off(substs);
+                    let substs = substs.into_iter().map(|(_, proc)| proc);
+                    procs.splice(0..0, substs);
+                    (procs, pgid)
+                },
+                _ => fork_subshell(sh, ctx, rt, &[keyword(a_cmd)], vec![], |sh, ctx, rt| {
+                    run_foreground(sh, ctx, rt, a_cmd)
+                })?,
+            };
+            rt.last_background = procs.last().and_then(|proc| proc.id()).map(u32::from);
+            run_job(&mut sh.job_manager.borrow_mut(), procs, pgid, false)?;
 
             if let Some(b_cmd) = b_cmd {
-                eval_command(job_manager, b_cmd, None, None)
+                run_tail(sh, ctx, rt, b_cmd)
             } else {
                 Ok((vec![], None))
             }
         },
+        ast::Command::SeqList(a_cmd, b_cmd) => {
+            run_foreground(sh, ctx, rt, a_cmd)?;
+            // stop early if the first command was `return`
+            match b_cmd {
+                Some(b_cmd) if rt.control_flow.is_none() => run_tail(sh, ctx, rt, b_cmd),
+                _ => Ok((vec![], None)),
+            }
+        },
+        ast::Command::Subshell(cmd) => {
+            fork_subshell(sh, ctx, rt, &["( )"], vec![], |sh, ctx, rt| {
+                run_foreground(sh, ctx, rt, cmd)
+            })
+        },
+        ast::Command::If { conds, else_part } => {
+            for cond in conds {
+                let code = run_condition(sh, ctx, rt, &cond.cond)?;
+                if rt.control_flow.is_some() {
+                    return Ok((vec![], None));
+                }
+                if code == 0 {
+                    return run_tail(sh, ctx, rt, &cond.body);
+                }
+            }
+            match else_part {
+                Some(else_part) => run_tail(sh, ctx, rt, else_part),
+                None => Ok((vec![completed(&["if"], 0)], None)),
+            }
+        },
+        ast::Command::While { cond, body } | ast::Command::Until { cond, body } => {
+            let is_while = matches!(cmd, ast::Command::While { .. });
+            let code = run_loop(sh, ctx, rt, |sh, ctx, rt| {
+                let mut code = 0;
+                loop {
+                    let cond_code = run_condition(sh, ctx, rt, cond)?;
+                    if leave_loop(rt) || (cond_code == 0) != is_while {
+                        break;
+                    }
+                    code = run_foreground(sh, ctx, rt, body)?;
+                    if leave_loop(rt) {
+                        break;
+                    }
+                }
+                Ok(code)
+            })?;
+            Ok((vec![completed(&[keyword(cmd)], code)], None))
+        },
+        ast::Command::For {
+            name,
+            wordlist,
+            body,
+        }
+        | ast::Command::Select {
+            name,
+            wordlist,
+            body,
+        } => {
+            let words = match wordlist {
+                Some(wordlist) => {
+                    let mut words = vec![];
+                    for word in wordlist {
+                        words.extend(expand_fields(sh, ctx, rt, word)?);
+                    }
+                    words
+                },
+                None => rt.args.clone(),
+            };
+
+            let code = match cmd {
+                ast::Command::For { .. } => run_loop(sh, ctx, rt, |sh, ctx, rt| {
+                    let mut code = 0;
+                    for word in words {
+                        arith::assign(rt, name, &word)?;
+                        code = run_foreground(sh, ctx, rt, body)?;
+                        if leave_loop(rt) {
+                            break;
+                        }
+                    }
+                    Ok(code)
+                })?,
+                // the menu is shown again after an empty reply, the loop only ends with `break`
+                // or at the end of the input
+                _ => run_loop(sh, ctx, rt, |sh, ctx, rt| {
+                    let mut code = 0;
+                    let mut show_menu = true;
+                    while let Some(reply) = select_reply(sh, ctx, rt, &words, show_menu)? {
+                        show_menu = reply.trim().is_empty();
+                        if show_menu {
+                            continue;
+                        }
+                        let picked = match reply.trim().parse::<usize>() {
+                            Ok(n) if (1..=words.len()).contains(&n) => words[n - 1].as_str(),
+                            _ => "",
+                        };
+                        arith::assign(rt, "REPLY", &reply)?;
+                        arith::assign(rt, name, picked)?;
+                        code = run_foreground(sh, ctx, rt, body)?;
+                        if leave_loop(rt) {
+                            break;
+                        }
+                    }
+                    Ok(code)
+                })?,
+            };
+            Ok((vec![completed(&[keyword(cmd)], code)], None))
+        },
+        ast::Command::Case { word, arms } => {
+            let word = expand_word(sh, ctx, rt, word)?;
+            for arm in arms {
+                for pattern in &arm.pattern {
+                    let pattern = expand_pattern(sh, ctx, rt, pattern)?;
+                    if Pattern::new(&pattern).matches(&word) {
+                        return run_tail(sh, ctx, rt, &arm.body);
+                    }
+                }
+            }
+            Ok((vec![completed(&["case"], 0)], None))
+        },
+        ast::Command::Fn { fname, body } => {
+            rt.functions.insert(fname, Rc::new((**body).clone()));
+            Ok((vec![completed(&[&fname.text, "()"], 0)], None))
+        },
+        ast::Command::Arith(expr) => {
+            let code = match expand_arith(sh, ctx, rt, expr) {
+                Ok(0) => 1,
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("shrs: {e}");
+                    1
+                },
+            };
+            Ok((vec![completed(&["((", expr, "))"], code)], None))
+        },
+        ast::Command::Cond(expr) => {
+            let res = cond::parse(expr)
+                .map_err(anyhow::Error::from)
+                .and_then(|expr| cond::eval(sh, ctx, rt, &expr));
+            let code = match res {
+                Ok(true) => 0,
+                Ok(false) => 1,
+                Err(e) => {
+                    eprintln!("shrs: {e}");
+                    2
+                },
+            };
+            Ok((vec![completed(&["[[", expr, "]]"], code)], None))
+        },
+        ast::Command::Redirected { cmd, redirects } => {
+            let fds = match open_redirects(sh, ctx, rt, redirects)
+                .and_then(|targets| SavedFds::apply(ctx, targets))
+            {
+                Ok(fds) => fds,
+                Err(e) => return Ok(failed(rt, &[keyword(cmd)], e)),
+            };
+            let res = run_tail(sh, ctx, rt, cmd);
+            ctx.out.flush()?;
+            drop(fds);
+            res
+        },
         ast::Command::None => Ok((vec![], None)),
-        _ => todo!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use nix::sys::signal::{raise, Signal};
+    use shrs_core::ControlFlow;
+
+    use super::run_foreground;
+    use crate::{
+        test_utils::{context, runtime, shell},
+        Lexer, Parser,
+    };
+
+    fn run(rt: &mut shrs_core::Runtime, script: &str) -> anyhow::Result<i32> {
+        let sh = shell();
+        let mut ctx = context();
+        let cmd = Parser::new().parse(Lexer::new(script)).unwrap();
+        run_foreground(&sh, &mut ctx, rt, &cmd)
+    }
+
+    #[test]
+    fn function_call() {
+        let mut rt = runtime();
+        let script =
+            "f() { local FILE=inner; shift; (( N = $# )); return $1; (( N = 0 )); }; f a 7 b";
+        assert_eq!(run(&mut rt, script).unwrap(), 7);
+
+        assert_eq!(rt.env.get("N").unwrap(), "2");
+        assert_eq!(rt.env.get("FILE").unwrap(), "archive.tar.gz");
+        assert_eq!(rt.args, vec!["one", "two"]);
+        assert!(rt.frames.is_empty());
+        assert!(rt.control_flow.is_none());
+
+        // `command` skips the function and runs the builtin of the same name
+        let script = "test() { return 5; }; command test x && command -- test x; test x";
+        assert_eq!(run(&mut rt, script).unwrap(), 5);
+    }
+
+    #[test]
+    fn assignments() {
+        let mut rt = runtime();
+        assert_eq!(run(&mut rt, "a=1 b=$a$FILE").unwrap(), 0);
+        assert_eq!(rt.env.get("a").unwrap(), "1");
+        assert_eq!(rt.env.get("b").unwrap(), "1archive.tar.gz");
+
+        // only last for the duration of the command
+        run(&mut rt, "f() { (( c = a + 1 )); }; a=5 f").unwrap();
+        assert_eq!(rt.env.get("c").unwrap(), "6");
+        assert_eq!(rt.env.get("a").unwrap(), "1");
+
+        // passed to the environment of external commands
+        assert_eq!(run(&mut rt, "a=2 sh -c 'test \"$a\" = 2'").unwrap(), 0);
+        assert_eq!(run(&mut rt, "sh -c 'test -z \"$a\"'").unwrap(), 0);
+
+        // stay set in front of special builtins, unless they are run through `command`
+        run(&mut rt, "d=1 set -- x; e=1 command set -- y").unwrap();
+        assert_eq!(rt.env.get("d").unwrap(), "1");
+        assert_eq!(rt.env.get("e"), None);
+        assert_eq!(rt.args, vec!["y"]);
+
+        // arithmetic errors only fail the command they are part of
+        let script = "test $((1/0)) || handled=1; g=$((1/0)) || assigned=1
+readonly R=1; R=2 f || ro=1";
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(rt.env.get("handled").unwrap(), "1");
+        assert_eq!(rt.env.get("assigned").unwrap(), "1");
+        assert_eq!(rt.env.get("ro").unwrap(), "1");
+        assert_eq!(rt.env.get("g"), None);
+
+        // builtins evaluate the values they give to integer variables
+        let script = "declare -i n=2+3; f() { local -i m=n*2; k=$m; }; f
+declare -i s; readonly s=n+1; t=abc; declare -i t";
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(rt.env.get("n").unwrap(), "5");
+        assert_eq!(rt.env.get("k").unwrap(), "10");
+        assert_eq!(rt.env.get("s").unwrap(), "6");
+        assert_eq!(rt.env.get("t").unwrap(), "abc");
+    }
+
+    #[test]
+    fn compound_commands() {
+        let mut rt = runtime();
+        let script = r#"
+n=0
+while (( n < 5 )); do (( n += 1 )); done
+until (( n == 0 )); do (( n -= 1 )); (( m += 1 )); done
+for i in a "b c"; do last=$i; done
+for arg; do first=${first:-$arg}; done
+if (( m == 4 )); then r=no; elif (( m == 5 )); then r=yes; else r=else; fi
+case $FILE in *.zip) kind=zip;; *.tar.*) kind=tar;; esac
+(( 0 )) || or=1
+(( 0 )) && and=1
+! (( 0 ))
+"#;
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(rt.env.get("n").unwrap(), "0");
+        assert_eq!(rt.env.get("m").unwrap(), "5");
+        assert_eq!(rt.env.get("last").unwrap(), "b c");
+        assert_eq!(rt.env.get("first").unwrap(), "one");
+        assert_eq!(rt.env.get("r").unwrap(), "yes");
+        assert_eq!(rt.env.get("kind").unwrap(), "tar");
+        assert_eq!(rt.env.get("or").unwrap(), "1");
+        assert_eq!(rt.env.get("and"), None);
+
+        assert_eq!(run(&mut rt, "(( 1 )) && ! (( 1 ))").unwrap(), 1);
+    }
+
+    #[test]
+    fn redirects() {
+        let dir = std::env::temp_dir().join(format!("shrs_redirect_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut rt = runtime();
+        rt.working_dir = dir.clone();
+
+        run(&mut rt, "f() { (( 1 )); }; f > out; x=1 >> created").unwrap();
+        assert!(dir.join("out").exists());
+        assert!(dir.join("created").exists());
+        assert_eq!(run(&mut rt, "f < missing").unwrap(), 1);
+
+        // a redirection that can not be opened only fails its own command
+        let script = "f > missing/out || handled=1; if f < missing; then r=y; else r=n; fi
+{ f; } > missing/out || group=1; ( f ) < missing; s=$?; after=1";
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(rt.env.get("handled").unwrap(), "1");
+        assert_eq!(rt.env.get("r").unwrap(), "n");
+        assert_eq!(rt.env.get("group").unwrap(), "1");
+        assert_eq!(rt.env.get("s").unwrap(), "1");
+        assert_eq!(rt.env.get("after").unwrap(), "1");
+
+        run(&mut rt, "set -C").unwrap();
+        assert_eq!(run(&mut rt, "f > out").unwrap(), 1);
+        run(&mut rt, "f > out; clobber=$?").unwrap();
+        assert_eq!(rt.env.get("clobber").unwrap(), "1");
+        assert_eq!(run(&mut rt, "f >| out; f > new").unwrap(), 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn special_params() {
+        let mut rt = runtime();
+        let script =
+            "(( 1 )) | (( 0 )) | (( 2 )); s=${PIPESTATUS[*]} f=$PIPESTATUS m=${PIPESTATUS[1]}
+test x = y; l=$_; (( 0 )) & b=$!
+test a = b; f() { r=$_; }; f x z; t=$_
+p=$$ q=$(emit 1 $$) a=$#$0$?";
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(rt.env.get("s").unwrap(), "0 1 0");
+        assert_eq!(rt.env.get("f").unwrap(), "0");
+        assert_eq!(rt.env.get("m").unwrap(), "1");
+        assert_eq!(rt.env.get("l").unwrap(), "y");
+        assert_eq!(rt.env.get("r").unwrap(), "b");
+        assert_eq!(rt.env.get("t").unwrap(), "z");
+        let pid = std::process::id().to_string();
+        assert_eq!(rt.env.get("p").unwrap(), &pid);
+        assert_eq!(rt.env.get("q").unwrap(), &pid);
+        assert!(rt.env.get("b").unwrap().parse::<u32>().is_ok());
+        assert_eq!(rt.env.get("a").unwrap(), "2shrs0");
+    }
+
+    #[test]
+    fn case_patterns() {
+        let mut rt = runtime();
+        let script = r#"
+for w in Makefile main.rs '*' x; do
+    case $w in
+        [[:upper:]]*) up=$w;;
+        *.rs | *.toml) rust=$w;;
+        '*') star=$w;;
+        ?) single=$w;;
+    esac
+done
+"#;
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(rt.env.get("up").unwrap(), "Makefile");
+        assert_eq!(rt.env.get("rust").unwrap(), "main.rs");
+        assert_eq!(rt.env.get("star").unwrap(), "*");
+        assert_eq!(rt.env.get("single").unwrap(), "x");
+    }
+
+    #[test]
+    fn conditionals() {
+        let mut rt = runtime();
+        let script = "[ -d . -a ! -f . ] && test 2 -lt 10 && test x && [ ! ] && ! [ a = b ]";
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        let script = "test \\( -z '' -o -n '' \\) -a ! x != x -a ! -e nonexistent";
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(run(&mut rt, "[ a = b").unwrap(), 2);
+        assert_eq!(run(&mut rt, "test 1 -eq x").unwrap(), 2);
+        assert_eq!(run(&mut rt, "test a b").unwrap(), 2);
+
+        let script =
+            "if [[ $FILE == *.gz && ! -e $FILE ]]; then x=1; fi; [[ $FILE =~ ^(.*)\\.(gz)$ ]]
+m=$BASH_REMATCH g=${BASH_REMATCH[1]} e=${BASH_REMATCH[-1]} n=${#BASH_REMATCH[@]}";
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(rt.env.get("x").unwrap(), "1");
+        assert_eq!(rt.env.get("m").unwrap(), "archive.tar.gz");
+        assert_eq!(rt.env.get("g").unwrap(), "archive.tar");
+        assert_eq!(rt.env.get("e").unwrap(), "gz");
+        assert_eq!(rt.env.get("n").unwrap(), "3");
+        assert_eq!(run(&mut rt, "[[ a == b ]]").unwrap(), 1);
+        assert_eq!(run(&mut rt, "[[ a == ( ]]").unwrap(), 2);
+    }
+
+    #[test]
+    fn fd_redirects() {
+        let dir = std::env::temp_dir().join(format!("shrs_fd_redirect_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut rt = runtime();
+        rt.working_dir = dir.clone();
+        let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+
+        // subshells keep the redirections away from the file descriptors of the tests
+        let script = "( { emit 1 out; emit 2 err; } >both 2>&1 ); \
+            ( { emit 2 first 2>&1 >second; emit 1 again 2>&1 >>second; } >first ); \
+            ( emit 3 three 3>fd3; emit 4 four 3>fd4 4>&3; emit 5 five 5<>fd5 ); \
+            ( { emit 1 out; emit 2 err; } &>all; emit 2 more &>>all ); \
+            ( f() { emit 1 body; } >func; f )";
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(read("both"), "out\nerr\n");
+        assert_eq!(read("first"), "first\n");
+        assert_eq!(read("second"), "again\n");
+        assert_eq!(read("fd3"), "three\n");
+        assert_eq!(read("fd4"), "four\n");
+        assert_eq!(read("fd5"), "five\n");
+        assert_eq!(read("all"), "out\nerr\nmore\n");
+        assert_eq!(read("func"), "body\n");
+
+        // closed file descriptors can not be written to
+        assert_eq!(run(&mut rt, "( emit 1 closed >&- )").unwrap(), 1);
+
+        // subshells reading a here-document or here-string see the end of it
+        let script = "( cat ) >heredoc <<EOF\nhere\nEOF\n( cat ) >herestring <<< there\n";
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(read("heredoc"), "here\n");
+        assert_eq!(read("herestring"), "there\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn process_substitution() {
+        let dir = std::env::temp_dir().join(format!("shrs_proc_subst_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut rt = runtime();
+        rt.working_dir = dir.clone();
+
+        run(&mut rt, "source <(emit 1 x=1; emit 1 y=2)").unwrap();
+        assert_eq!(rt.env.get("x").unwrap(), "1");
+        assert_eq!(rt.env.get("y").unwrap(), "2");
+
+        // the substitution has finished by the time the command it was given to has
+        run(
+            &mut rt,
+            "( emit 1 'emit 1 written' > >(source /dev/stdin >out) )",
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(dir.join("out")).unwrap(), "written\n");
+
+        // only the command that uses the substitution is given the pipe, so the rest of the
+        // pipeline does not wait for it
+        let script = "sh -c 'echo piped >\"$1\"' sh >(cat >piped) | cat >listed";
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(fs::read_to_string(dir.join("piped")).unwrap(), "piped\n");
+        assert_eq!(fs::read_to_string(dir.join("listed")).unwrap(), "");
+        run(&mut rt, "echo >(cat) | cat >listed").unwrap();
+        assert!(fs::read_to_string(dir.join("listed"))
+            .unwrap()
+            .starts_with("/dev/fd/"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn set_options() {
+        let mut rt = runtime();
+        run(&mut rt, "set -euo pipefail +e -x; flags=$-").unwrap();
+        assert!(rt.options.nounset && rt.options.pipefail && rt.options.xtrace);
+        assert!(!rt.options.errexit);
+        assert_eq!(rt.env.get("flags").unwrap(), "ux");
+
+        run(&mut rt, "set +ux -- a 'b c'; n=$#").unwrap();
+        assert_eq!(rt.args, vec!["a", "b c"]);
+        assert_eq!(rt.env.get("n").unwrap(), "2");
+        assert_eq!(run(&mut rt, "set -o nonexistent").unwrap(), 2);
+    }
+
+    #[test]
+    fn errexit() {
+        let mut rt = runtime();
+        let script = "set -e; if (( 0 )); then x=1; fi; (( 0 )) || a=1; ! (( 1 )); \
+            f() { (( 0 )); b=1; }; f && c=1; (( 0 )); d=1";
+        assert_eq!(run(&mut rt, script).unwrap(), 1);
+        assert_eq!(rt.control_flow, Some(ControlFlow::Exit(1)));
+        assert_eq!(rt.env.get("a").unwrap(), "1");
+        assert_eq!(rt.env.get("b").unwrap(), "1");
+        assert_eq!(rt.env.get("c").unwrap(), "1");
+        assert_eq!(rt.env.get("d"), None);
+
+        // failures in the body of a compound command also count
+        let mut rt = runtime();
+        run(&mut rt, "set -e; if (( 1 )); then (( 0 )); fi; d=1").unwrap();
+        assert_eq!(rt.env.get("d"), None);
+    }
+
+    #[test]
+    fn nounset() {
+        let mut rt = runtime();
+        run(&mut rt, "set -u; a=${UNSET:-default} b=$1").unwrap();
+        assert_eq!(rt.env.get("a").unwrap(), "default");
+        assert!(run(&mut rt, "a=$UNSET").is_err());
+        assert!(run(&mut rt, "a=${#UNSET}").is_err());
+    }
+
+    #[test]
+    fn traps() {
+        let mut rt = runtime();
+        let script = "trap 'e=$?' ERR; trap 'r=$(( r + 1 ))' RETURN; f() { return 3; }; \
+            f; (( 1 )) || (( 0 )); trap - ERR RETURN; f";
+        assert_eq!(run(&mut rt, script).unwrap(), 3);
+        assert_eq!(rt.env.get("e").unwrap(), "3");
+        assert_eq!(rt.env.get("r").unwrap(), "1");
+
+        run(
+            &mut rt,
+            "trap 'd=$(( d + 1 ))' DEBUG; a=1; b=2; trap - DEBUG; c=3",
+        )
+        .unwrap();
+        assert_eq!(rt.env.get("d").unwrap(), "3");
+
+        // signals are handled by the next command to finish
+        let sh = shell();
+        let mut ctx = context();
+        let cmd = Parser::new()
+            .parse(Lexer::new("trap 'got=1' USR1"))
+            .unwrap();
+        run_foreground(&sh, &mut ctx, &mut rt, &cmd).unwrap();
+        raise(Signal::SIGUSR1).unwrap();
+        assert_eq!(rt.env.get("got"), None);
+        let cmd = Parser::new().parse(Lexer::new("x=1")).unwrap();
+        run_foreground(&sh, &mut ctx, &mut rt, &cmd).unwrap();
+        assert_eq!(rt.env.get("got").unwrap(), "1");
+    }
+
+    #[test]
+    fn loop_control() {
+        let mut rt = runtime();
+        let script = r#"
+for i in 1 2 3; do
+    for j in a b; do
+        (( i == 2 )) && continue 2
+        (( i == 3 )) && break 5
+        out=$out$i$j
+    done
+done
+n=0
+while (( 1 )); do (( n += 1 )); (( n < 3 )) && continue; break; done
+f() { for i in x; do return 4; done; }
+f; r=$?
+g() { break; }
+for i in 1 2; do g; ( break ); last=$i; done
+"#;
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(rt.env.get("out").unwrap(), "1a1b");
+        assert_eq!(rt.env.get("n").unwrap(), "3");
+        assert_eq!(rt.env.get("r").unwrap(), "4");
+        assert_eq!(rt.env.get("last").unwrap(), "2");
+        assert_eq!(rt.loops, 0);
+        assert!(rt.control_flow.is_none());
+
+        // only meaningful in loops, functions and sourced files
+        assert_eq!(run(&mut rt, "break").unwrap(), 1);
+        assert_eq!(run(&mut rt, "return").unwrap(), 1);
+        let script = "source <(emit 1 's=1; return 3; s=2')";
+        assert_eq!(run(&mut rt, script).unwrap(), 3);
+        assert_eq!(rt.env.get("s").unwrap(), "1");
+    }
+
+    #[test]
+    fn select_loop() {
+        let dir = std::env::temp_dir().join(format!("shrs_select_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut rt = runtime();
+        rt.working_dir = dir.clone();
+        fs::write(dir.join("input"), "\n3\nx\n2\n1\n").unwrap();
+
+        // an empty reply shows the menu again, one that is not in the menu leaves the name empty
+        let script = r#"( select w in a b c; do
+    picked=$picked$w replies=$replies$REPLY
+    [ -z "$w" ] && continue
+    (( n += 1 )); (( n == 2 )) && break
+done <input 2>/dev/null; emit 1 "$picked $replies" >out )"#;
+        assert_eq!(run(&mut rt, script).unwrap(), 0);
+        assert_eq!(fs::read_to_string(dir.join("out")).unwrap(), "cb 3x2\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn pipefail() {
+        let mut rt = runtime();
+        assert_eq!(run(&mut rt, "(( 0 )) | (( 1 ))").unwrap(), 0);
+        run(&mut rt, "set -o pipefail").unwrap();
+        assert_eq!(run(&mut rt, "(( 0 )) | (( 1 ))").unwrap(), 1);
+        assert_eq!(run(&mut rt, "(( 1 )) | (( 1 ))").unwrap(), 0);
     }
 }
@@ -0,0 +1,202 @@
+//! Command and process substitution
+//!
+//! Both the `$(command)` and the legacy backquoted form of command substitution are supported.
+//! The inner command is parsed when the word is parsed and run through the evaluator when the
+//! word is expanded.
+
+use shrs_core::{Context, Runtime, Shell};
+
+use super::{Error, WordPart};
+use crate::{ast, eval2, Lexer, Parser};
+
+/// Parse `$(...)`, `pos` points right after the opening parenthesis and is left after the
+/// closing one
+pub(super) fn parse_dollar_paren(chars: &[char], pos: &mut usize) -> Result<WordPart, Error> {
+    let start = *pos;
+    let end = closing_paren(chars, start).ok_or(Error::Unterminated(')'))?;
+    *pos = end + 1;
+
+    let text = chars[start..end].iter().collect::<String>();
+    Ok(WordPart::CommandSubst(Box::new(parse_command(&text)?)))
+}
+
+/// Parse `<(...)` or `>(...)`, `pos` points right after the opening parenthesis and is left
+/// after the closing one
+pub(super) fn parse_process_subst(
+    chars: &[char],
+    pos: &mut usize,
+    input: bool,
+) -> Result<WordPart, Error> {
+    let start = *pos;
+    let end = closing_paren(chars, start).ok_or(Error::Unterminated(')'))?;
+    *pos = end + 1;
+
+    let text = chars[start..end].iter().collect::<String>();
+    let cmd = Box::new(parse_command(&text)?);
+    Ok(WordPart::ProcessSubst { cmd, input })
+}
+
+/// Parse a backquoted command substitution, `pos` points right after the opening backquote and
+/// is left after the closing one
+pub(super) fn parse_backquote(chars: &[char], pos: &mut usize) -> Result<WordPart, Error> {
+    // backslash only escapes `$`, '`' and '\', all other backslashes are kept literally
+    let mut text = String::new();
+    loop {
+        match (chars.get(*pos), chars.get(*pos + 1)) {
+            (Some('\\'), Some(next @ ('$' | '`' | '\\'))) => {
+                text.push(*next);
+                *pos += 2;
+            },
+            (Some('`'), _) => {
+                *pos += 1;
+                break;
+            },
+            (Some(ch), _) => {
+                text.push(*ch);
+                *pos += 1;
+            },
+            (None, _) => return Err(Error::Unterminated('`')),
+        }
+    }
+    Ok(WordPart::CommandSubst(Box::new(parse_command(&text)?)))
+}
+
+fn parse_command(text: &str) -> Result<ast::Command, Error> {
+    Parser::new()
+        .parse(Lexer::new(text))
+        .map_err(|e| Error::CommandSubst(text.to_string(), e))
+}
+
+/// Find index of the parenthesis that closes the one right before `start`
+///
+/// Quoted sections and nested expansions are skipped.
+pub(super) fn closing_paren(chars: &[char], start: usize) -> Option<usize> {
+    let mut depth = 1;
+    let mut quote: Option<char> = None;
+    let mut i = start;
+    while let Some(&ch) = chars.get(i) {
+        match (quote, ch) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {},
+            (_, '\\') => i += 1,
+            (Some(q), ch) if ch == q => quote = None,
+            (Some(_), _) => {},
+            (None, '\'' | '"' | '`') => quote = Some(ch),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            },
+            (None, _) => {},
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Run the command and return its output with trailing newlines removed
+pub fn expand_command(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    cmd: &ast::Command,
+) -> anyhow::Result<String> {
+    let (output, status) = eval2::capture_output(sh, ctx, rt, cmd)?;
+    // the exit status of the last command substitution is visible to commands that consist only
+    // of assignments
+    rt.exit_status = status;
+    Ok(output.trim_end_matches('\n').to_string())
+}
+
+/// Start the command of a process substitution and return the path of the file connected to it
+///
+/// The command keeps running alongside the command the path is given to, it is waited for once
+/// that command finishes.
+pub fn expand_process_subst(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    cmd: &ast::Command,
+    input: bool,
+) -> anyhow::Result<String> {
+    eval2::process_subst(sh, ctx, rt, cmd, input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse_word, WordPart};
+    use crate::{ast, Lexer, Parser};
+
+    /// Command as it is parsed when it is the entire input, spans are relative to the start of
+    /// the substitution
+    fn simple(args: &[&str]) -> ast::Command {
+        Parser::new().parse(Lexer::new(&args.join(" "))).unwrap()
+    }
+
+    #[test]
+    fn dollar_paren() {
+        assert_eq!(
+            parse_word("a$(echo hi)b").unwrap(),
+            vec![
+                WordPart::Literal(String::from("a")),
+                WordPart::CommandSubst(Box::new(simple(&["echo", "hi"]))),
+                WordPart::Literal(String::from("b")),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let parts = parse_word("$(echo $(echo ')'))").unwrap();
+        assert_eq!(
+            parts,
+            vec![WordPart::CommandSubst(Box::new(simple(&[
+                "echo",
+                "$(echo ')')"
+            ])))]
+        );
+    }
+
+    #[test]
+    fn backquote() {
+        assert_eq!(
+            parse_word("`echo \\`echo a\\``").unwrap(),
+            vec![WordPart::CommandSubst(Box::new(simple(&[
+                "echo", "`echo a`"
+            ])))]
+        );
+        assert_eq!(
+            parse_word("\"`echo a`\"").unwrap(),
+            vec![WordPart::DoubleQuoted(vec![WordPart::CommandSubst(
+                Box::new(simple(&["echo", "a"]))
+            )])]
+        );
+    }
+
+    #[test]
+    fn process_subst() {
+        assert_eq!(
+            parse_word(">(wc -l)").unwrap(),
+            vec![WordPart::ProcessSubst {
+                cmd: Box::new(simple(&["wc", "-l"])),
+                input: true
+            }]
+        );
+        // only at the top level of a word
+        assert_eq!(
+            parse_word("\"<(a)\"").unwrap(),
+            vec![WordPart::DoubleQuoted(vec![WordPart::Literal(
+                String::from("<(a)")
+            )])]
+        );
+    }
+
+    #[test]
+    fn unterminated() {
+        assert!(parse_word("$(echo").is_err());
+        assert!(parse_word("<(echo").is_err());
+        assert!(parse_word("`echo").is_err());
+    }
+}
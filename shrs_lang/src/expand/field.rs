@@ -0,0 +1,294 @@
+//! Field splitting and pathname expansion
+//!
+//! The results of unquoted expansions are split into fields on the characters in `$IFS` as
+//! described in POSIX 2.6.5. Text that was typed literally or quoted is never split. Each field
+//! then goes through pathname expansion, and finally quote removal.
+
+use shrs_core::{Context, Runtime, Shell};
+
+use super::{
+    elements, expand_parts, glob, pattern, Error, Expandable, Index, Param, ParamOp, WordPart,
+};
+
+/// Value used when `IFS` is unset
+pub const DEFAULT_IFS: &str = " \t\n";
+
+/// Accumulates fields while the parts of a word are expanded
+///
+/// Text is stored in pattern form, meaning characters that should not be treated as pattern
+/// characters are escaped with a backslash.
+struct Fields<'a> {
+    ifs: &'a str,
+    fields: Vec<String>,
+    current: String,
+    /// The current field exists even if it is empty, because it contains a quoted part
+    started: bool,
+}
+
+impl<'a> Fields<'a> {
+    fn push_literal(&mut self, s: &str) {
+        self.current.push_str(s);
+        self.started |= !s.is_empty();
+    }
+
+    fn push_quoted(&mut self, s: &str) {
+        self.current.push_str(&pattern::escape(s));
+        self.started = true;
+    }
+
+    fn finish(&mut self) {
+        self.fields.push(std::mem::take(&mut self.current));
+        self.started = false;
+    }
+
+    fn is_ifs_whitespace(&self, ch: char) -> bool {
+        ch.is_whitespace() && self.ifs.contains(ch)
+    }
+
+    /// Add the result of an unquoted expansion, which is split into fields
+    fn push_split(&mut self, s: &str) {
+        let mut chars = s.chars().peekable();
+        while let Some(ch) = chars.next() {
+            if !self.ifs.contains(ch) {
+                // only pattern characters from expansions are special, not backslashes
+                if ch == '\\' {
+                    self.current.push('\\');
+                }
+                self.current.push(ch);
+                self.started = true;
+                continue;
+            }
+
+            // whitespace around a delimiter is part of that delimiter
+            let mut delimited = !self.is_ifs_whitespace(ch);
+            while let Some(&next) = chars.peek() {
+                if self.is_ifs_whitespace(next) {
+                    chars.next();
+                } else if !delimited && self.ifs.contains(next) {
+                    delimited = true;
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+
+            // non whitespace delimiters always end a field, even an empty one
+            if self.started || delimited {
+                self.finish();
+            }
+        }
+    }
+}
+
+/// Expand a word into the fields that make up the arguments of a command
+///
+/// Unlike [super::expand_word], this also performs field splitting and pathname expansion, so a
+/// single word may result in any number of fields.
+pub fn expand_fields<W: Expandable + ?Sized>(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    word: &W,
+) -> anyhow::Result<Vec<String>> {
+    let parts = word.word_parts()?;
+    let ifs = rt
+        .env
+        .get("IFS")
+        .cloned()
+        .unwrap_or_else(|| DEFAULT_IFS.to_string());
+    let mut fields = Fields {
+        ifs: &ifs,
+        fields: vec![],
+        current: String::new(),
+        started: false,
+    };
+
+    for part in parts.iter() {
+        match part {
+            WordPart::Literal(literal) => fields.push_literal(literal),
+            WordPart::Quoted(quoted) => fields.push_quoted(quoted),
+            WordPart::DoubleQuoted(inner) => {
+                fields.started = true;
+                for inner_part in inner {
+                    match inner_part {
+                        // "$@" expands to one field per positional parameter, and
+                        // "${name[@]}" to one field per element
+                        WordPart::Param(Param {
+                            name,
+                            index,
+                            op: ParamOp::Value,
+                        }) if (name == "@" && index.is_none()) || index == &Some(Index::At) => {
+                            let args = match index {
+                                Some(_) => elements(rt, name),
+                                None => rt.args.clone(),
+                            };
+                            for (i, arg) in args.iter().enumerate() {
+                                if i > 0 {
+                                    fields.finish();
+                                }
+                                fields.push_quoted(arg);
+                            }
+                            // no elements and nothing else in the quotes
+                            if args.is_empty() && inner.len() == 1 {
+                                fields.started = !fields.current.is_empty();
+                            }
+                        },
+                        part => {
+                            let value =
+                                expand_parts(sh, ctx, rt, std::slice::from_ref(part), false)?;
+                            fields.push_quoted(&value);
+                        },
+                    }
+                }
+            },
+            // the results of tilde expansion and process substitution are not split
+            WordPart::Tilde(_) | WordPart::ProcessSubst { .. } => {
+                let value = expand_parts(sh, ctx, rt, std::slice::from_ref(part), false)?;
+                fields.push_quoted(&value);
+            },
+            WordPart::Param(_) | WordPart::CommandSubst(_) | WordPart::Arith(_) => {
+                let value = expand_parts(sh, ctx, rt, std::slice::from_ref(part), false)?;
+                fields.push_split(&value);
+            },
+        }
+    }
+    if fields.started {
+        fields.finish();
+    }
+
+    let mut expanded = vec![];
+    for field in fields.fields {
+        expanded.extend(expand_pathname(rt, word, field)?);
+    }
+    Ok(expanded)
+}
+
+/// Perform pathname expansion on a field in pattern form, and remove the escapes
+fn expand_pathname(rt: &Runtime, word: &str, field: String) -> anyhow::Result<Vec<String>> {
+    if rt.options.noglob || pattern::Pattern::new(&field).is_literal() {
+        return Ok(vec![pattern::unescape(&field)]);
+    }
+    let paths = glob(rt, &field);
+    if !paths.is_empty() {
+        Ok(paths)
+    } else if rt.options.failglob {
+        Err(Error::NoMatch(word.to_string()).into())
+    } else if rt.options.nullglob {
+        Ok(vec![])
+    } else {
+        Ok(vec![pattern::unescape(&field)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand_fields;
+    use crate::test_utils::{context, runtime, shell};
+
+    fn fields(rt: &mut shrs_core::Runtime, word: &str) -> Vec<String> {
+        expand_fields(&shell(), &mut context(), rt, word).unwrap()
+    }
+
+    #[test]
+    fn quote_removal() {
+        let mut rt = runtime();
+        assert_eq!(fields(&mut rt, "'hello world'"), vec!["hello world"]);
+        assert_eq!(fields(&mut rt, "\"a  b\""), vec!["a  b"]);
+        assert_eq!(fields(&mut rt, "a\\ b\\\\c"), vec!["a b\\c"]);
+        assert_eq!(
+            fields(&mut rt, "\"\\$1 \\\"$1\\\" \\x\""),
+            vec!["$1 \"one\" \\x"]
+        );
+        assert_eq!(fields(&mut rt, "''"), vec![""]);
+        assert_eq!(fields(&mut rt, "$EMPTY"), Vec::<String>::new());
+        assert_eq!(fields(&mut rt, "\"$EMPTY\""), vec![""]);
+    }
+
+    #[test]
+    fn default_ifs() {
+        let mut rt = runtime();
+        rt.env.set("X", "  a  b\tc\n").unwrap();
+        assert_eq!(fields(&mut rt, "$X"), vec!["a", "b", "c"]);
+        assert_eq!(fields(&mut rt, "x${X}y"), vec!["x", "a", "b", "c", "y"]);
+        assert_eq!(fields(&mut rt, "\"$X\""), vec!["  a  b\tc\n"]);
+    }
+
+    #[test]
+    fn custom_ifs() {
+        let mut rt = runtime();
+        rt.env.set("IFS", ": ").unwrap();
+        rt.env.set("X", ":a :: b: ").unwrap();
+        assert_eq!(fields(&mut rt, "$X"), vec!["", "a", "", "b"]);
+        rt.env.set("IFS", "").unwrap();
+        assert_eq!(fields(&mut rt, "$X"), vec![":a :: b: "]);
+    }
+
+    #[test]
+    fn positional() {
+        let mut rt = runtime();
+        rt.args = vec![String::from("a b"), String::from("c")];
+        assert_eq!(fields(&mut rt, "\"$@\""), vec!["a b", "c"]);
+        assert_eq!(fields(&mut rt, "\"x$@y\""), vec!["xa b", "cy"]);
+        assert_eq!(fields(&mut rt, "\"$*\""), vec!["a b c"]);
+        assert_eq!(fields(&mut rt, "$@"), vec!["a", "b", "c"]);
+        rt.args = vec![];
+        assert_eq!(fields(&mut rt, "\"$@\""), Vec::<String>::new());
+        assert_eq!(fields(&mut rt, "\"x$@\""), vec!["x"]);
+
+        // every element of a list is a field of its own, like the positional parameters
+        rt.rematch = vec![String::from("a b"), String::from("c")];
+        assert_eq!(
+            fields(&mut rt, "\"x${BASH_REMATCH[@]}\""),
+            vec!["xa b", "c"]
+        );
+        assert_eq!(fields(&mut rt, "\"${BASH_REMATCH[*]}\""), vec!["a b c"]);
+
+        // "$*" is joined with the first character of IFS, and not joined at all with an empty one
+        rt.args = vec![String::from("a b"), String::from("c")];
+        rt.env.set("IFS", ":-").unwrap();
+        assert_eq!(fields(&mut rt, "\"$*\""), vec!["a b:c"]);
+        assert_eq!(fields(&mut rt, "\"${BASH_REMATCH[*]}\""), vec!["a b:c"]);
+        assert_eq!(fields(&mut rt, "$*"), vec!["a b", "c"]);
+        assert_eq!(fields(&mut rt, "\"$@\""), vec!["a b", "c"]);
+        rt.env.set("IFS", "").unwrap();
+        assert_eq!(fields(&mut rt, "\"$*\""), vec!["a bc"]);
+    }
+
+    #[test]
+    fn unquoted_pattern_characters() {
+        let mut rt = runtime();
+        rt.working_dir = std::env::temp_dir().join("shrs_no_such_dir");
+        rt.env.set("X", "a\\*").unwrap();
+        assert_eq!(fields(&mut rt, "$X"), vec!["a\\*"]);
+        assert_eq!(fields(&mut rt, "'*'"), vec!["*"]);
+    }
+
+    #[test]
+    fn glob_options() {
+        let dir = std::env::temp_dir().join(format!("shrs_glob_options_{}", std::process::id()));
+        for file in ["a.rs", "src/main.rs", "src/nested/lib.rs"] {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let mut rt = runtime();
+        rt.working_dir = dir.clone();
+
+        // a pattern without matches is left as is unless nullglob or failglob is set
+        assert_eq!(fields(&mut rt, "*.zip"), vec!["*.zip"]);
+        assert!(rt.options.set("nullglob", true));
+        assert_eq!(fields(&mut rt, "*.zip"), Vec::<String>::new());
+        assert!(rt.options.set("failglob", true));
+        assert!(expand_fields(&shell(), &mut context(), &mut rt, "*.zip").is_err());
+        assert_eq!(fields(&mut rt, "*.rs"), vec!["a.rs"]);
+
+        assert_eq!(fields(&mut rt, "**/*.rs"), vec!["src/main.rs"]);
+        assert!(rt.options.set("globstar", true));
+        assert_eq!(
+            fields(&mut rt, "**/*.rs"),
+            vec!["a.rs", "src/main.rs", "src/nested/lib.rs"]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
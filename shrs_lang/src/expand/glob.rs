@@ -0,0 +1,170 @@
+//! Pathname expansion
+//!
+//! Implements POSIX 2.13.3. The pattern is matched one path component at a time, so that `*` and
+//! `?` never match a slash. Files starting with a period are only matched if the component
+//! explicitly starts with a period. With the `globstar` option a `**` component matches any
+//! number of directories.
+
+use std::{fs, path::Path};
+
+use shrs_core::Runtime;
+
+use super::pattern::{unescape, Pattern};
+
+/// Find all paths matched by the pattern, sorted
+///
+/// Relative patterns are resolved against the working directory of the runtime, but the matched
+/// paths are returned relative, the same way they were written in the pattern.
+pub fn glob(rt: &Runtime, pattern: &str) -> Vec<String> {
+    let (mut paths, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec![String::from("/")], rest),
+        None => (vec![String::new()], pattern),
+    };
+
+    let components = rest.split('/').collect::<Vec<_>>();
+    for (i, component) in components.iter().enumerate() {
+        let last = i + 1 == components.len();
+        let mut next = vec![];
+        for prefix in &paths {
+            expand_component(rt, prefix, component, last, &mut next);
+        }
+        paths = next;
+        if paths.is_empty() {
+            break;
+        }
+    }
+
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Extend `prefix` with every entry that matches `component`
+///
+/// Components other than the last one only match directories, which are pushed with a trailing
+/// slash so the next component can be appended directly.
+fn expand_component(
+    rt: &Runtime,
+    prefix: &str,
+    component: &str,
+    last: bool,
+    out: &mut Vec<String>,
+) {
+    // repeated or trailing slash
+    if component.is_empty() {
+        out.push(prefix.to_string());
+        return;
+    }
+
+    let dir = rt.working_dir.join(prefix);
+    let push = |out: &mut Vec<String>, path: String| {
+        if last {
+            out.push(path);
+        } else if rt.working_dir.join(&path).is_dir() {
+            out.push(path + "/");
+        }
+    };
+
+    if component == "**" && rt.options.globstar {
+        if !last {
+            out.push(prefix.to_string());
+        }
+        let mut descendants = vec![];
+        walk(&dir, prefix, &mut descendants);
+        for path in descendants {
+            push(out, path);
+        }
+        return;
+    }
+
+    let pattern = Pattern::new(component);
+    if pattern.is_literal() {
+        let path = format!("{prefix}{}", unescape(component));
+        if rt.working_dir.join(&path).symlink_metadata().is_ok() {
+            push(out, path);
+        }
+        return;
+    }
+
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return;
+    };
+    let match_hidden = component.starts_with('.') || component.starts_with("\\.");
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') && !match_hidden {
+            continue;
+        }
+        if pattern.matches(&name) {
+            push(out, format!("{prefix}{name}"));
+        }
+    }
+}
+
+/// Collect all files and directories below `dir`, skipping hidden ones
+fn walk(dir: &Path, prefix: &str, out: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = format!("{prefix}{name}");
+        // do not follow symlinks so that cycles can not cause infinite recursion
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        out.push(path.clone());
+        if is_dir {
+            walk(&entry.path(), &format!("{path}/"), out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::glob;
+    use crate::test_utils::runtime;
+
+    #[test]
+    fn glob_files() {
+        let dir = std::env::temp_dir().join(format!("shrs_glob_{}", std::process::id()));
+        for file in [
+            "a.rs",
+            "b.rs",
+            "c.txt",
+            ".hidden.rs",
+            "src/main.rs",
+            "src/nested/lib.rs",
+        ] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        let mut rt = runtime();
+        rt.working_dir = dir.clone();
+        assert_eq!(glob(&rt, "*.rs"), vec!["a.rs", "b.rs"]);
+        assert_eq!(glob(&rt, ".*.rs"), vec![".hidden.rs"]);
+        assert_eq!(glob(&rt, "[ab].?s"), vec!["a.rs", "b.rs"]);
+        assert_eq!(glob(&rt, "*/"), vec!["src/"]);
+        assert_eq!(glob(&rt, "s*/*.rs"), vec!["src/main.rs"]);
+        assert_eq!(glob(&rt, "\\*.rs"), Vec::<String>::new());
+        assert_eq!(glob(&rt, "*.zip"), Vec::<String>::new());
+
+        // without globstar `**` is the same as `*`
+        assert_eq!(glob(&rt, "**/*.rs"), vec!["src/main.rs"]);
+        rt.options.globstar = true;
+        assert_eq!(
+            glob(&rt, "**/*.rs"),
+            vec!["a.rs", "b.rs", "src/main.rs", "src/nested/lib.rs"]
+        );
+
+        let abs = format!("{}/*.txt", dir.display());
+        assert_eq!(glob(&rt, &abs), vec![format!("{}/c.txt", dir.display())]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,554 @@
+//! Word expansion
+//!
+//! Words are parsed into [WordPart]s along with the rest of the AST, so that the commands of
+//! substitutions are part of it and syntax errors in them are found by the parser. Expansions are
+//! performed when the evaluator uses the words, in the order described in POSIX 2.6.
+
+mod param;
+pub use param::{elements, expand_param, lookup, Index, Param, ParamOp, SPECIAL_PARAMS};
+
+mod command;
+pub use command::{expand_command, expand_process_subst};
+
+mod glob;
+pub use glob::glob;
+
+mod field;
+pub use field::{expand_fields, DEFAULT_IFS};
+
+pub mod pattern;
+
+use std::borrow::Cow;
+
+use nix::unistd::User;
+use shrs_core::{Context, Runtime, Shell};
+use thiserror::Error;
+
+use crate::{arith, ast, parser};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("{0}: bad substitution")]
+    BadSubstitution(String),
+    /// Raised by `${parameter:?word}`
+    #[error("{0}: {1}")]
+    ParamUnset(String, String),
+    #[error("${0}: cannot assign in this way")]
+    BadAssign(String),
+    #[error("unexpected EOF while looking for matching `{0}'")]
+    Unterminated(char),
+    #[error("{0}: syntax error in command substitution: {1}")]
+    CommandSubst(String, parser::Error),
+    /// Raised by patterns that match no files when `failglob` is set
+    #[error("no match: {0}")]
+    NoMatch(String),
+}
+
+/// Piece of a word that is expanded on its own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordPart {
+    /// Unquoted text
+    Literal(String),
+    /// Single quoted or backslash escaped text, never expanded
+    Quoted(String),
+    /// Text inside double quotes, only expansions starting with `$` are performed
+    DoubleQuoted(Vec<WordPart>),
+    /// Parameter expansion
+    Param(Param),
+    /// Tilde prefix, holds the login name following the tilde
+    Tilde(String),
+    /// Command substitution
+    CommandSubst(Box<ast::Command>),
+    /// Process substitution, `input` is set for `>(command)` where the command reads what is
+    /// written to the file, and unset for `<(command)` where the file reads the command's output
+    ProcessSubst { cmd: Box<ast::Command>, input: bool },
+    /// Arithmetic expansion, the expression is expanded before it is evaluated
+    Arith(Vec<WordPart>),
+}
+
+/// Text that can be expanded
+///
+/// Words of the AST were broken up into their parts by the parser, other text such as the value
+/// of `$PS4` is parsed right before it is expanded.
+pub trait Expandable {
+    fn word_parts(&self) -> Result<Cow<'_, [WordPart]>, Error>;
+}
+
+impl Expandable for ast::Word {
+    fn word_parts(&self) -> Result<Cow<'_, [WordPart]>, Error> {
+        Ok(Cow::Borrowed(&self.parts))
+    }
+}
+
+impl Expandable for str {
+    fn word_parts(&self) -> Result<Cow<'_, [WordPart]>, Error> {
+        Ok(Cow::Owned(parse_word(self)?))
+    }
+}
+
+impl Expandable for String {
+    fn word_parts(&self) -> Result<Cow<'_, [WordPart]>, Error> {
+        self.as_str().word_parts()
+    }
+}
+
+/// What ends the sequence of parts currently being parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Terminator {
+    /// End of the word
+    Word,
+    /// Closing double quote
+    DoubleQuote,
+    /// Closing brace of a parameter expansion
+    Brace,
+    /// Closing bracket of the subscript of a parameter expansion
+    Bracket,
+    /// End of an arithmetic expression, a leading tilde is not special here
+    Arith,
+    /// End of a here-document body, which is treated like double quoted text except that double
+    /// quotes are not special
+    HereDoc,
+}
+
+/// Break up a word into the parts that need to be expanded
+pub fn parse_word(word: &str) -> Result<Vec<WordPart>, Error> {
+    let chars = word.chars().collect::<Vec<_>>();
+    let mut pos = 0;
+    parse_parts(&chars, &mut pos, Terminator::Word)
+}
+
+pub(crate) fn parse_parts(
+    chars: &[char],
+    pos: &mut usize,
+    term: Terminator,
+) -> Result<Vec<WordPart>, Error> {
+    let mut parts = vec![];
+    let mut literal = String::new();
+    let quoted = matches!(term, Terminator::DoubleQuote | Terminator::HereDoc);
+
+    fn flush(parts: &mut Vec<WordPart>, literal: &mut String) {
+        if !literal.is_empty() {
+            parts.push(WordPart::Literal(std::mem::take(literal)));
+        }
+    }
+
+    if matches!(term, Terminator::Word | Terminator::Brace) && chars.get(*pos) == Some(&'~') {
+        parse_tilde(chars, pos, &mut parts);
+    }
+
+    while let Some(&ch) = chars.get(*pos) {
+        match ch {
+            '"' if term == Terminator::DoubleQuote => break,
+            '}' if term == Terminator::Brace => break,
+            ']' if term == Terminator::Bracket => break,
+            '\'' if !quoted => {
+                let start = *pos + 1;
+                let len = chars[start..]
+                    .iter()
+                    .position(|ch| *ch == '\'')
+                    .ok_or(Error::Unterminated('\''))?;
+                flush(&mut parts, &mut literal);
+                parts.push(WordPart::Quoted(chars[start..start + len].iter().collect()));
+                *pos = start + len + 1;
+            },
+            '"' if !quoted => {
+                *pos += 1;
+                let inner = parse_parts(chars, pos, Terminator::DoubleQuote)?;
+                if chars.get(*pos) != Some(&'"') {
+                    return Err(Error::Unterminated('"'));
+                }
+                *pos += 1;
+                flush(&mut parts, &mut literal);
+                parts.push(WordPart::DoubleQuoted(inner));
+            },
+            '\\' => match chars.get(*pos + 1) {
+                // line continuation
+                Some('\n') => *pos += 2,
+                // inside double quotes backslash only escapes a few characters
+                Some(next)
+                    if !quoted
+                        || matches!(next, '$' | '`' | '\\')
+                        || (*next == '"' && term == Terminator::DoubleQuote) =>
+                {
+                    flush(&mut parts, &mut literal);
+                    parts.push(WordPart::Quoted(next.to_string()));
+                    *pos += 2;
+                },
+                _ => {
+                    literal.push('\\');
+                    *pos += 1;
+                },
+            },
+            '`' => {
+                *pos += 1;
+                flush(&mut parts, &mut literal);
+                parts.push(command::parse_backquote(chars, pos)?);
+            },
+            // the lexer only keeps `<` and `>` in a word when they start a process substitution
+            '<' | '>' if term == Terminator::Word && chars.get(*pos + 1) == Some(&'(') => {
+                *pos += 2;
+                flush(&mut parts, &mut literal);
+                parts.push(command::parse_process_subst(chars, pos, ch == '>')?);
+            },
+            '$' => {
+                *pos += 1;
+                match parse_dollar(chars, pos)? {
+                    Some(part) => {
+                        flush(&mut parts, &mut literal);
+                        parts.push(part);
+                    },
+                    None => literal.push('$'),
+                }
+            },
+            ch => {
+                literal.push(ch);
+                *pos += 1;
+            },
+        }
+    }
+    flush(&mut parts, &mut literal);
+
+    Ok(parts)
+}
+
+/// Parse the expansion following a `$`, returns [None] if the `$` should be taken literally
+fn parse_dollar(chars: &[char], pos: &mut usize) -> Result<Option<WordPart>, Error> {
+    let part = match chars.get(*pos) {
+        Some('{') => {
+            *pos += 1;
+            WordPart::Param(param::parse_braced(chars, pos)?)
+        },
+        Some('(') if chars.get(*pos + 1) == Some(&'(') => {
+            *pos += 1;
+            match parse_arith(chars, pos)? {
+                Some(part) => part,
+                // something like `$( (cd dir) )`
+                None => command::parse_dollar_paren(chars, pos)?,
+            }
+        },
+        Some('(') => {
+            *pos += 1;
+            command::parse_dollar_paren(chars, pos)?
+        },
+        Some(ch) if *ch == '_' || ch.is_ascii_alphabetic() => {
+            let start = *pos;
+            while chars
+                .get(*pos)
+                .is_some_and(|ch| *ch == '_' || ch.is_ascii_alphanumeric())
+            {
+                *pos += 1;
+            }
+            WordPart::Param(Param {
+                name: chars[start..*pos].iter().collect(),
+                index: None,
+                op: ParamOp::Value,
+            })
+        },
+        // without braces only a single digit is used for positional parameters
+        Some(ch) if ch.is_ascii_digit() || SPECIAL_PARAMS.contains(ch) => {
+            *pos += 1;
+            WordPart::Param(Param {
+                name: ch.to_string(),
+                index: None,
+                op: ParamOp::Value,
+            })
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(part))
+}
+
+/// Parse `$((...))`, `pos` points at the second opening parenthesis
+///
+/// Returns [None] and leaves `pos` untouched if the parentheses are not closed by `))`, in which
+/// case this is a command substitution that starts with a subshell.
+fn parse_arith(chars: &[char], pos: &mut usize) -> Result<Option<WordPart>, Error> {
+    let start = *pos + 1;
+    let end = command::closing_paren(chars, start).ok_or(Error::Unterminated(')'))?;
+    if chars.get(end + 1) != Some(&')') {
+        return Ok(None);
+    }
+    let mut inner_pos = 0;
+    let parts = parse_parts(&chars[start..end], &mut inner_pos, Terminator::Arith)?;
+    *pos = end + 2;
+    Ok(Some(WordPart::Arith(parts)))
+}
+
+/// Parse a tilde prefix, which runs up until the first slash
+///
+/// If any character in the prefix is quoted, the tilde is left as a literal.
+fn parse_tilde(chars: &[char], pos: &mut usize, parts: &mut Vec<WordPart>) {
+    let start = *pos + 1;
+    let end = chars[start..]
+        .iter()
+        .position(|ch| *ch == '/')
+        .map_or(chars.len(), |i| start + i);
+    let login = &chars[start..end];
+    if login
+        .iter()
+        .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-' | '.'))
+    {
+        parts.push(WordPart::Tilde(login.iter().collect()));
+        *pos = end;
+    }
+}
+
+/// Parse the body of a here-document
+///
+/// If any part of the delimiter is quoted the body is taken as is, otherwise parameter expansion,
+/// command substitution and arithmetic expansion are performed on it. With `strip_tabs` leading
+/// tabs are removed from every line first.
+pub fn parse_here_doc(
+    delimiter: &str,
+    body: &str,
+    strip_tabs: bool,
+) -> Result<Vec<WordPart>, Error> {
+    let body = if strip_tabs {
+        body.split_inclusive('\n')
+            .map(|line| line.trim_start_matches('\t'))
+            .collect::<String>()
+    } else {
+        body.to_string()
+    };
+    if delimiter.contains(['\'', '"', '\\']) {
+        return Ok(vec![WordPart::Quoted(body)]);
+    }
+    let chars = body.chars().collect::<Vec<_>>();
+    parse_parts(&chars, &mut 0, Terminator::HereDoc)
+}
+
+/// Expand a word into a single string
+///
+/// Quotes are removed from the result.
+pub fn expand_word<W: Expandable + ?Sized>(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    word: &W,
+) -> anyhow::Result<String> {
+    let parts = word.word_parts()?;
+    expand_parts(sh, ctx, rt, &parts, false)
+}
+
+/// Expand a word that is to be used as a pattern
+///
+/// Quoted characters are escaped so they are matched literally.
+pub fn expand_pattern<W: Expandable + ?Sized>(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    word: &W,
+) -> anyhow::Result<String> {
+    let parts = word.word_parts()?;
+    expand_parts(sh, ctx, rt, &parts, true)
+}
+
+/// Expand a word that is to be used as a regular expression
+///
+/// Quoted parts are escaped so they are matched literally.
+pub fn expand_regex<W: Expandable + ?Sized>(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    word: &W,
+) -> anyhow::Result<String> {
+    let mut regex = String::new();
+    for part in word.word_parts()?.iter() {
+        let expanded = expand_parts(sh, ctx, rt, std::slice::from_ref(part), false)?;
+        match part {
+            WordPart::Quoted(_) | WordPart::DoubleQuoted(_) => {
+                regex.push_str(&regex::escape(&expanded));
+            },
+            _ => regex.push_str(&expanded),
+        }
+    }
+    Ok(regex)
+}
+
+/// Expand and evaluate the expression of an arithmetic command
+pub fn expand_arith(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    expr: &str,
+) -> anyhow::Result<i64> {
+    let chars = expr.chars().collect::<Vec<_>>();
+    let parts = parse_parts(&chars, &mut 0, Terminator::Arith)?;
+    let expr = expand_parts(sh, ctx, rt, &parts, false)?;
+    Ok(arith::eval(rt, &expr)?)
+}
+
+pub(crate) fn expand_parts(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    parts: &[WordPart],
+    as_pattern: bool,
+) -> anyhow::Result<String> {
+    let mut expanded = String::new();
+    for part in parts {
+        match part {
+            WordPart::Literal(literal) => expanded.push_str(literal),
+            WordPart::Quoted(quoted) if as_pattern => {
+                expanded.push_str(&pattern::escape(quoted));
+            },
+            WordPart::Quoted(quoted) => expanded.push_str(quoted),
+            WordPart::DoubleQuoted(inner) => {
+                let inner = expand_parts(sh, ctx, rt, inner, false)?;
+                if as_pattern {
+                    expanded.push_str(&pattern::escape(&inner));
+                } else {
+                    expanded.push_str(&inner);
+                }
+            },
+            WordPart::Param(param) => expanded.push_str(&expand_param(sh, ctx, rt, param)?),
+            WordPart::CommandSubst(cmd) => expanded.push_str(&expand_command(sh, ctx, rt, cmd)?),
+            WordPart::ProcessSubst { cmd, input } => {
+                expanded.push_str(&expand_process_subst(sh, ctx, rt, cmd, *input)?);
+            },
+            WordPart::Arith(expr) => {
+                let expr = expand_parts(sh, ctx, rt, expr, false)?;
+                expanded.push_str(&arith::eval(rt, &expr)?.to_string());
+            },
+            WordPart::Tilde(login) => {
+                // `~` is the value of `$HOME` and `~login` the home directory of that user, the
+                // prefix is left as is if there is no such directory
+                let home = match login.is_empty() {
+                    true => rt.env.get("HOME").map(|home| home.to_string()),
+                    false => User::from_name(login)
+                        .ok()
+                        .flatten()
+                        .map(|user| user.dir.to_string_lossy().into_owned()),
+                };
+                match home {
+                    Some(home) => expanded.push_str(&home),
+                    None => {
+                        expanded.push('~');
+                        expanded.push_str(login);
+                    },
+                }
+            },
+        }
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_parts, expand_word, parse_here_doc, parse_word, Param, ParamOp, WordPart};
+    use crate::test_utils::{context, runtime, shell};
+
+    #[test]
+    fn parse_quotes() {
+        assert_eq!(
+            parse_word("a'b c'\"$X d\"\\$").unwrap(),
+            vec![
+                WordPart::Literal(String::from("a")),
+                WordPart::Quoted(String::from("b c")),
+                WordPart::DoubleQuoted(vec![
+                    WordPart::Param(Param {
+                        name: String::from("X"),
+                        index: None,
+                        op: ParamOp::Value
+                    }),
+                    WordPart::Literal(String::from(" d")),
+                ]),
+                WordPart::Quoted(String::from("$")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_tilde() {
+        assert_eq!(
+            parse_word("~/src").unwrap(),
+            vec![
+                WordPart::Tilde(String::new()),
+                WordPart::Literal(String::from("/src")),
+            ]
+        );
+        assert_eq!(
+            parse_word("'~'").unwrap(),
+            vec![WordPart::Quoted(String::from("~"))]
+        );
+    }
+
+    #[test]
+    fn parse_arith() {
+        assert_eq!(
+            parse_word("$((~1 + $X))").unwrap(),
+            vec![WordPart::Arith(vec![
+                WordPart::Literal(String::from("~1 + ")),
+                WordPart::Param(Param {
+                    name: String::from("X"),
+                    index: None,
+                    op: ParamOp::Value
+                }),
+            ])]
+        );
+        assert!(matches!(
+            parse_word("$( (echo a) )").unwrap()[..],
+            [WordPart::CommandSubst(_)]
+        ));
+        assert!(parse_word("$((1 + 2)").is_err());
+    }
+
+    #[test]
+    fn expand_arith() {
+        let (sh, mut ctx, mut rt) = (shell(), context(), runtime());
+        rt.env.set("X", "4").unwrap();
+        assert_eq!(
+            expand_word(&sh, &mut ctx, &mut rt, "x$(( X * (1 + ${#FILE}) ))").unwrap(),
+            "x60"
+        );
+        assert_eq!(
+            expand_word(&sh, &mut ctx, &mut rt, "$((X += 1))$X").unwrap(),
+            "55"
+        );
+        assert!(expand_word(&sh, &mut ctx, &mut rt, "$((1 / 0))").is_err());
+    }
+
+    #[test]
+    fn here_doc() {
+        let (sh, mut ctx, mut rt) = (shell(), context(), runtime());
+        let body = "\t$1 \"$2\" \\$ '$#'\n\tend\n";
+        let mut expand = |delimiter, strip_tabs| {
+            let parts = parse_here_doc(delimiter, body, strip_tabs).unwrap();
+            expand_parts(&sh, &mut ctx, &mut rt, &parts, false).unwrap()
+        };
+        assert_eq!(expand("EOF", false), "\tone \"two\" $ '2'\n\tend\n");
+        assert_eq!(expand("EOF", true), "one \"two\" $ '2'\nend\n");
+        assert_eq!(expand("'EOF'", false), body);
+        assert!(parse_here_doc("EOF", "$(echo\n", false).is_err());
+    }
+
+    #[test]
+    fn tilde() {
+        let (sh, mut ctx, mut rt) = (shell(), context(), runtime());
+        let root = nix::unistd::User::from_uid(nix::unistd::ROOT)
+            .unwrap()
+            .unwrap();
+        let expected = format!("{}/src", root.dir.display());
+        let word = format!("~{}/src", root.name);
+        assert_eq!(
+            expand_word(&sh, &mut ctx, &mut rt, &word).unwrap(),
+            expected
+        );
+        assert_eq!(
+            expand_word(&sh, &mut ctx, &mut rt, "~/src").unwrap(),
+            "/home/user/src"
+        );
+        assert_eq!(
+            expand_word(&sh, &mut ctx, &mut rt, "~no-such-user/src").unwrap(),
+            "~no-such-user/src"
+        );
+    }
+
+    #[test]
+    fn lone_dollar() {
+        assert_eq!(
+            parse_word("$ $/").unwrap(),
+            vec![WordPart::Literal(String::from("$ $/"))]
+        );
+    }
+}
@@ -0,0 +1,540 @@
+//! Parameter expansion
+//!
+//! Supports every form listed in POSIX 2.6.2, including the `${parameter:-word}` family of
+//! operators where `word` may itself contain further expansions.
+//!
+//! The elements of the lists kept by the shell, `BASH_REMATCH` and `PIPESTATUS`, are read with
+//! `${name[index]}`. Any other parameter is a list with its value as the only element.
+
+use shrs_core::{util::is_name, Context, Runtime, Shell};
+
+use super::{expand_parts, pattern::Pattern, Error, WordPart};
+use crate::arith;
+
+/// Parameter expansion of the form `$name` or `${...}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Param {
+    /// Name of the parameter, this may also be a positional or special parameter
+    pub name: String,
+    /// Subscript of `${name[index]}`
+    pub index: Option<Index>,
+    pub op: ParamOp,
+}
+
+/// Which elements of a list a parameter expansion refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Index {
+    /// `[@]`, every element, each in its own field inside double quotes like `"$@"`
+    At,
+    /// `[*]`, every element joined together like `"$*"`
+    Star,
+    /// Arithmetic expression for the position of a single element, negative positions count
+    /// from the end
+    Expr(Vec<WordPart>),
+}
+
+/// Operation to perform on a parameter
+///
+/// The `colon` flag corresponds to the operator being prefixed with `:`, in which case a null
+/// parameter is treated the same as an unset one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamOp {
+    /// `${parameter}`
+    Value,
+    /// `${#parameter}`
+    Length,
+    /// `${parameter:-word}`
+    Default { colon: bool, word: Vec<WordPart> },
+    /// `${parameter:=word}`
+    Assign { colon: bool, word: Vec<WordPart> },
+    /// `${parameter:?word}`
+    Error { colon: bool, word: Vec<WordPart> },
+    /// `${parameter:+word}`
+    Alternative { colon: bool, word: Vec<WordPart> },
+    /// `${parameter%word}`
+    RemoveSmallestSuffix(Vec<WordPart>),
+    /// `${parameter%%word}`
+    RemoveLargestSuffix(Vec<WordPart>),
+    /// `${parameter#word}`
+    RemoveSmallestPrefix(Vec<WordPart>),
+    /// `${parameter##word}`
+    RemoveLargestPrefix(Vec<WordPart>),
+}
+
+/// Characters that name a special parameter
+pub const SPECIAL_PARAMS: &[char] = &['@', '*', '#', '?', '-', '$', '!', '0'];
+
+/// Look up the value of a parameter, returns [None] if the parameter is unset
+pub fn lookup(rt: &Runtime, name: &str) -> Option<String> {
+    if let Ok(n) = name.parse::<usize>() {
+        return match n {
+            0 => Some(rt.name.clone()),
+            n => rt.args.get(n - 1).cloned(),
+        };
+    }
+    match name {
+        "@" => Some(rt.args.join(" ")),
+        "*" => Some(rt.args.join(&separator(rt))),
+        "#" => Some(rt.args.len().to_string()),
+        "?" => Some(rt.exit_status.to_string()),
+        "$" => Some(rt.pid.to_string()),
+        "-" => Some(rt.options.flags()),
+        "!" => rt.last_background.map(|pid| pid.to_string()),
+        // a list without a subscript is its first element
+        "BASH_REMATCH" | "PIPESTATUS" => elements(rt, name).into_iter().next(),
+        name => rt.env.get(name).cloned(),
+    }
+}
+
+/// Separator that `$*` and `${name[*]}` join the elements with, the first character of `IFS`
+fn separator(rt: &Runtime) -> String {
+    match rt.env.get("IFS") {
+        Some(ifs) => ifs.chars().take(1).collect(),
+        None => String::from(" "),
+    }
+}
+
+/// Elements of a parameter, the value of a parameter that is not a list is its only element
+pub fn elements(rt: &Runtime, name: &str) -> Vec<String> {
+    match name {
+        "BASH_REMATCH" => rt.rematch.clone(),
+        "PIPESTATUS" => rt.pipe_status.iter().map(|code| code.to_string()).collect(),
+        name => lookup(rt, name).into_iter().collect(),
+    }
+}
+
+/// Perform the parameter expansion
+pub fn expand_param(
+    sh: &Shell,
+    ctx: &mut Context,
+    rt: &mut Runtime,
+    param: &Param,
+) -> anyhow::Result<String> {
+    let value = match &param.index {
+        None => lookup(rt, &param.name),
+        Some(index @ (Index::At | Index::Star)) => {
+            let elements = elements(rt, &param.name);
+            let separator = match index {
+                Index::Star => separator(rt),
+                _ => String::from(" "),
+            };
+            (!elements.is_empty()).then(|| elements.join(&separator))
+        },
+        Some(Index::Expr(expr)) => {
+            let expr = expand_parts(sh, ctx, rt, expr, false)?;
+            let i = arith::eval(rt, &expr)?;
+            let elements = elements(rt, &param.name);
+            let i = if i < 0 { i + elements.len() as i64 } else { i };
+            usize::try_from(i)
+                .ok()
+                .and_then(|i| elements.get(i).cloned())
+        },
+    };
+    // value to use when testing if the parameter is 'set' for the colon variants
+    let is_set = |colon: bool| match &value {
+        Some(value) => !colon || !value.is_empty(),
+        None => false,
+    };
+
+    let expanded = match &param.op {
+        // number of elements rather than the length of the value
+        ParamOp::Length if matches!(param.index, Some(Index::At | Index::Star)) => {
+            elements(rt, &param.name).len().to_string()
+        },
+        ParamOp::Value | ParamOp::Length if value.is_none() && rt.options.nounset => {
+            return Err(Error::ParamUnset(param.name.clone(), "unbound variable".into()).into());
+        },
+        ParamOp::Value => value.unwrap_or_default(),
+        ParamOp::Length => value.unwrap_or_default().chars().count().to_string(),
+        ParamOp::Default { colon, word } => {
+            if is_set(*colon) {
+                value.unwrap_or_default()
+            } else {
+                expand_parts(sh, ctx, rt, word, false)?
+            }
+        },
+        // the lists are read only, so a subscript is refused even if nothing would be assigned
+        ParamOp::Assign { .. } if param.index.is_some() => {
+            return Err(Error::BadAssign(param.name.clone()).into());
+        },
+        ParamOp::Assign { colon, word } => {
+            if is_set(*colon) {
+                value.unwrap_or_default()
+            } else {
+                if !is_name(&param.name) {
+                    return Err(Error::BadAssign(param.name.clone()).into());
+                }
+                let word = expand_parts(sh, ctx, rt, word, false)?;
+                arith::assign(rt, &param.name, &word)?;
+                word
+            }
+        },
+        ParamOp::Error { colon, word } => {
+            if is_set(*colon) {
+                value.unwrap_or_default()
+            } else {
+                let msg = expand_parts(sh, ctx, rt, word, false)?;
+                let msg = if msg.is_empty() {
+                    String::from("parameter null or not set")
+                } else {
+                    msg
+                };
+                return Err(Error::ParamUnset(param.name.clone(), msg).into());
+            }
+        },
+        ParamOp::Alternative { colon, word } => {
+            if is_set(*colon) {
+                expand_parts(sh, ctx, rt, word, false)?
+            } else {
+                String::new()
+            }
+        },
+        ParamOp::RemoveSmallestSuffix(word) | ParamOp::RemoveLargestSuffix(word) => {
+            let value = value.unwrap_or_default();
+            let pattern = Pattern::new(&expand_parts(sh, ctx, rt, word, true)?);
+            let largest = matches!(param.op, ParamOp::RemoveLargestSuffix(_));
+            remove_suffix(&value, &pattern, largest).to_string()
+        },
+        ParamOp::RemoveSmallestPrefix(word) | ParamOp::RemoveLargestPrefix(word) => {
+            let value = value.unwrap_or_default();
+            let pattern = Pattern::new(&expand_parts(sh, ctx, rt, word, true)?);
+            let largest = matches!(param.op, ParamOp::RemoveLargestPrefix(_));
+            remove_prefix(&value, &pattern, largest).to_string()
+        },
+    };
+    Ok(expanded)
+}
+
+fn remove_suffix<'a>(value: &'a str, pattern: &Pattern, largest: bool) -> &'a str {
+    let mut splits = value
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(value.len()))
+        .collect::<Vec<_>>();
+    // smallest suffix starts from the end of the string
+    if !largest {
+        splits.reverse();
+    }
+    splits
+        .into_iter()
+        .find(|i| pattern.matches(&value[*i..]))
+        .map(|i| &value[..i])
+        .unwrap_or(value)
+}
+
+fn remove_prefix<'a>(value: &'a str, pattern: &Pattern, largest: bool) -> &'a str {
+    let mut splits = value
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(value.len()))
+        .collect::<Vec<_>>();
+    // largest prefix starts from the end of the string
+    if largest {
+        splits.reverse();
+    }
+    splits
+        .into_iter()
+        .find(|i| pattern.matches(&value[..*i]))
+        .map(|i| &value[i..])
+        .unwrap_or(value)
+}
+
+/// Parse the inside of a braced parameter expansion, `pos` points right after the opening `${`
+/// and is left after the closing brace
+pub(super) fn parse_braced(chars: &[char], pos: &mut usize) -> Result<Param, Error> {
+    let bad_substitution = |pos: usize| {
+        let end = chars[pos..]
+            .iter()
+            .position(|ch| *ch == '}')
+            .map_or(chars.len(), |i| pos + i + 1);
+        Error::BadSubstitution(chars[..end].iter().collect())
+    };
+
+    // `${#parameter}`, but be careful of `${#}` and `${#-word}` which refer to the `#` parameter
+    if chars.get(*pos) == Some(&'#') {
+        let mut lookahead = *pos + 1;
+        if let Some(name) = parse_name(chars, &mut lookahead) {
+            let index = parse_index(chars, &mut lookahead, &name)?;
+            if chars.get(lookahead) == Some(&'}') {
+                *pos = lookahead + 1;
+                return Ok(Param {
+                    name,
+                    index,
+                    op: ParamOp::Length,
+                });
+            }
+        }
+    }
+
+    let name = parse_name(chars, pos).ok_or_else(|| bad_substitution(*pos))?;
+    let index = parse_index(chars, pos, &name)?;
+
+    let (colon, op) = match (chars.get(*pos), chars.get(*pos + 1)) {
+        (Some('}'), _) => {
+            *pos += 1;
+            return Ok(Param {
+                name,
+                index,
+                op: ParamOp::Value,
+            });
+        },
+        (Some(':'), Some(op @ ('-' | '=' | '?' | '+'))) => {
+            *pos += 2;
+            (true, *op)
+        },
+        (Some(op @ ('-' | '=' | '?' | '+')), _) => {
+            *pos += 1;
+            (false, *op)
+        },
+        (Some('%'), Some('%')) | (Some('#'), Some('#')) => {
+            *pos += 2;
+            (true, chars[*pos - 1])
+        },
+        (Some(op @ ('%' | '#')), _) => {
+            *pos += 1;
+            (false, *op)
+        },
+        _ => return Err(bad_substitution(*pos)),
+    };
+
+    let word = super::parse_parts(chars, pos, super::Terminator::Brace)?;
+    if chars.get(*pos) != Some(&'}') {
+        return Err(Error::BadSubstitution(chars.iter().collect()));
+    }
+    *pos += 1;
+
+    // for the pattern operators, `colon` is reused to mean the doubled (largest) variant
+    let op = match op {
+        '-' => ParamOp::Default { colon, word },
+        '=' => ParamOp::Assign { colon, word },
+        '?' => ParamOp::Error { colon, word },
+        '+' => ParamOp::Alternative { colon, word },
+        '%' if colon => ParamOp::RemoveLargestSuffix(word),
+        '%' => ParamOp::RemoveSmallestSuffix(word),
+        '#' if colon => ParamOp::RemoveLargestPrefix(word),
+        '#' => ParamOp::RemoveSmallestPrefix(word),
+        _ => unreachable!(),
+    };
+    Ok(Param { name, index, op })
+}
+
+/// Parse the subscript that may follow the name of a variable, `pos` is left after the closing
+/// bracket
+fn parse_index(chars: &[char], pos: &mut usize, name: &str) -> Result<Option<Index>, Error> {
+    if chars.get(*pos) != Some(&'[') || !is_name(name) {
+        return Ok(None);
+    }
+    let index = match (chars.get(*pos + 1), chars.get(*pos + 2)) {
+        (Some('@'), Some(']')) => Index::At,
+        (Some('*'), Some(']')) => Index::Star,
+        _ => {
+            *pos += 1;
+            let expr = super::parse_parts(chars, pos, super::Terminator::Bracket)?;
+            if expr.is_empty() || chars.get(*pos) != Some(&']') {
+                return Err(Error::BadSubstitution(chars.iter().collect()));
+            }
+            *pos += 1;
+            return Ok(Some(Index::Expr(expr)));
+        },
+    };
+    *pos += 3;
+    Ok(Some(index))
+}
+
+/// Parse the name of a parameter that appears inside braces
+///
+/// Positional parameters may have more than one digit when inside braces.
+fn parse_name(chars: &[char], pos: &mut usize) -> Option<String> {
+    let start = *pos;
+    match chars.get(start) {
+        Some(ch) if *ch == '_' || ch.is_ascii_alphabetic() => {
+            while chars
+                .get(*pos)
+                .is_some_and(|ch| *ch == '_' || ch.is_ascii_alphanumeric())
+            {
+                *pos += 1;
+            }
+        },
+        Some(ch) if ch.is_ascii_digit() => {
+            while chars.get(*pos).is_some_and(|ch| ch.is_ascii_digit()) {
+                *pos += 1;
+            }
+        },
+        Some(ch) if SPECIAL_PARAMS.contains(ch) => *pos += 1,
+        _ => return None,
+    }
+    Some(chars[start..*pos].iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use shrs_core::Runtime;
+
+    use super::super::{expand_word, Error};
+    use crate::test_utils::{context, runtime, shell};
+
+    fn expand(rt: &mut Runtime, word: &str) -> String {
+        expand_word(&shell(), &mut context(), rt, word).unwrap()
+    }
+
+    fn expand_err(rt: &mut Runtime, word: &str) -> Error {
+        let err = expand_word(&shell(), &mut context(), rt, word).unwrap_err();
+        err.downcast::<Error>().unwrap()
+    }
+
+    #[test]
+    fn value() {
+        let mut rt = runtime();
+        assert_eq!(expand(&mut rt, "$FILE"), "archive.tar.gz");
+        assert_eq!(expand(&mut rt, "${FILE}.bak"), "archive.tar.gz.bak");
+        assert_eq!(expand(&mut rt, "x${UNSET}y"), "xy");
+    }
+
+    #[test]
+    fn special() {
+        let mut rt = runtime();
+        assert_eq!(expand(&mut rt, "$?"), "3");
+        assert_eq!(expand(&mut rt, "$#"), "2");
+        assert_eq!(expand(&mut rt, "$0 $1 ${2}"), "shrs one two");
+        assert_eq!(expand(&mut rt, "$@"), "one two");
+    }
+
+    #[test]
+    fn default() {
+        let mut rt = runtime();
+        assert_eq!(expand(&mut rt, "${UNSET:-fallback}"), "fallback");
+        assert_eq!(expand(&mut rt, "${EMPTY:-fallback}"), "fallback");
+        assert_eq!(expand(&mut rt, "${EMPTY-fallback}"), "");
+        assert_eq!(expand(&mut rt, "${UNSET-fallback}"), "fallback");
+        assert_eq!(expand(&mut rt, "${FILE:-fallback}"), "archive.tar.gz");
+    }
+
+    #[test]
+    fn assign() {
+        let mut rt = runtime();
+        assert_eq!(expand(&mut rt, "${NEW:=value}"), "value");
+        assert_eq!(rt.env.get("NEW").map(String::as_str), Some("value"));
+        assert_eq!(expand(&mut rt, "${NEW:=other}"), "value");
+        assert_eq!(expand(&mut rt, "${EMPTY=other}"), "");
+        assert!(matches!(
+            expand_err(&mut rt, "${3:=value}"),
+            Error::BadAssign(_)
+        ));
+    }
+
+    #[test]
+    fn error() {
+        let mut rt = runtime();
+        assert_eq!(expand(&mut rt, "${FILE:?oops}"), "archive.tar.gz");
+        assert_eq!(expand(&mut rt, "${EMPTY?oops}"), "");
+        match expand_err(&mut rt, "${UNSET:?custom message}") {
+            Error::ParamUnset(name, msg) => {
+                assert_eq!(name, "UNSET");
+                assert_eq!(msg, "custom message");
+            },
+            err => panic!("unexpected {err:?}"),
+        }
+        assert!(matches!(
+            expand_err(&mut rt, "${EMPTY:?}"),
+            Error::ParamUnset(..)
+        ));
+    }
+
+    #[test]
+    fn alternative() {
+        let mut rt = runtime();
+        assert_eq!(expand(&mut rt, "${FILE:+set}"), "set");
+        assert_eq!(expand(&mut rt, "${EMPTY:+set}"), "");
+        assert_eq!(expand(&mut rt, "${EMPTY+set}"), "set");
+        assert_eq!(expand(&mut rt, "${UNSET+set}"), "");
+    }
+
+    #[test]
+    fn length() {
+        let mut rt = runtime();
+        assert_eq!(expand(&mut rt, "${#FILE}"), "14");
+        assert_eq!(expand(&mut rt, "${#UNSET}"), "0");
+        assert_eq!(expand(&mut rt, "${#}"), "2");
+    }
+
+    #[test]
+    fn index() {
+        let mut rt = runtime();
+        rt.rematch = vec![String::from("ab"), String::from("a"), String::from("b")];
+        assert_eq!(expand(&mut rt, "$BASH_REMATCH"), "ab");
+        assert_eq!(
+            expand(&mut rt, "${BASH_REMATCH[2]}${BASH_REMATCH[0]}"),
+            "bab"
+        );
+        assert_eq!(expand(&mut rt, "${BASH_REMATCH[$# - 1]}"), "a");
+        assert_eq!(expand(&mut rt, "${BASH_REMATCH[-1]}"), "b");
+        assert_eq!(expand(&mut rt, "${BASH_REMATCH[@]}"), "ab a b");
+        assert_eq!(
+            expand(&mut rt, "${#BASH_REMATCH[*]} ${#BASH_REMATCH[1]}"),
+            "3 1"
+        );
+        assert_eq!(expand(&mut rt, "${BASH_REMATCH[5]:-none}"), "none");
+        // any other variable has its value as the only element
+        assert_eq!(expand(&mut rt, "${FILE[0]}"), "archive.tar.gz");
+        assert_eq!(expand(&mut rt, "${FILE[1]}"), "");
+        assert!(matches!(
+            expand_err(&mut rt, "${FILE[]}"),
+            Error::BadSubstitution(_)
+        ));
+        assert!(matches!(
+            expand_err(&mut rt, "${FILE[0]:=x}"),
+            Error::BadAssign(_)
+        ));
+        assert!(matches!(
+            expand_err(&mut rt, "${BASH_REMATCH[5]=x}"),
+            Error::BadAssign(_)
+        ));
+    }
+
+    #[test]
+    fn remove_suffix() {
+        let mut rt = runtime();
+        assert_eq!(expand(&mut rt, "${FILE%.*}"), "archive.tar");
+        assert_eq!(expand(&mut rt, "${FILE%%.*}"), "archive");
+        assert_eq!(expand(&mut rt, "${FILE%.zip}"), "archive.tar.gz");
+    }
+
+    #[test]
+    fn remove_prefix() {
+        let mut rt = runtime();
+        assert_eq!(expand(&mut rt, "${FILE#*.}"), "tar.gz");
+        assert_eq!(expand(&mut rt, "${FILE##*.}"), "gz");
+        assert_eq!(expand(&mut rt, "${HOME#/home/}"), "user");
+    }
+
+    #[test]
+    fn quoted_pattern() {
+        let mut rt = runtime();
+        rt.env.set("GLOB", "a*b*").unwrap();
+        assert_eq!(expand(&mut rt, "${GLOB%'*'}"), "a*b");
+        assert_eq!(expand(&mut rt, "${GLOB%%\\**}"), "a");
+        assert_eq!(expand(&mut rt, "${GLOB%%*}"), "");
+    }
+
+    #[test]
+    fn nested_word() {
+        let mut rt = runtime();
+        assert_eq!(expand(&mut rt, "${UNSET:-${FILE%%.*}}"), "archive");
+        assert_eq!(expand(&mut rt, "${UNSET:-$HOME/x}"), "/home/user/x");
+        assert_eq!(expand(&mut rt, "${UNSET:-'a}b'}"), "a}b");
+        assert_eq!(expand(&mut rt, "${UNSET:-\"$1 $2\"}"), "one two");
+    }
+
+    #[test]
+    fn bad_substitution() {
+        let mut rt = runtime();
+        assert!(matches!(
+            expand_err(&mut rt, "${FILE/a/b}"),
+            Error::BadSubstitution(_)
+        ));
+        assert!(matches!(
+            expand_err(&mut rt, "${FILE"),
+            Error::BadSubstitution(_)
+        ));
+    }
+}
@@ -0,0 +1,320 @@
+//! Pattern matching notation
+//!
+//! Implements the patterns described in POSIX 2.13, which are used by parameter expansion,
+//! pathname expansion and case statements. A backslash in the pattern makes the next character
+//! match literally, which is how quoted parts of a word are represented.
+
+/// A single element of a compiled pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Match this exact character
+    Char(char),
+    /// `?` matches any single character
+    Any,
+    /// `*` matches any string, including the empty string
+    Star,
+    /// Bracket expression like `[a-z]` or `[!abc]`
+    Bracket {
+        negate: bool,
+        items: Vec<BracketItem>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BracketItem {
+    Char(char),
+    Range(char, char),
+    /// Character class like `[:alpha:]`, holds the name of the class
+    Class(String),
+}
+
+impl BracketItem {
+    fn matches(&self, ch: char) -> bool {
+        match self {
+            BracketItem::Char(c) => *c == ch,
+            BracketItem::Range(lo, hi) => *lo <= ch && ch <= *hi,
+            BracketItem::Class(class) => match class.as_str() {
+                "alnum" => ch.is_alphanumeric(),
+                "alpha" => ch.is_alphabetic(),
+                "blank" => ch == ' ' || ch == '\t',
+                "cntrl" => ch.is_control(),
+                "digit" => ch.is_ascii_digit(),
+                "graph" => !ch.is_whitespace() && !ch.is_control(),
+                "lower" => ch.is_lowercase(),
+                "print" => !ch.is_control(),
+                "punct" => ch.is_ascii_punctuation(),
+                "space" => ch.is_whitespace(),
+                "upper" => ch.is_uppercase(),
+                "xdigit" => ch.is_ascii_hexdigit(),
+                // an unknown class matches nothing
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Compiled shell pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+}
+
+impl Pattern {
+    /// Compile a pattern
+    ///
+    /// Malformed bracket expressions are not an error, the opening bracket is matched literally
+    /// instead.
+    pub fn new(pattern: &str) -> Self {
+        let chars = pattern.chars().collect::<Vec<_>>();
+        let mut tokens = vec![];
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '\\' if i + 1 < chars.len() => {
+                    tokens.push(Token::Char(chars[i + 1]));
+                    i += 2;
+                },
+                '?' => {
+                    tokens.push(Token::Any);
+                    i += 1;
+                },
+                '*' => {
+                    // consecutive stars are equivalent to a single one
+                    if tokens.last() != Some(&Token::Star) {
+                        tokens.push(Token::Star);
+                    }
+                    i += 1;
+                },
+                '[' => match parse_bracket(&chars, i) {
+                    Some((token, next)) => {
+                        tokens.push(token);
+                        i = next;
+                    },
+                    None => {
+                        tokens.push(Token::Char('['));
+                        i += 1;
+                    },
+                },
+                ch => {
+                    tokens.push(Token::Char(ch));
+                    i += 1;
+                },
+            }
+        }
+        Pattern { tokens }
+    }
+
+    /// Check if the entire string is matched by the pattern
+    pub fn matches(&self, s: &str) -> bool {
+        let chars = s.chars().collect::<Vec<_>>();
+        self.matches_chars(&chars)
+    }
+
+    /// Returns true if the pattern contains any special characters
+    ///
+    /// Words that are not patterns do not need to go through pathname expansion.
+    pub fn is_literal(&self) -> bool {
+        self.tokens.iter().all(|t| matches!(t, Token::Char(_)))
+    }
+
+    fn matches_chars(&self, chars: &[char]) -> bool {
+        // iterative matching that backtracks to the most recent star on failure
+        let (mut t, mut c) = (0, 0);
+        let mut backtrack: Option<(usize, usize)> = None;
+        while c < chars.len() {
+            let matched = match self.tokens.get(t) {
+                Some(Token::Star) => {
+                    backtrack = Some((t, c));
+                    t += 1;
+                    continue;
+                },
+                Some(Token::Char(ch)) => *ch == chars[c],
+                Some(Token::Any) => true,
+                Some(Token::Bracket { negate, items }) => {
+                    items.iter().any(|item| item.matches(chars[c])) != *negate
+                },
+                None => false,
+            };
+            if matched {
+                t += 1;
+                c += 1;
+            } else if let Some((star_t, star_c)) = backtrack {
+                // let the last star consume one more character and try again
+                t = star_t + 1;
+                c = star_c + 1;
+                backtrack = Some((star_t, star_c + 1));
+            } else {
+                return false;
+            }
+        }
+        self.tokens[t..].iter().all(|t| *t == Token::Star)
+    }
+}
+
+/// Parse bracket expression starting at `start`, returns the token and the index after the
+/// closing bracket
+fn parse_bracket(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut i = start + 1;
+    let negate = matches!(chars.get(i), Some('!') | Some('^'));
+    if negate {
+        i += 1;
+    }
+
+    let mut items = vec![];
+    let mut first = true;
+    loop {
+        let ch = match chars.get(i) {
+            // a closing bracket right after the opening one is taken literally
+            Some(']') if !first => return Some((Token::Bracket { negate, items }, i + 1)),
+            Some('\\') => {
+                i += 1;
+                *chars.get(i)?
+            },
+            // character class `[:name:]`, equivalence class `[=c=]` or collating symbol `[.c.]`
+            Some('[') if matches!(chars.get(i + 1), Some(':' | '=' | '.')) => {
+                match parse_class(chars, i) {
+                    Some((BracketItem::Char(ch), next)) => {
+                        i = next - 1;
+                        ch
+                    },
+                    Some((item, next)) => {
+                        items.push(item);
+                        first = false;
+                        i = next;
+                        continue;
+                    },
+                    None => '[',
+                }
+            },
+            Some(ch) => *ch,
+            None => return None,
+        };
+        first = false;
+        i += 1;
+
+        // range expression
+        if chars.get(i) == Some(&'-') && chars.get(i + 1).is_some_and(|c| *c != ']') {
+            let hi = match chars.get(i + 1)? {
+                '\\' => {
+                    i += 1;
+                    *chars.get(i + 1)?
+                },
+                hi => *hi,
+            };
+            items.push(BracketItem::Range(ch, hi));
+            i += 2;
+        } else {
+            items.push(BracketItem::Char(ch));
+        }
+    }
+}
+
+/// Parse a class inside a bracket expression starting at `start`, returns the item and the index
+/// after the class
+///
+/// Equivalence classes and collating symbols are only supported for single characters, which
+/// stand for themselves.
+fn parse_class(chars: &[char], start: usize) -> Option<(BracketItem, usize)> {
+    let delim = chars[start + 1];
+    let name_start = start + 2;
+    let len = chars[name_start..]
+        .windows(2)
+        .position(|w| w[0] == delim && w[1] == ']')?;
+    let name = &chars[name_start..name_start + len];
+    let next = name_start + len + 2;
+    match (delim, name) {
+        (':', name) => Some((BracketItem::Class(name.iter().collect()), next)),
+        (_, [ch]) => Some((BracketItem::Char(*ch), next)),
+        _ => None,
+    }
+}
+
+/// Escape characters that have special meaning in patterns so they match literally
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        if matches!(ch, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// Remove the backslashes added by [escape]
+pub fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => unescaped.extend(chars.next()),
+            ch => unescaped.push(ch),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, unescape, Pattern};
+
+    #[test]
+    fn literal() {
+        assert!(Pattern::new("abc").matches("abc"));
+        assert!(!Pattern::new("abc").matches("abcd"));
+        assert!(Pattern::new("abc").is_literal());
+    }
+
+    #[test]
+    fn star() {
+        let pat = Pattern::new("*.rs");
+        assert!(pat.matches("main.rs"));
+        assert!(pat.matches(".rs"));
+        assert!(!pat.matches("main.rsx"));
+        assert!(Pattern::new("a*b*c").matches("aXXbYYbc"));
+        assert!(Pattern::new("*").matches(""));
+    }
+
+    #[test]
+    fn question_mark() {
+        assert!(Pattern::new("a?c").matches("abc"));
+        assert!(!Pattern::new("a?c").matches("ac"));
+    }
+
+    #[test]
+    fn bracket() {
+        let pat = Pattern::new("[a-c]x");
+        assert!(pat.matches("bx"));
+        assert!(!pat.matches("dx"));
+        assert!(Pattern::new("[!a-c]").matches("d"));
+        assert!(!Pattern::new("[!a-c]").matches("a"));
+        assert!(Pattern::new("[]]").matches("]"));
+        assert!(Pattern::new("[a-]").matches("-"));
+        // unterminated bracket is literal
+        assert!(Pattern::new("[ab").matches("[ab"));
+    }
+
+    #[test]
+    fn classes() {
+        let pat = Pattern::new("[[:upper:]][[:digit:]_]*");
+        assert!(pat.matches("A1"));
+        assert!(pat.matches("Z_x"));
+        assert!(!pat.matches("a1"));
+        assert!(Pattern::new("[![:space:][:punct:]]").matches("x"));
+        assert!(!Pattern::new("[![:space:][:punct:]]").matches("."));
+        assert!(Pattern::new("[[=a=]-c]").matches("b"));
+        assert!(Pattern::new("[[.-.]]").matches("-"));
+        assert!(!Pattern::new("[[:nonexistent:]]").matches("a"));
+        // without the closing delimiter the bracket is an ordinary character
+        assert!(Pattern::new("[[:a]").matches(":"));
+    }
+
+    #[test]
+    fn escaped() {
+        assert!(Pattern::new("\\*").matches("*"));
+        assert!(!Pattern::new("\\*").matches("a"));
+        assert!(Pattern::new(&escape("a*[b]?")).matches("a*[b]?"));
+        assert!(Pattern::new(&escape("a*")).is_literal());
+        assert_eq!(unescape(&escape("a*[b]\\")), "a*[b]\\");
+    }
+}
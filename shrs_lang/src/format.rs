@@ -0,0 +1,792 @@
+//! Pretty printer that turns an AST back into source text
+//!
+//! The output puts one command per line, indents the bodies of compound commands and normalises
+//! the spacing around operators and redirections. Parsing the output gives back the same AST,
+//! apart from the spans. Brace groups are not part of the AST, so they are added back wherever a
+//! list of commands appears in a place that only takes a single command.
+//!
+//! When formatting source text, comments on a line of their own are kept in front of the command
+//! that follows them and comments at the end of a line stay at the end of that line.
+
+use std::collections::VecDeque;
+
+use crate::{
+    ast::{self, Command},
+    parser::{Error, Parser},
+    Lexer, Span,
+};
+
+/// Options for [format]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Number of spaces per level of indentation, tabs are used if this is zero
+    pub indent: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions { indent: 4 }
+    }
+}
+
+/// Format a command as source text, the output ends with a newline unless it is empty
+pub fn format(cmd: &Command, options: &FormatOptions) -> String {
+    Printer::new(options, "", vec![]).finish(cmd)
+}
+
+/// Parse and format source text, keeping its comments
+pub fn format_source(source: &str, options: &FormatOptions) -> Result<String, Error> {
+    let mut lexer = Lexer::new(source);
+    let cmd = Parser::new().parse(lexer.clone())?;
+    // lex again to find the comments, the parser takes ownership of the lexer
+    lexer.by_ref().for_each(drop);
+    let comments = lexer.comments().to_vec();
+    Ok(Printer::new(options, source, comments).finish(&cmd))
+}
+
+struct Printer<'a> {
+    options: &'a FormatOptions,
+    out: String,
+    level: usize,
+    /// Bodies and delimiters of here-documents that start after the current line
+    heredocs: Vec<(String, String)>,
+    /// Source text the AST was parsed from, used to look up comments
+    source: &'a str,
+    /// Comments that were not written yet, in the order they appear in the source
+    comments: VecDeque<Span>,
+    /// Location in the source of the command at the start of the current line
+    line_pos: Option<usize>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(options: &'a FormatOptions, source: &'a str, comments: Vec<Span>) -> Self {
+        Printer {
+            options,
+            out: String::new(),
+            level: 0,
+            heredocs: vec![],
+            source,
+            comments: comments.into(),
+            line_pos: None,
+        }
+    }
+
+    fn finish(mut self, cmd: &Command) -> String {
+        self.program(cmd);
+        if !self.out.is_empty() {
+            self.newline();
+        }
+        while let Some(comment) = self.comments.pop_front() {
+            self.write(&self.source[comment.start..comment.end]);
+            self.newline();
+        }
+        self.out
+    }
+
+    fn write(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    /// Write the comments that come before `pos` in the source on lines of their own and
+    /// remember that the current line holds the command at `pos`
+    fn line_start(&mut self, pos: Option<usize>) {
+        let Some(pos) = pos else {
+            return;
+        };
+        // comments can only be moved to a line of their own at the start of a line
+        if self.out.trim_end_matches([' ', '\t']).ends_with('\n') || self.out.is_empty() {
+            self.line_pos = None;
+            while let Some(comment) = self.comments.front().copied() {
+                if comment.start >= pos {
+                    break;
+                }
+                self.comments.pop_front();
+                self.write(&self.source[comment.start..comment.end]);
+                self.newline();
+            }
+        }
+        self.line_pos = Some(pos);
+    }
+
+    /// End the current line
+    fn newline(&mut self) {
+        self.end_line(None)
+    }
+
+    /// End the current line, followed by any pending here-documents
+    ///
+    /// A comment that was at the end of the same line in the source is kept at the end of the
+    /// line, unless it comes after the command at `next`.
+    fn end_line(&mut self, next: Option<usize>) {
+        if let (Some(pos), Some(comment)) = (self.line_pos.take(), self.comments.front().copied()) {
+            let same_line = comment.start >= pos && !self.source[pos..comment.start].contains('\n');
+            if same_line && next.is_none_or(|next| comment.start < next) {
+                self.comments.pop_front();
+                self.write(" ");
+                self.write(&self.source[comment.start..comment.end]);
+            }
+        }
+        self.out.push('\n');
+        for (body, delimiter) in std::mem::take(&mut self.heredocs) {
+            self.out.push_str(&body);
+            self.out.push_str(&delimiter);
+            self.out.push('\n');
+        }
+        let indent = match self.options.indent {
+            0 => "\t".repeat(self.level),
+            n => " ".repeat(n * self.level),
+        };
+        self.out.push_str(&indent);
+    }
+
+    /// Commands at the top level of a script, separated by newlines
+    fn program(&mut self, cmd: &Command) {
+        match cmd {
+            Command::SeqList(l, Some(r)) => {
+                self.program(l);
+                self.end_line(first_pos(r));
+                self.line_start(first_pos(r));
+                self.complete(r);
+            },
+            Command::SeqList(l, None) => self.program(l),
+            _ => {
+                self.line_start(first_pos(cmd));
+                self.complete(cmd);
+            },
+        }
+    }
+
+    /// A list with an optional trailing `&`, which can only end a line at the top level
+    fn complete(&mut self, cmd: &Command) {
+        match cmd {
+            Command::AsyncList(l, None) => {
+                self.list(l);
+                self.write(" &");
+            },
+            Command::SeqList(l, None) => self.list(l),
+            _ => self.list(cmd),
+        }
+    }
+
+    /// Commands separated by `;` or `&` on a single line
+    fn list(&mut self, cmd: &Command) {
+        match cmd {
+            Command::SeqList(l, Some(r)) => {
+                self.list(l);
+                self.write("; ");
+                self.list(r);
+            },
+            Command::AsyncList(l, Some(r)) => {
+                self.list(l);
+                self.write(" & ");
+                self.list(r);
+            },
+            Command::AsyncList(l, None) => {
+                self.list(l);
+                self.write(" &");
+            },
+            _ => self.and_or(cmd),
+        }
+    }
+
+    /// Body of a compound command, one command per line
+    fn term(&mut self, cmd: &Command) {
+        match cmd {
+            Command::SeqList(l, Some(r)) => {
+                self.term_left(l);
+                self.end_line(first_pos(r));
+                self.term(r);
+            },
+            Command::AsyncList(l, Some(r)) => {
+                self.term_left(l);
+                self.write(" &");
+                self.end_line(first_pos(r));
+                self.term(r);
+            },
+            Command::AsyncList(l, None) => {
+                self.term_left(l);
+                self.write(" &");
+            },
+            Command::SeqList(l, None) => self.term(l),
+            _ => {
+                self.line_start(first_pos(cmd));
+                self.and_or(cmd);
+            },
+        }
+    }
+
+    /// Left side of a list in a compound command, which can not end with `&` itself
+    fn term_left(&mut self, cmd: &Command) {
+        match ends_async(cmd) {
+            true => self.brace_group(cmd),
+            false => self.term(cmd),
+        }
+    }
+
+    fn and_or(&mut self, cmd: &Command) {
+        match cmd {
+            Command::And(a, b) => {
+                self.and_or(a);
+                self.write(" && ");
+                self.pipeline(b);
+            },
+            Command::Or(a, b) => {
+                self.and_or(a);
+                self.write(" || ");
+                self.pipeline(b);
+            },
+            _ => self.pipeline(cmd),
+        }
+    }
+
+    fn pipeline(&mut self, cmd: &Command) {
+        match cmd {
+            Command::Not(cmd) => {
+                self.write("! ");
+                self.pipe_sequence(cmd);
+            },
+            _ => self.pipe_sequence(cmd),
+        }
+    }
+
+    fn pipe_sequence(&mut self, cmd: &Command) {
+        match cmd {
+            Command::Pipeline(a, b) => {
+                self.pipe_sequence(a);
+                self.write(" | ");
+                self.command(b);
+            },
+            _ => self.command(cmd),
+        }
+    }
+
+    fn command(&mut self, cmd: &Command) {
+        match cmd {
+            Command::Simple {
+                assigns,
+                redirects,
+                args,
+            } => self.simple(assigns, redirects, args),
+            Command::Subshell(body) => self.subshell(body),
+            Command::If { conds, else_part } => {
+                for (i, cond) in conds.iter().enumerate() {
+                    self.write(if i == 0 { "if " } else { "elif " });
+                    self.condition(&cond.cond, "then");
+                    self.block(&cond.body);
+                }
+                if let Some(else_part) = else_part {
+                    self.write("else");
+                    self.block(else_part);
+                }
+                self.write("fi");
+            },
+            Command::While { cond, body } | Command::Until { cond, body } => {
+                let keyword = match cmd {
+                    Command::While { .. } => "while ",
+                    _ => "until ",
+                };
+                self.write(keyword);
+                self.condition(cond, "do");
+                self.block(body);
+                self.write("done");
+            },
+            Command::For {
+                name,
+                wordlist,
+                body,
+            }
+            | Command::Select {
+                name,
+                wordlist,
+                body,
+            } => {
+                let keyword = match cmd {
+                    Command::For { .. } => "for ",
+                    _ => "select ",
+                };
+                self.write(keyword);
+                self.write(name);
+                if let Some(wordlist) = wordlist {
+                    self.write(" in");
+                    for word in wordlist {
+                        self.write(" ");
+                        self.write(word);
+                    }
+                }
+                self.write("; do");
+                self.block(body);
+                self.write("done");
+            },
+            Command::Case { word, arms } => {
+                self.write("case ");
+                self.write(word);
+                self.write(" in");
+                self.level += 1;
+                for arm in arms {
+                    let pos = arm.pattern.first().map(|w| w.span.start);
+                    self.end_line(pos);
+                    self.line_start(pos);
+                    let pattern = arm
+                        .pattern
+                        .iter()
+                        .map(|w| w.text.as_str())
+                        .collect::<Vec<_>>();
+                    self.write(&pattern.join(" | "));
+                    self.write(")");
+                    self.level += 1;
+                    if *arm.body != Command::None {
+                        self.newline();
+                        self.term(&arm.body);
+                    }
+                    self.newline();
+                    self.write(";;");
+                    self.level -= 1;
+                }
+                self.level -= 1;
+                self.newline();
+                self.write("esac");
+            },
+            Command::Arith(expr) => {
+                self.write("((");
+                self.write(expr);
+                self.write("))");
+            },
+            Command::Cond(expr) => {
+                self.write("[[ ");
+                self.write(expr);
+                self.write(" ]]");
+            },
+            Command::Fn { fname, body } => {
+                self.write(fname);
+                if let Command::Redirected { .. } = **body {
+                    self.write("() ");
+                    self.command(body);
+                    return;
+                }
+                self.write("() {");
+                self.block(body);
+                self.write("}");
+            },
+            Command::Redirected { cmd, redirects } => {
+                match **cmd {
+                    Command::Subshell(_)
+                    | Command::If { .. }
+                    | Command::While { .. }
+                    | Command::Until { .. }
+                    | Command::For { .. }
+                    | Command::Select { .. }
+                    | Command::Case { .. }
+                    | Command::Arith(_)
+                    | Command::Cond(_) => self.command(cmd),
+                    _ => self.brace_group(cmd),
+                }
+                for redirect in redirects {
+                    let redirect = self.redirect(redirect);
+                    self.write(" ");
+                    self.write(&redirect);
+                }
+            },
+            Command::None => {},
+            _ => self.brace_group(cmd),
+        }
+    }
+
+    fn simple(&mut self, assigns: &[ast::Assign], redirects: &[ast::Redirect], args: &[ast::Word]) {
+        let mut parts = vec![];
+        parts.extend(assigns.iter().map(|a| format!("{}={}", a.var, a.val)));
+        parts.extend(args.iter().map(|w| w.to_string()));
+        for redirect in redirects {
+            parts.push(self.redirect(redirect));
+        }
+        self.write(&parts.join(" "));
+    }
+
+    fn redirect(&mut self, redirect: &ast::Redirect) -> String {
+        let op = match &redirect.mode {
+            ast::RedirectMode::Read => "<",
+            ast::RedirectMode::Write => ">",
+            ast::RedirectMode::Clobber => ">|",
+            ast::RedirectMode::HereDoc { body, strip_tabs } => {
+                // quotes only mark that the body is not expanded, they are not part of the delimiter
+                let delimiter = redirect
+                    .file
+                    .chars()
+                    .filter(|ch| !matches!(ch, '\'' | '"' | '\\'))
+                    .collect::<String>();
+                self.heredocs.push((body.text.clone(), delimiter));
+                match strip_tabs {
+                    true => "<<-",
+                    false => "<<",
+                }
+            },
+            ast::RedirectMode::HereString => "<<<",
+            ast::RedirectMode::WriteAppend => ">>",
+            ast::RedirectMode::ReadDup => "<&",
+            ast::RedirectMode::WriteDup => ">&",
+            ast::RedirectMode::ReadWrite => "<>",
+            ast::RedirectMode::WriteAll => "&>",
+            ast::RedirectMode::AppendAll => "&>>",
+        };
+        let n = redirect.n.map(|n| n.to_string()).unwrap_or_default();
+        format!("{n}{op}{}", redirect.file)
+    }
+
+    /// Condition of an `if` or loop, followed by the keyword that starts the body
+    fn condition(&mut self, cond: &Command, keyword: &str) {
+        self.term(cond);
+        // `&` already separates the condition from the keyword
+        if !ends_async(cond) {
+            self.write(";");
+        }
+        self.write(" ");
+        self.write(keyword);
+    }
+
+    /// Indented body of a compound command, the closing keyword goes on the line after it
+    fn block(&mut self, body: &Command) {
+        self.level += 1;
+        self.end_line(first_pos(body));
+        self.term(body);
+        self.block_comments(last_pos(body));
+        self.level -= 1;
+        self.newline();
+    }
+
+    /// Write the comments between the end of a block and its closing keyword, which has no
+    /// location of its own
+    fn block_comments(&mut self, end: Option<usize>) {
+        let Some(mut end) = end else {
+            return;
+        };
+        while let Some(comment) = self.comments.front().copied() {
+            let Some(gap) = self.source.get(end..comment.start) else {
+                break;
+            };
+            if gap.contains(|ch: char| !ch.is_whitespace() && ch != ';' && ch != '&') {
+                break;
+            }
+            match gap.contains('\n') {
+                true => self.newline(),
+                false => self.write(" "),
+            }
+            self.comments.pop_front();
+            self.write(&self.source[comment.start..comment.end]);
+            self.line_pos = None;
+            end = comment.end;
+        }
+    }
+
+    fn brace_group(&mut self, cmd: &Command) {
+        self.write("{");
+        self.block(cmd);
+        self.write("}");
+    }
+
+    fn subshell(&mut self, body: &Command) {
+        if is_list(body) {
+            self.write("(");
+            self.block(body);
+            self.write(")");
+            return;
+        }
+
+        self.write("(");
+        let start = self.out.len();
+        self.and_or(body);
+        // `((` starts an arithmetic command
+        if self.out[start..].starts_with('(') {
+            self.out.insert(start, ' ');
+            self.write(" ");
+        }
+        self.write(")");
+    }
+}
+
+/// Location in the source where the command starts
+///
+/// Keywords are not part of the AST, so the first word after them is used instead.
+fn first_pos(cmd: &Command) -> Option<usize> {
+    match cmd {
+        Command::Simple {
+            assigns,
+            redirects,
+            args,
+        } => assigns
+            .iter()
+            .map(|a| a.span)
+            .chain(redirects.iter().map(|r| r.span))
+            .chain(args.iter().map(|w| w.span))
+            .map(|span| span.start)
+            .min(),
+        Command::Pipeline(cmd, _)
+        | Command::And(cmd, _)
+        | Command::Or(cmd, _)
+        | Command::AsyncList(cmd, _)
+        | Command::SeqList(cmd, _)
+        | Command::Not(cmd)
+        | Command::Subshell(cmd)
+        | Command::While { cond: cmd, .. }
+        | Command::Until { cond: cmd, .. }
+        | Command::Redirected { cmd, .. } => first_pos(cmd),
+        Command::If { conds, .. } => conds.first().and_then(|cond| first_pos(&cond.cond)),
+        Command::For { name, .. } | Command::Select { name, .. } => Some(name.span.start),
+        Command::Case { word, .. } => Some(word.span.start),
+        Command::Fn { fname, .. } => Some(fname.span.start),
+        Command::Arith(_) | Command::Cond(_) | Command::None => None,
+    }
+}
+
+/// Location in the source where the command ends
+fn last_pos(cmd: &Command) -> Option<usize> {
+    match cmd {
+        Command::Simple {
+            assigns,
+            redirects,
+            args,
+        } => assigns
+            .iter()
+            .map(|a| a.span)
+            .chain(redirects.iter().map(|r| r.span))
+            .chain(args.iter().map(|w| w.span))
+            .map(|span| span.end)
+            .max(),
+        Command::Pipeline(_, cmd)
+        | Command::And(_, cmd)
+        | Command::Or(_, cmd)
+        | Command::AsyncList(_, Some(cmd))
+        | Command::SeqList(_, Some(cmd))
+        | Command::AsyncList(cmd, None)
+        | Command::SeqList(cmd, None)
+        | Command::Not(cmd)
+        | Command::Subshell(cmd)
+        | Command::While { body: cmd, .. }
+        | Command::Until { body: cmd, .. }
+        | Command::For { body: cmd, .. }
+        | Command::Select { body: cmd, .. }
+        | Command::Fn { body: cmd, .. } => last_pos(cmd),
+        Command::If { conds, else_part } => match else_part {
+            Some(else_part) => last_pos(else_part),
+            None => conds.last().and_then(|cond| last_pos(&cond.body)),
+        },
+        Command::Case { word, arms } => match arms.last() {
+            Some(arm) => last_pos(&arm.body).or(arm.pattern.last().map(|w| w.span.end)),
+            None => Some(word.span.end),
+        },
+        Command::Redirected { cmd, redirects } => redirects
+            .last()
+            .map(|r| r.span.end)
+            .or_else(|| last_pos(cmd)),
+        Command::Arith(_) | Command::Cond(_) | Command::None => None,
+    }
+}
+
+fn is_list(cmd: &Command) -> bool {
+    matches!(cmd, Command::SeqList(..) | Command::AsyncList(..))
+}
+
+/// Check if the command ends with a `&` that has no command after it
+fn ends_async(cmd: &Command) -> bool {
+    match cmd {
+        Command::AsyncList(_, None) => true,
+        Command::SeqList(l, None) => ends_async(l),
+        Command::SeqList(_, Some(r)) | Command::AsyncList(_, Some(r)) => ends_async(r),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format, format_source, FormatOptions};
+    use crate::{ast, Lexer, Parser, Span};
+
+    /// Reset all spans so that ASTs parsed from different source text can be compared
+    fn strip_spans(cmd: &mut ast::Command) {
+        fn word(w: &mut ast::Word) {
+            w.span = Span::default();
+        }
+        match cmd {
+            ast::Command::Simple {
+                assigns,
+                redirects,
+                args,
+            } => {
+                assigns.iter_mut().for_each(|a| a.span = Span::default());
+                for redirect in redirects {
+                    redirect.span = Span::default();
+                    word(&mut redirect.file);
+                }
+                args.iter_mut().for_each(word);
+            },
+            ast::Command::Pipeline(a, b) | ast::Command::And(a, b) | ast::Command::Or(a, b) => {
+                strip_spans(a);
+                strip_spans(b);
+            },
+            ast::Command::AsyncList(a, b) | ast::Command::SeqList(a, b) => {
+                strip_spans(a);
+                if let Some(b) = b {
+                    strip_spans(b);
+                }
+            },
+            ast::Command::Not(c) | ast::Command::Subshell(c) => strip_spans(c),
+            ast::Command::If { conds, else_part } => {
+                for cond in conds {
+                    strip_spans(&mut cond.cond);
+                    strip_spans(&mut cond.body);
+                }
+                if let Some(else_part) = else_part {
+                    strip_spans(else_part);
+                }
+            },
+            ast::Command::While { cond, body } | ast::Command::Until { cond, body } => {
+                strip_spans(cond);
+                strip_spans(body);
+            },
+            ast::Command::For {
+                name,
+                wordlist,
+                body,
+            }
+            | ast::Command::Select {
+                name,
+                wordlist,
+                body,
+            } => {
+                word(name);
+                wordlist.iter_mut().flatten().for_each(word);
+                strip_spans(body);
+            },
+            ast::Command::Case { word: w, arms } => {
+                word(w);
+                for arm in arms {
+                    arm.pattern.iter_mut().for_each(word);
+                    strip_spans(&mut arm.body);
+                }
+            },
+            ast::Command::Fn { fname, body } => {
+                word(fname);
+                strip_spans(body);
+            },
+            ast::Command::Redirected { cmd, redirects } => {
+                strip_spans(cmd);
+                for redirect in redirects {
+                    redirect.span = Span::default();
+                    word(&mut redirect.file);
+                }
+            },
+            ast::Command::Arith(_) | ast::Command::Cond(_) | ast::Command::None => {},
+        }
+    }
+
+    fn parse(source: &str) -> ast::Command {
+        let mut cmd = Parser::new().parse(Lexer::new(source)).unwrap();
+        strip_spans(&mut cmd);
+        cmd
+    }
+
+    /// Check that formatting gives the expected output and parses back to the same AST
+    fn check(source: &str, expected: &str) {
+        let formatted = format_source(source, &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(parse(&formatted), parse(source), "{formatted}");
+        assert_eq!(
+            format_source(&formatted, &FormatOptions::default()).unwrap(),
+            formatted
+        );
+    }
+
+    #[test]
+    fn simple() {
+        check("", "");
+        check("echo   hi   there", "echo hi there\n");
+        check("a=1  b='x y'   env", "a=1 b='x y' env\n");
+        check("cat  < input  >>out   >&2", "cat <input >>out >&2\n");
+        check(
+            "cmd 2>&1  >out 3<> f  &>> log",
+            "cmd 2>&1 >out 3<>f &>>log\n",
+        );
+        check("a |& b", "a 2>&1 | b\n");
+        check(
+            "ls|grep  x&&echo ok ||  ! false",
+            "ls | grep x && echo ok || ! false\n",
+        );
+    }
+
+    #[test]
+    fn lists() {
+        check("a; b\nc", "a\nb\nc\n");
+        check("a & b", "a & b\n");
+        check("sleep 1 &", "sleep 1 &\n");
+        check("a;\n\n\nb;", "a\nb\n");
+        check("{ a; b; } | c", "{\n    a\n    b\n} | c\n");
+        check(
+            "! { a || b; } && (c; d)",
+            "! {\n    a || b\n} && (\n    c\n    d\n)\n",
+        );
+        check("(cd src && ls)", "(cd src && ls)\n");
+        check("( (a) )", "( (a) )\n");
+    }
+
+    #[test]
+    fn compound() {
+        check(
+            "if true; then echo a; elif false; then echo b; else echo c; fi",
+            "if true; then\n    echo a\nelif false; then\n    echo b\nelse\n    echo c\nfi\n",
+        );
+        check(
+            "while read x; do echo $x; sleep 1 & done",
+            "while read x; do\n    echo $x\n    sleep 1 &\ndone\n",
+        );
+        check(
+            "for i in a b; do until false; do break; done; done",
+            "for i in a b; do\n    until false; do\n        break\n    done\ndone\n",
+        );
+        check("for i; do :; done", "for i; do\n    :\ndone\n");
+        check(
+            "select x in a b\ndo break; done",
+            "select x in a b; do\n    break\ndone\n",
+        );
+        check(
+            "case $x in a|b) echo ab;; *) ;; esac",
+            "case $x in\n    a | b)\n        echo ab\n        ;;\n    *)\n        ;;\nesac\n",
+        );
+        check("f() { echo $1; }", "f() {\n    echo $1\n}\n");
+        check("((i += 1))", "((i += 1))\n");
+        check("a; b & c &", "a\nb & c &\n");
+        check(
+            "while a; do b & c; done",
+            "while a; do\n    b &\n    c\ndone\n",
+        );
+        check(
+            "[[  -n $a&&$b == \"]]\" ]]>out",
+            "[[ -n $a&&$b == \"]]\" ]] >out\n",
+        );
+        check("{ a; b; } > log 2>&1", "{\n    a\n    b\n} >log 2>&1\n");
+        check("(a) <input | b", "(a) <input | b\n");
+        check("f() { a; } >&2", "f() {\n    a\n} >&2\n");
+    }
+
+    #[test]
+    fn heredoc() {
+        check(
+            "if true; then cat <<EOF | wc -l\nhello\nEOF\nfi",
+            "if true; then\n    cat <<EOF | wc -l\nhello\nEOF\nfi\n",
+        );
+    }
+
+    #[test]
+    fn comments() {
+        check(
+            "# intro\nls  -a # all\n\n# check\nif true; then # yes\n  echo hi\n  # unused\nfi\n# end",
+            "# intro\nls -a # all\n# check\nif true; then # yes\n    echo hi\n    # unused\nfi\n# end\n",
+        );
+    }
+
+    #[test]
+    fn tabs() {
+        let cmd = parse("while true; do ls; done");
+        assert_eq!(
+            format(&cmd, &FormatOptions { indent: 0 }),
+            "while true; do\n\tls\ndone\n"
+        );
+    }
+}
@@ -1,13 +1,10 @@
-use std::cell::RefCell;
+use std::{cell::RefCell, rc::Rc};
 
-use shrs_core::Lang;
-use shrs_job::{initialize_job_control, JobManager, ProcessGroup};
+use shrs_core::{trap::exit_shell, ControlFlow, Lang};
+use shrs_job::{JobManager, ProcessGroup};
 use thiserror::Error;
 
-use crate::{
-    eval2::{self, run_job},
-    parser, Lexer, Parser,
-};
+use crate::{arith, ast, eval2, parser, Lexer, Parser};
 
 #[derive(Error, Debug)]
 pub enum PosixError {
@@ -17,20 +14,32 @@ pub enum PosixError {
     /// Error emitted by hook
     #[error("Hook Error:")]
     Hook(),
-    /// Issue parsing command
-    #[error("Parse failed: {0}")]
-    Parse(parser::Error),
+    /// Issue parsing command, displayed along with the offending line of the input
+    #[error("{}", error.render(input))]
+    Parse { input: String, error: parser::Error },
     /// Issue evaluating command
     #[error("Failed evaluating command: {0}")]
     Eval(anyhow::Error),
 }
 
+/// Context for hooks that run once a command line is parsed, before it is evaluated
+///
+/// Hooks can inspect the command with a [crate::visit::Visitor] or rewrite it with a
+/// [crate::visit::VisitorMut], the command that is left once every hook has run is the one that
+/// is evaluated.
+#[derive(Clone)]
+pub struct ParsedCommandCtx {
+    /// Command line as it was entered, aliases are only replaced in the parsed command
+    pub line: String,
+    /// Parsed command
+    pub command: Rc<RefCell<ast::Command>>,
+}
+
 /// Posix implementation of shell command language
 pub struct PosixLang {}
 
 impl PosixLang {
     pub fn new() -> Self {
-        initialize_job_control().unwrap();
         Self {}
     }
 }
@@ -46,21 +55,30 @@ impl Lang for PosixLang {
         // TODO rewrite the error handling here better
         let lexer = Lexer::new(&line);
         let parser = Parser::new();
-        let cmd = match parser.parse(lexer) {
+        let cmd = match parser.parse_with_aliases(lexer, &ctx.alias) {
             Ok(cmd) => cmd,
-            Err(e) => {
-                // TODO detailed parse errors
-                eprintln!("{e}");
-                return Err(e.into());
-            },
+            Err(error) => return Err(PosixError::Parse { input: line, error }.into()),
+        };
+
+        let command = Rc::new(RefCell::new(cmd));
+        let hook_ctx = ParsedCommandCtx {
+            line,
+            command: Rc::clone(&command),
         };
-        println!("{:?}", cmd);
+        sh.hooks.run::<ParsedCommandCtx>(sh, ctx, rt, hook_ctx)?;
+        let cmd = command.replace(ast::Command::None);
 
-        let mut job_manager = sh.job_manager.borrow_mut();
-        let (procs, pgid) = eval2::eval_command(&mut job_manager, &cmd, None, None)?;
+        eval2::run_foreground(sh, ctx, rt, &cmd)?;
 
-        run_job(&mut job_manager, procs, pgid, true)?;
+        // set by `errexit`
+        if let Some(ControlFlow::Exit(code)) = rt.control_flow {
+            exit_shell(sh, ctx, rt, code);
+        }
 
         Ok(())
     }
+
+    fn eval_arith(&self, rt: &mut shrs_core::Runtime, expr: &str) -> anyhow::Result<i64> {
+        Ok(arith::eval(rt, expr)?)
+    }
 }
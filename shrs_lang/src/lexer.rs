@@ -7,10 +7,12 @@ use std::str::CharIndices;
 use lazy_static::lazy_static;
 use thiserror::Error;
 
+use crate::{expand, Span};
+
 lazy_static! {
     pub static ref RESERVED_WORDS: Vec<&'static str> = vec![
-        "!", "{", "}", "case", "do", "done", "elif", "else", "esac", "fi", "for", "if", "in",
-        "then", "until", "while"
+        "!", "{", "}", "[[", "]]", "case", "do", "done", "elif", "else", "esac", "fi", "for", "if",
+        "in", "select", "then", "until", "while"
     ];
 }
 
@@ -48,6 +50,14 @@ pub enum Token<'input> {
     LESSGREAT,
     DLESSDASH,
     CLOBBER,
+    /// Here-string operator `<<<`
+    TLESS,
+    /// Redirect both stdout and stderr `&>`
+    ANDGREAT,
+    /// Append both stdout and stderr `&>>`
+    ANDDGREAT,
+    /// Pipe both stdout and stderr `|&`
+    PIPEAMP,
 
     IF,
     THEN,
@@ -62,6 +72,7 @@ pub enum Token<'input> {
     WHILE,
     UNTIL,
     FOR,
+    SELECT,
     IN,
 
     WORD(&'input str),
@@ -69,12 +80,38 @@ pub enum Token<'input> {
     FNAME(&'input str),
     NAME(&'input str),
     IO_NUMBER(&'input str),
+    /// Expression of an arithmetic command `((expression))`
+    ARITH(&'input str),
+    /// Expression of a conditional command `[[ expression ]]`
+    COND(&'input str),
+    /// Body of a here-document, emitted right after the delimiter
+    HEREDOC(&'input str),
 }
 
-#[derive(Debug, PartialEq, Eq, Error)]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum Error {
     #[error("unrecognized character {1} in range {0}:{2}")]
     UnrecognizedChar(usize, char, usize),
+    /// Raised by the parser for a word whose quotes or expansions can not be parsed
+    #[error("{1}")]
+    BadWord(usize, expand::Error, usize),
+}
+
+/// Bookkeeping for here-documents
+///
+/// The body of a here-document starts on the line after the redirection operator, so when the
+/// delimiter is lexed the body is located right away and the lexer skips over it once it reaches
+/// the end of the current line.
+#[derive(Clone, Default)]
+struct HereDocState {
+    /// Set after `<<` or `<<-`, holds whether leading tabs are stripped
+    expecting_delimiter: Option<bool>,
+    /// Body token waiting to be emitted after the delimiter
+    body: Option<(usize, usize)>,
+    /// Where lexing continues after the newline that precedes the bodies
+    resume: Option<usize>,
+    /// A here-document was not closed by its delimiter
+    unterminated: bool,
 }
 
 // TODO could technically make EOF a token so we don't need to do Result<Option> shinengans
@@ -83,6 +120,11 @@ pub struct Lexer<'input> {
     input: &'input str,
     chars: CharIndices<'input>,
     lookahead: Option<(usize, char, usize)>,
+    heredoc: HereDocState,
+    /// A quote or expansion was not closed before the end of the input
+    unterminated_quote: bool,
+    /// Comments that were skipped so far
+    comments: Vec<Span>,
 }
 
 impl<'input> Lexer<'input> {
@@ -94,6 +136,9 @@ impl<'input> Lexer<'input> {
             input,
             chars,
             lookahead,
+            heredoc: HereDocState::default(),
+            unterminated_quote: false,
+            comments: vec![],
         }
     }
 
@@ -101,6 +146,21 @@ impl<'input> Lexer<'input> {
         self.input
     }
 
+    /// Returns true if a quoted section or expansion lexed so far is missing its closing quote
+    pub fn unterminated_quote(&self) -> bool {
+        self.unterminated_quote
+    }
+
+    /// Returns true if a here-document that was lexed so far is missing its delimiter line
+    pub fn unterminated_heredoc(&self) -> bool {
+        self.heredoc.unterminated
+    }
+
+    /// Locations of the comments lexed so far, including the leading `#`
+    pub fn comments(&self) -> &[Span] {
+        &self.comments
+    }
+
     fn advance(&mut self) -> Option<(usize, char, usize)> {
         match self.lookahead {
             Some((start, ch, end)) => {
@@ -118,7 +178,41 @@ impl<'input> Lexer<'input> {
         start: usize,
         end: usize,
     ) -> Result<(usize, Token<'input>, usize), Error> {
-        let (word, end) = self.take_until(start, end, |ch| !is_word_continue(ch));
+        let mut end = match self.input[start..end].chars().next() {
+            Some(ch @ ('\'' | '"')) => self.quoted(end, ch),
+            Some('\\') => self.escaped(end),
+            Some('`') => self.backquote(end),
+            _ => end,
+        };
+        loop {
+            let (_, new_end) = self.take_until(start, end, |ch| !is_word_continue(ch));
+            end = new_end;
+            match self.lookahead {
+                // parameter expansion `${...}` and command substitution `$(...)` are part of the
+                // word, even if they contain whitespace
+                Some((_, '{', _)) if self.input[start..end].ends_with('$') => {
+                    end = self.balanced(end, '{', '}');
+                },
+                Some((_, '(', _)) if self.input[start..end].ends_with('$') => {
+                    end = self.balanced(end, '(', ')');
+                },
+                // process substitution `<(...)` and `>(...)` starts a word
+                Some((_, '(', _)) if matches!(&self.input[start..end], "<" | ">") => {
+                    end = self.balanced(end, '(', ')');
+                },
+                // quoted sections are kept in the word, quote removal is done during expansion
+                Some((_, ch @ ('\'' | '"' | '`' | '\\'), _)) => {
+                    let (_, _, e) = self.advance().unwrap();
+                    end = match ch {
+                        '`' => self.backquote(e),
+                        '\\' => self.escaped(e),
+                        ch => self.quoted(e, ch),
+                    };
+                },
+                _ => break,
+            }
+        }
+        let word = &self.input[start..end];
         let token = match word {
             "if" => Token::IF,
             "then" => Token::THEN,
@@ -132,53 +226,212 @@ impl<'input> Lexer<'input> {
             "while" => Token::WHILE,
             "until" => Token::UNTIL,
             "for" => Token::FOR,
+            "select" => Token::SELECT,
             "in" => Token::IN,
+            "[[" => return Ok(self.cond_command(start, end)),
+            // a number right before a redirection operator is the file descriptor it applies to
+            word if word.bytes().all(|b| b.is_ascii_digit())
+                && matches!(self.lookahead, Some((_, '<' | '>', _))) =>
+            {
+                Token::IO_NUMBER(word)
+            },
             word => Token::WORD(word),
         };
         Ok((start, token, end))
     }
 
-    // TODO escape characters
-    fn single_quote(
-        &mut self,
-        start: usize,
-        end: usize,
-    ) -> Result<(usize, Token<'input>, usize), Error> {
-        let (_, end) = self.take_until_inclusive(start, end, |ch| ch == '\'');
-        self.advance();
-        Ok((start, Token::WORD(&self.input[start..end]), end))
+    /// Consume the rest of a single or double quoted section, the opening quote is expected to
+    /// already be consumed
+    fn quoted(&mut self, mut end: usize, quote: char) -> usize {
+        while let Some((_, ch, e)) = self.lookahead {
+            match ch {
+                ch if ch == quote => {
+                    self.advance();
+                    return e;
+                },
+                // expansions inside double quotes may contain double quotes of their own
+                '\\' if quote == '"' => {
+                    self.advance();
+                    end = self.escaped(e);
+                    continue;
+                },
+                '`' if quote == '"' => {
+                    self.advance();
+                    end = self.backquote(e);
+                    continue;
+                },
+                '{' | '(' if quote == '"' && self.input[..end].ends_with('$') => {
+                    let close = if ch == '{' { '}' } else { ')' };
+                    end = self.balanced(end, ch, close);
+                    continue;
+                },
+                _ => {},
+            }
+            self.advance();
+            end = e;
+        }
+        self.unterminated_quote = true;
+        end
     }
 
-    fn double_quote(
-        &mut self,
-        start: usize,
-        end: usize,
-    ) -> Result<(usize, Token<'input>, usize), Error> {
-        let (_, end) = self.take_until_inclusive(start, end, |ch| ch == '"');
-        self.advance();
-        Ok((start, Token::WORD(&self.input[start..end]), end))
+    /// Consume the character following a backslash
+    fn escaped(&mut self, end: usize) -> usize {
+        match self.advance() {
+            Some((_, _, e)) => e,
+            None => end,
+        }
     }
 
-    // utils for reading until condition is met
-    fn take_until<F>(
-        &mut self,
-        start: usize,
-        mut end: usize,
-        mut terminate: F,
-    ) -> (&'input str, usize)
-    where
-        F: FnMut(char) -> bool,
-    {
-        while let Some((_, ch, _)) = self.lookahead {
-            if terminate(ch) {
-                return (&self.input[start..end], end);
-            } else if let Some((_, _, e)) = self.advance() {
-                end = e;
+    /// Consume an expansion delimited by `open` and `close`, the lookahead is expected to be the
+    /// opening delimiter
+    ///
+    /// Nested expansions and quoted sections are skipped over so that the matching closing
+    /// delimiter is found. If the input ends before the expansion is closed, the rest of the input
+    /// is consumed.
+    fn balanced(&mut self, mut end: usize, open: char, close: char) -> usize {
+        let mut depth = 0;
+        let mut quote: Option<char> = None;
+        let mut prev = '\0';
+        while let Some((_, ch, e)) = self.advance() {
+            end = e;
+            match (quote, ch) {
+                (_, _) if prev == '\\' && quote != Some('\'') => {
+                    // escaped character, reset so that `\\` does not escape the next one too
+                    prev = '\0';
+                    continue;
+                },
+                (Some(q), ch) if ch == q => quote = None,
+                (Some(_), _) => {},
+                (None, '\'' | '"' | '`') => quote = Some(ch),
+                (None, ch) if ch == open => depth += 1,
+                (None, ch) if ch == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                },
+                (None, _) => {},
             }
+            prev = ch;
         }
-        (&self.input[start..end], end)
+        if depth != 0 {
+            self.unterminated_quote = true;
+        }
+        end
+    }
+
+    /// Try to lex an arithmetic command `((expression))`, the first parenthesis is expected to
+    /// already be consumed
+    ///
+    /// If the parentheses are not closed by `))` nothing is consumed and [None] is returned, so
+    /// that the input can be lexed as nested subshells instead.
+    fn arith_command(&mut self, start: usize, end: usize) -> Option<(usize, Token<'input>, usize)> {
+        let mut probe = self.clone();
+        let inner_start = probe.lookahead?.2;
+        let inner_end = probe.balanced(end, '(', ')');
+        match probe.lookahead {
+            Some((_, ')', new_end)) if self.input[..inner_end].ends_with(')') => {
+                probe.advance();
+                *self = probe;
+                let expr = &self.input[inner_start..inner_end - 1];
+                Some((start, Token::ARITH(expr), new_end))
+            },
+            _ => None,
+        }
+    }
+
+    /// Lex a conditional command `[[ expression ]]`, the opening brackets are expected to already
+    /// be consumed
+    ///
+    /// The expression is made of words and operators like the rest of the input, so it runs up to
+    /// the first `]]` that is a word of its own. If there is none, only the opening brackets are
+    /// consumed and returned as a word.
+    fn cond_command(&mut self, start: usize, end: usize) -> (usize, Token<'input>, usize) {
+        let mut probe = self.clone();
+        while let Some(token) = probe.next_token() {
+            if let Ok((close, Token::WORD("]]"), new_end)) = token {
+                *self = probe;
+                return (start, Token::COND(&self.input[end..close]), new_end);
+            }
+        }
+        self.unterminated_quote = true;
+        (start, Token::WORD(&self.input[start..end]), end)
+    }
+
+    /// Locate the body of a here-document with the given delimiter, which starts on the line
+    /// after `end` or after the previous here-document on the same line
+    fn heredoc_body(&mut self, delimiter: &str, end: usize, strip_tabs: bool) {
+        // quotes only mark that the body is not expanded, they are not part of the delimiter
+        let delimiter = delimiter
+            .chars()
+            .filter(|ch| !matches!(ch, '\'' | '"' | '\\'))
+            .collect::<String>();
+
+        let start = match self.heredoc.resume {
+            Some(resume) => resume,
+            None => match self.input[end..].find('\n') {
+                Some(i) => end + i + 1,
+                None => self.input.len(),
+            },
+        };
+
+        let mut line_start = start;
+        let mut terminated = false;
+        while line_start < self.input.len() {
+            let line_end = self.input[line_start..]
+                .find('\n')
+                .map_or(self.input.len(), |i| line_start + i);
+            let mut line = &self.input[line_start..line_end];
+            if strip_tabs {
+                line = line.trim_start_matches('\t');
+            }
+            if line == delimiter {
+                terminated = true;
+                break;
+            }
+            line_start = (line_end + 1).min(self.input.len());
+        }
+
+        let resume = if terminated {
+            self.input[line_start..]
+                .find('\n')
+                .map_or(self.input.len(), |i| line_start + i + 1)
+        } else {
+            self.heredoc.unterminated = true;
+            self.input.len()
+        };
+        self.heredoc.body = Some((start, line_start));
+        self.heredoc.resume = Some(resume);
     }
-    fn take_until_inclusive<F>(
+
+    /// Jump past the here-document bodies that follow the newline just lexed
+    fn skip_heredoc_bodies(&mut self) {
+        if let Some(resume) = self.heredoc.resume.take() {
+            while matches!(self.lookahead, Some((start, _, _)) if start < resume) {
+                self.advance();
+            }
+        }
+    }
+
+    /// Consume the rest of a backquoted command substitution, the opening backquote is expected
+    /// to already be consumed
+    fn backquote(&mut self, mut end: usize) -> usize {
+        let mut escaped = false;
+        while let Some((_, ch, e)) = self.advance() {
+            end = e;
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '`' => return end,
+                _ => {},
+            }
+        }
+        self.unterminated_quote = true;
+        end
+    }
+
+    // utils for reading until condition is met
+    fn take_until<F>(
         &mut self,
         start: usize,
         mut end: usize,
@@ -189,7 +442,7 @@ impl<'input> Lexer<'input> {
     {
         while let Some((_, ch, _)) = self.lookahead {
             if terminate(ch) {
-                return (&self.input[start..=end], end + 1);
+                return (&self.input[start..end], end);
             } else if let Some((_, _, e)) = self.advance() {
                 end = e;
             }
@@ -201,8 +454,30 @@ impl<'input> Lexer<'input> {
 impl<'input> Iterator for Lexer<'input> {
     type Item = Spanned<Token<'input>, usize, Error>;
 
-    // TODO create proc macro to generate all this?
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some((start, end)) = self.heredoc.body.take() {
+            return Some(Ok((start, Token::HEREDOC(&self.input[start..end]), end)));
+        }
+
+        let token = self.next_token();
+        match &token {
+            Some(Ok((_, Token::DLESS, _))) => self.heredoc.expecting_delimiter = Some(false),
+            Some(Ok((_, Token::DLESSDASH, _))) => self.heredoc.expecting_delimiter = Some(true),
+            Some(Ok((_, Token::WORD(delimiter), end))) => {
+                if let Some(strip_tabs) = self.heredoc.expecting_delimiter.take() {
+                    self.heredoc_body(delimiter, *end, strip_tabs);
+                }
+            },
+            Some(Ok((_, Token::NEWLINE, _))) => self.skip_heredoc_bodies(),
+            _ => self.heredoc.expecting_delimiter = None,
+        }
+        token
+    }
+}
+
+impl<'input> Lexer<'input> {
+    // TODO create proc macro to generate all this?
+    fn next_token(&mut self) -> Option<Spanned<Token<'input>, usize, Error>> {
         while let Some((start, ch, end)) = self.advance() {
             // TODO see if this could be generated with macro
             let token = match ch {
@@ -219,6 +494,16 @@ impl<'input> Iterator for Lexer<'input> {
                         self.advance();
                         Some(Ok((start, Token::AND_IF, new_end)))
                     },
+                    Some((_, '>', new_end)) => {
+                        self.advance();
+                        match self.lookahead {
+                            Some((_, '>', new_end)) => {
+                                self.advance();
+                                Some(Ok((start, Token::ANDDGREAT, new_end)))
+                            },
+                            _ => Some(Ok((start, Token::ANDGREAT, new_end))),
+                        }
+                    },
                     _ => Some(Ok((start, Token::AMP, end))),
                 },
                 '|' => match self.lookahead {
@@ -226,16 +511,33 @@ impl<'input> Iterator for Lexer<'input> {
                         self.advance();
                         Some(Ok((start, Token::OR_IF, new_end)))
                     },
+                    Some((_, '&', new_end)) => {
+                        self.advance();
+                        Some(Ok((start, Token::PIPEAMP, new_end)))
+                    },
                     _ => Some(Ok((start, Token::PIPE, end))),
                 },
-                '`' => Some(Ok((start, Token::BACKTICK, end))),
-                '=' => Some(Ok((start, Token::EQUAL, end))),
-                '\\' => Some(Ok((start, Token::BACKSLASH, end))),
+                '`' | '\'' | '"' => Some(self.keyword(start, end)),
+                // line continuation
+                '\\' if matches!(self.lookahead, Some((_, '\n', _))) => {
+                    self.advance();
+                    continue;
+                },
+                '\\' => Some(self.keyword(start, end)),
                 '<' => match self.lookahead {
-                    // TODO current doesn't support <<-
                     Some((_, '<', new_end)) => {
                         self.advance();
-                        Some(Ok((start, Token::DLESS, new_end)))
+                        match self.lookahead {
+                            Some((_, '-', new_end)) => {
+                                self.advance();
+                                Some(Ok((start, Token::DLESSDASH, new_end)))
+                            },
+                            Some((_, '<', new_end)) => {
+                                self.advance();
+                                Some(Ok((start, Token::TLESS, new_end)))
+                            },
+                            _ => Some(Ok((start, Token::DLESS, new_end))),
+                        }
                     },
                     Some((_, '&', new_end)) => {
                         self.advance();
@@ -245,6 +547,7 @@ impl<'input> Iterator for Lexer<'input> {
                         self.advance();
                         Some(Ok((start, Token::LESSGREAT, new_end)))
                     },
+                    Some((_, '(', _)) => Some(self.keyword(start, end)),
                     _ => Some(Ok((start, Token::LESS, end))),
                 },
                 '>' => match self.lookahead {
@@ -260,16 +563,31 @@ impl<'input> Iterator for Lexer<'input> {
                         self.advance();
                         Some(Ok((start, Token::CLOBBER, new_end)))
                     },
+                    Some((_, '(', _)) => Some(self.keyword(start, end)),
                     _ => Some(Ok((start, Token::GREAT, end))),
                 },
 
-                '(' => Some(Ok((start, Token::LPAREN, end))),
+                '(' => match self.lookahead {
+                    Some((_, '(', _)) => match self.arith_command(start, end) {
+                        Some(token) => Some(Ok(token)),
+                        None => Some(Ok((start, Token::LPAREN, end))),
+                    },
+                    _ => Some(Ok((start, Token::LPAREN, end))),
+                },
                 ')' => Some(Ok((start, Token::RPAREN, end))),
                 '{' => Some(Ok((start, Token::LBRACE, end))),
                 '}' => Some(Ok((start, Token::RBRACE, end))),
+                // `!` is only special on its own, so that words like `!=` can be used as arguments
+                '!' if matches!(self.lookahead, Some((_, ch, _)) if is_word_continue(ch)) => {
+                    Some(self.keyword(start, end))
+                },
                 '!' => Some(Ok((start, Token::BANG, end))),
-                '\'' => Some(self.single_quote(start, end)),
-                '"' => Some(self.double_quote(start, end)),
+                // a comment runs until the end of the line, the newline itself is still a token
+                '#' => {
+                    let (_, end) = self.take_until(start, end, |ch| ch == '\n');
+                    self.comments.push(Span::new(start, end));
+                    continue;
+                },
                 ch if is_word_start(ch) => Some(self.keyword(start, end)),
                 ch if ch.is_whitespace() => continue,
                 ch => return Some(Err(Error::UnrecognizedChar(start, ch, end))),
@@ -291,8 +609,7 @@ fn is_word_start(ch: char) -> bool {
 /// predicate for when to keep reading word token
 fn is_word_continue(ch: char) -> bool {
     match ch {
-        ';' | ')' | '(' | '`' | '!' | '\\' | '\'' | '"' | '>' | '<' | '&' | '|' | '{' | '}'
-        | '*' => false,
+        ';' | ')' | '(' | '`' | '\\' | '\'' | '"' | '>' | '<' | '&' | '|' | '{' | '}' => false,
         _ => !ch.is_whitespace(),
     }
 }
@@ -300,11 +617,44 @@ fn is_word_continue(ch: char) -> bool {
 #[cfg(test)]
 mod tests {
     use super::{Lexer, Token};
+    use crate::Span;
 
     #[test]
     fn single_quote() {
         let mut lexer = Lexer::new("'hello world'");
-        assert_eq!(lexer.next(), Some(Ok((0, Token::WORD("hello world"), 12))));
+        assert_eq!(
+            lexer.next(),
+            Some(Ok((0, Token::WORD("'hello world'"), 13)))
+        );
+    }
+
+    #[test]
+    fn quoted_word() {
+        let mut lexer = Lexer::new("a'b c'\"d \\\" $(echo \")\")\"\\ e \\\nf 'if'");
+        assert_eq!(
+            lexer.next(),
+            Some(Ok((
+                0,
+                Token::WORD("a'b c'\"d \\\" $(echo \")\")\"\\ e"),
+                27
+            )))
+        );
+        assert_eq!(lexer.next(), Some(Ok((30, Token::WORD("f"), 31))));
+        assert_eq!(lexer.next(), Some(Ok((32, Token::WORD("'if'"), 36))));
+        assert_eq!(lexer.next(), None);
+        assert!(!lexer.unterminated_quote());
+
+        let mut lexer = Lexer::new("echo \"abc");
+        assert_eq!(lexer.nth(1), Some(Ok((5, Token::WORD("\"abc"), 9))));
+        assert!(lexer.unterminated_quote());
+    }
+
+    #[test]
+    fn glob() {
+        let mut lexer = Lexer::new("ls *.rs src/[ab]?");
+        assert_eq!(lexer.next(), Some(Ok((0, Token::WORD("ls"), 2))));
+        assert_eq!(lexer.next(), Some(Ok((3, Token::WORD("*.rs"), 7))));
+        assert_eq!(lexer.next(), Some(Ok((8, Token::WORD("src/[ab]?"), 17))));
     }
 
     #[test]
@@ -312,4 +662,176 @@ mod tests {
         let mut lexer = Lexer::new("case");
         assert_eq!(lexer.next(), Some(Ok((0, Token::CASE, 4))));
     }
+
+    #[test]
+    fn param_expansion() {
+        let mut lexer = Lexer::new("echo ${VAR:-a b}x ${#VAR}");
+        assert_eq!(lexer.next(), Some(Ok((0, Token::WORD("echo"), 4))));
+        assert_eq!(lexer.next(), Some(Ok((5, Token::WORD("${VAR:-a b}x"), 17))));
+        assert_eq!(lexer.next(), Some(Ok((18, Token::WORD("${#VAR}"), 25))));
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn command_substitution() {
+        let mut lexer = Lexer::new("echo $(ls -l | wc -l)x `echo a` \"$(echo ')')\"");
+        assert_eq!(lexer.next(), Some(Ok((0, Token::WORD("echo"), 4))));
+        assert_eq!(
+            lexer.next(),
+            Some(Ok((5, Token::WORD("$(ls -l | wc -l)x"), 22)))
+        );
+        assert_eq!(lexer.next(), Some(Ok((23, Token::WORD("`echo a`"), 31))));
+        assert_eq!(lexer.next().map(|t| t.is_ok()), Some(true));
+        let mut lexer = Lexer::new("$(echo $(echo a))");
+        assert_eq!(
+            lexer.next(),
+            Some(Ok((0, Token::WORD("$(echo $(echo a))"), 17)))
+        );
+    }
+
+    #[test]
+    fn cond() {
+        let mut lexer = Lexer::new("[[ $a == \"]]\" && ( -n $(echo ]]) ) ]]; x");
+        assert_eq!(
+            lexer.next(),
+            Some(Ok((
+                0,
+                Token::COND(" $a == \"]]\" && ( -n $(echo ]]) ) "),
+                37
+            )))
+        );
+        assert_eq!(lexer.next(), Some(Ok((37, Token::SEMI, 38))));
+        assert_eq!(lexer.next(), Some(Ok((39, Token::WORD("x"), 40))));
+        assert!(!lexer.unterminated_quote());
+
+        // `=` is an ordinary word for the `test` builtin
+        let mut lexer = Lexer::new("[ = == ]");
+        assert_eq!(lexer.next(), Some(Ok((0, Token::WORD("["), 1))));
+        assert_eq!(lexer.next(), Some(Ok((2, Token::WORD("="), 3))));
+        assert_eq!(lexer.next(), Some(Ok((4, Token::WORD("=="), 6))));
+
+        let mut lexer = Lexer::new("[[ -n x");
+        assert_eq!(lexer.next(), Some(Ok((0, Token::WORD("[["), 2))));
+        assert!(lexer.unterminated_quote());
+    }
+
+    #[test]
+    fn arith() {
+        let mut lexer = Lexer::new("((i += (1 + 2)))");
+        assert_eq!(
+            lexer.next(),
+            Some(Ok((0, Token::ARITH("i += (1 + 2)"), 16)))
+        );
+        assert_eq!(lexer.next(), None);
+        let mut lexer = Lexer::new("echo $((1 + 2))");
+        assert_eq!(lexer.next(), Some(Ok((0, Token::WORD("echo"), 4))));
+        assert_eq!(lexer.next(), Some(Ok((5, Token::WORD("$((1 + 2))"), 15))));
+        // nested subshells
+        let mut lexer = Lexer::new("((ls) )");
+        assert_eq!(lexer.next(), Some(Ok((0, Token::LPAREN, 1))));
+        assert_eq!(lexer.next(), Some(Ok((1, Token::LPAREN, 2))));
+    }
+
+    #[test]
+    fn redirects() {
+        let input = "cmd 2>&1 3<>f 12 >x &>>log |& wc";
+        let tokens = Lexer::new(input).map(|t| t.unwrap().1).collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::WORD("cmd"),
+                Token::IO_NUMBER("2"),
+                Token::GREATAND,
+                Token::WORD("1"),
+                Token::IO_NUMBER("3"),
+                Token::LESSGREAT,
+                Token::WORD("f"),
+                Token::WORD("12"),
+                Token::GREAT,
+                Token::WORD("x"),
+                Token::ANDDGREAT,
+                Token::WORD("log"),
+                Token::PIPEAMP,
+                Token::WORD("wc"),
+            ]
+        );
+    }
+
+    #[test]
+    fn process_substitution() {
+        let input = "diff <(sort a) >(tee 'x)') < (b)";
+        let tokens = Lexer::new(input).map(|t| t.unwrap().1).collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::WORD("diff"),
+                Token::WORD("<(sort a)"),
+                Token::WORD(">(tee 'x)')"),
+                Token::LESS,
+                Token::LPAREN,
+                Token::WORD("b"),
+                Token::RPAREN,
+            ]
+        );
+    }
+
+    #[test]
+    fn heredoc() {
+        let input = "cat <<EOF; cat <<-'END'\nhello $X\nEOF\n\tbye\n\tEND\necho ok";
+        let tokens = Lexer::new(input).map(|t| t.unwrap().1).collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::WORD("cat"),
+                Token::DLESS,
+                Token::WORD("EOF"),
+                Token::HEREDOC("hello $X\n"),
+                Token::SEMI,
+                Token::WORD("cat"),
+                Token::DLESSDASH,
+                Token::WORD("'END'"),
+                Token::HEREDOC("\tbye\n"),
+                Token::NEWLINE,
+                Token::WORD("echo"),
+                Token::WORD("ok"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_heredoc() {
+        let mut lexer = Lexer::new("cat <<EOF\nhello");
+        while lexer.next().is_some() {}
+        assert!(lexer.unterminated_heredoc());
+
+        let mut lexer = Lexer::new("cat <<< word");
+        assert_eq!(lexer.nth(1), Some(Ok((4, Token::TLESS, 7))));
+        assert!(!lexer.unterminated_heredoc());
+    }
+
+    #[test]
+    fn nested_param_expansion() {
+        let mut lexer = Lexer::new("${A:-${B:-'}'}}");
+        assert_eq!(
+            lexer.next(),
+            Some(Ok((0, Token::WORD("${A:-${B:-'}'}}"), 15)))
+        );
+    }
+
+    #[test]
+    fn comment() {
+        let mut lexer = Lexer::new("# intro\necho a#b $# # done\n");
+        let tokens = lexer.by_ref().map(|t| t.unwrap().1).collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::NEWLINE,
+                Token::WORD("echo"),
+                Token::WORD("a#b"),
+                Token::WORD("$#"),
+                Token::NEWLINE,
+            ]
+        );
+        assert_eq!(lexer.comments(), &[Span::new(0, 7), Span::new(20, 26)]);
+    }
 }
@@ -8,16 +8,40 @@ extern crate lalrpop_util;
 lalrpop_mod!(pub grammar);
 
 mod parser;
-pub use parser::{Error, Parser};
+pub use parser::{Error, Parser, PartialParse};
 
 mod lexer;
 pub use lexer::{Lexer, Token, RESERVED_WORDS};
 
+mod alias;
+pub use alias::expand_aliases;
+
 pub mod ast;
 
+pub mod visit;
+
+mod span;
+pub use span::Span;
+
 pub mod eval2;
 
+pub mod builtin;
+
+mod format;
+pub use format::{format, format_source, FormatOptions};
+
+pub mod lint;
+
+pub mod arith;
+
+pub mod cond;
+
+pub mod expand;
+
 // pub mod process;
 
 mod lang;
-pub use lang::{PosixError, PosixLang};
+pub use lang::{ParsedCommandCtx, PosixError, PosixLang};
+
+#[cfg(test)]
+mod test_utils;
@@ -0,0 +1,581 @@
+//! Static checks that catch common mistakes in scripts
+//!
+//! Every check is a [Rule] with an id such as `SH001`. Diagnostics can be suppressed with a
+//! comment, either at the end of the offending line or on the line before it:
+//!
+//! ```sh
+//! # shrs-lint disable=SH001,SH002
+//! cd $dir
+//! ```
+//! `disable=all` suppresses every rule.
+
+use std::{collections::HashSet, fmt};
+
+use crate::{ast, parser, Lexer, Parser, Span};
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Style issue or a pattern that is usually better written another way
+    Info,
+    /// Likely bug
+    Warning,
+    /// Definite bug
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Entry in the catalogue of checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    /// Identifier used to suppress the rule, such as `SH001`
+    pub id: &'static str,
+    pub name: &'static str,
+    pub severity: Severity,
+    /// Why the flagged code is a problem
+    pub description: &'static str,
+}
+
+pub const UNQUOTED_EXPANSION: Rule = Rule {
+    id: "SH001",
+    name: "unquoted-expansion",
+    severity: Severity::Warning,
+    description: "unquoted expansions are split into fields and used as glob patterns, \
+                  wrap them in double quotes",
+};
+
+pub const UNCHECKED_CD: Rule = Rule {
+    id: "SH002",
+    name: "unchecked-cd",
+    severity: Severity::Warning,
+    description: "if `cd` fails the rest of the script runs in the wrong directory, \
+                  use `cd ... || exit`",
+};
+
+pub const UNUSED_ASSIGNMENT: Rule = Rule {
+    id: "SH003",
+    name: "unused-assignment",
+    severity: Severity::Warning,
+    description: "the variable is never used, check for a typo or export it",
+};
+
+pub const INDIRECT_STATUS_CHECK: Rule = Rule {
+    id: "SH004",
+    name: "indirect-status-check",
+    severity: Severity::Info,
+    description: "`$?` changes with every command, check the command directly with \
+                  `if command; then`",
+};
+
+/// All rules that are checked by [lint]
+pub const RULES: &[Rule] = &[
+    UNQUOTED_EXPANSION,
+    UNCHECKED_CD,
+    UNUSED_ASSIGNMENT,
+    INDIRECT_STATUS_CHECK,
+];
+
+/// Look up a rule by its id
+pub fn rule(id: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|rule| rule.id == id)
+}
+
+/// Problem found by a rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(rule: Rule, span: Span, message: impl Into<String>) -> Self {
+        Diagnostic {
+            rule,
+            span,
+            message: message.into(),
+        }
+    }
+
+    /// Show the diagnostic along with the line it was found on
+    ///
+    /// ```text
+    /// warning[SH002]: `cd` is not checked for errors
+    ///  --> 1:1
+    ///   |
+    /// 1 | cd build
+    ///   | ^^^^^^^^
+    ///   = note: if `cd` fails the rest of the script runs in the wrong directory, use `cd ... || exit`
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let header = format!("{}[{}]: {}", self.rule.severity, self.rule.id, self.message);
+        let note = format!("note: {}", self.rule.description);
+        self.span.render(source, &header, Some(&note))
+    }
+}
+
+/// Parse and check a script, diagnostics that are suppressed by comments are left out
+///
+/// The diagnostics are sorted by where they occur in the source.
+pub fn lint(source: &str) -> Result<Vec<Diagnostic>, parser::Error> {
+    let mut lexer = Lexer::new(source);
+    let cmd = Parser::new().parse(lexer.clone())?;
+    lexer.by_ref().for_each(drop);
+    let suppressions = suppressions(source, lexer.comments());
+
+    let mut diagnostics = check(&cmd)
+        .into_iter()
+        .filter(|diagnostic| {
+            let (line, _) = diagnostic.span.line_col(source);
+            !suppressions.iter().any(|(l, ids)| {
+                *l == line && ids.iter().any(|id| id == "all" || id == diagnostic.rule.id)
+            })
+        })
+        .collect::<Vec<_>>();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    Ok(diagnostics)
+}
+
+/// Run every rule on a command, without looking at suppressions
+pub fn check(cmd: &ast::Command) -> Vec<Diagnostic> {
+    let mut checker = Checker::default();
+    checker.command(cmd, false);
+
+    for (name, span) in std::mem::take(&mut checker.assigned) {
+        if !checker.used.contains(&name) && !SPECIAL_VARIABLES.contains(&name.as_str()) {
+            checker.diagnostics.push(Diagnostic::new(
+                UNUSED_ASSIGNMENT,
+                span,
+                format!("`{name}` is assigned but never used"),
+            ));
+        }
+    }
+    checker.diagnostics
+}
+
+/// Variables that change the behaviour of the shell itself, so assigning them is a use
+const SPECIAL_VARIABLES: &[&str] = &[
+    "CDPATH", "ENV", "HOME", "IFS", "LANG", "LC_ALL", "OPTIND", "PATH", "PS1", "PS2", "PS4",
+];
+
+/// Lines that have rules disabled by a `# shrs-lint disable=...` comment
+fn suppressions(source: &str, comments: &[Span]) -> Vec<(usize, Vec<String>)> {
+    let mut suppressions = vec![];
+    for comment in comments {
+        let text = source[comment.start..comment.end].trim_start_matches('#');
+        let Some(ids) = text
+            .trim()
+            .strip_prefix("shrs-lint")
+            .and_then(|rest| rest.trim_start().strip_prefix("disable="))
+        else {
+            continue;
+        };
+        let ids = ids.split(',').map(|id| id.trim().to_string()).collect();
+
+        // a comment on a line of its own applies to the next line
+        let (line, _) = comment.line_col(source);
+        let line_start = source[..comment.start].rfind('\n').map_or(0, |i| i + 1);
+        let own_line = source[line_start..comment.start].trim().is_empty();
+        suppressions.push((if own_line { line + 1 } else { line }, ids));
+    }
+    suppressions
+}
+
+/// Expansion found in a word
+struct Expansion {
+    /// Name of the parameter, empty for command substitutions
+    name: String,
+    /// Location of the expansion in the source
+    span: Span,
+    quoted: bool,
+}
+
+/// Find the parameter expansions and command substitutions in a word
+///
+/// The names of variables used inside nested expansions, command substitutions and arithmetic
+/// expansions are included as well, so that they count as used.
+fn expansions(word: &ast::Word) -> Vec<Expansion> {
+    let mut out = vec![];
+    scan(&word.text, word.span.start, false, &mut out);
+    out
+}
+
+fn scan(text: &str, offset: usize, in_double: bool, out: &mut Vec<Expansion>) {
+    let chars = text.char_indices().collect::<Vec<_>>();
+    let mut quoted = in_double;
+    let mut i = 0;
+    while i < chars.len() {
+        let (pos, ch) = chars[i];
+        match ch {
+            '\\' => i += 1,
+            '\'' if !quoted => {
+                while i + 1 < chars.len() && chars[i + 1].1 != '\'' {
+                    i += 1;
+                }
+                i += 1;
+            },
+            '"' => quoted = !quoted,
+            '`' => {
+                let end = (i + 1..chars.len())
+                    .find(|&j| chars[j].1 == '`')
+                    .unwrap_or(chars.len());
+                let inner_end = chars.get(end).map_or(text.len(), |c| c.0);
+                let span = Span::new(offset + pos, offset + inner_end + 1);
+                out.push(Expansion {
+                    name: String::new(),
+                    span,
+                    quoted,
+                });
+                scan(&text[pos + 1..inner_end], offset + pos + 1, false, out);
+                i = end;
+            },
+            '$' => {
+                let rest = &text[pos + 1..];
+                if rest.starts_with("((") {
+                    let len = balanced(rest, '(', ')');
+                    arith_names(&rest[2..len.saturating_sub(2).max(2)], out);
+                    i = skip_to(&chars, pos + 1 + len) - 1;
+                } else if rest.starts_with('(') || rest.starts_with('{') {
+                    let (open, close) = match rest.starts_with('(') {
+                        true => ('(', ')'),
+                        false => ('{', '}'),
+                    };
+                    let len = balanced(rest, open, close);
+                    let inner = &rest[1..len.saturating_sub(1).max(1)];
+                    let name = match open {
+                        '(' => String::new(),
+                        _ => inner
+                            .trim_start_matches(['#', '!'])
+                            .chars()
+                            .take_while(|ch| ch.is_alphanumeric() || *ch == '_')
+                            .collect(),
+                    };
+                    out.push(Expansion {
+                        name,
+                        span: Span::new(offset + pos, offset + pos + 1 + len),
+                        quoted,
+                    });
+                    // words inside a parameter expansion are not split again
+                    scan(inner, offset + pos + 2, open == '{', out);
+                    i = skip_to(&chars, pos + 1 + len) - 1;
+                } else {
+                    let name = match rest.chars().next() {
+                        Some(ch) if ch.is_ascii_digit() || "@*#?$!-".contains(ch) => ch.to_string(),
+                        _ => rest
+                            .chars()
+                            .take_while(|ch| ch.is_alphanumeric() || *ch == '_')
+                            .collect::<String>(),
+                    };
+                    if !name.is_empty() {
+                        out.push(Expansion {
+                            span: Span::new(offset + pos, offset + pos + 1 + name.len()),
+                            name,
+                            quoted,
+                        });
+                    }
+                }
+            },
+            _ => {},
+        }
+        i += 1;
+    }
+}
+
+/// Length of the bracketed section at the start of `text`, including the brackets
+fn balanced(text: &str, open: char, close: char) -> usize {
+    let mut depth = 0;
+    for (i, ch) in text.char_indices() {
+        if ch == open {
+            depth += 1;
+        } else if ch == close {
+            depth -= 1;
+            if depth == 0 {
+                return i + 1;
+            }
+        }
+    }
+    text.len()
+}
+
+/// Index of the first char at or after byte position `pos`
+fn skip_to(chars: &[(usize, char)], pos: usize) -> usize {
+    chars
+        .iter()
+        .position(|(p, _)| *p >= pos)
+        .unwrap_or(chars.len())
+}
+
+/// Variables named in an arithmetic expression, which may leave out the `$`
+fn arith_names(expr: &str, out: &mut Vec<Expansion>) {
+    let mut name = String::new();
+    for ch in expr.chars().chain(std::iter::once(' ')) {
+        if ch.is_alphanumeric() || ch == '_' {
+            name.push(ch);
+        } else if !name.is_empty() {
+            if !name.starts_with(|ch: char| ch.is_ascii_digit()) {
+                out.push(Expansion {
+                    name: std::mem::take(&mut name),
+                    span: Span::default(),
+                    quoted: true,
+                });
+            }
+            name.clear();
+        }
+    }
+}
+
+/// Special parameters that hold a single field, so they do not need quotes
+fn is_safe_unquoted(name: &str) -> bool {
+    matches!(name, "?" | "#" | "$" | "!" | "-")
+}
+
+#[derive(Default)]
+struct Checker {
+    diagnostics: Vec<Diagnostic>,
+    /// Variables set by assignments that are not part of a command
+    assigned: Vec<(String, Span)>,
+    used: HashSet<String>,
+}
+
+impl Checker {
+    /// Record the variables used by a word
+    fn uses(&mut self, word: &ast::Word) {
+        for expansion in expansions(word) {
+            self.used.insert(expansion.name);
+        }
+    }
+
+    /// Check a command, `checked` is set if its exit status is tested, for example in the
+    /// condition of an `if` or on the left of `||`
+    fn command(&mut self, cmd: &ast::Command, checked: bool) {
+        match cmd {
+            ast::Command::Simple {
+                assigns,
+                redirects,
+                args,
+            } => self.simple(assigns, redirects, args, checked),
+            ast::Command::And(a, b) | ast::Command::Or(a, b) => {
+                self.command(a, true);
+                self.command(b, checked);
+            },
+            ast::Command::Pipeline(a, b) => {
+                self.command(a, checked);
+                self.command(b, checked);
+            },
+            ast::Command::AsyncList(a, b) | ast::Command::SeqList(a, b) => match b {
+                Some(b) => {
+                    self.command(a, false);
+                    self.command(b, checked);
+                },
+                None => self.command(a, checked),
+            },
+            ast::Command::Not(cmd) => self.command(cmd, true),
+            ast::Command::Subshell(cmd) => self.command(cmd, checked),
+            ast::Command::If { conds, else_part } => {
+                for cond in conds {
+                    self.command(&cond.cond, true);
+                    self.command(&cond.body, false);
+                }
+                if let Some(else_part) = else_part {
+                    self.command(else_part, false);
+                }
+            },
+            ast::Command::While { cond, body } | ast::Command::Until { cond, body } => {
+                self.command(cond, true);
+                self.command(body, false);
+            },
+            ast::Command::For {
+                name,
+                wordlist,
+                body,
+            }
+            | ast::Command::Select {
+                name,
+                wordlist,
+                body,
+            } => {
+                // splitting the word list is usually intended
+                wordlist.iter().flatten().for_each(|word| self.uses(word));
+                self.used.insert(name.to_string());
+                self.command(body, false);
+            },
+            ast::Command::Case { word, arms } => {
+                self.uses(word);
+                for arm in arms {
+                    arm.pattern.iter().for_each(|word| self.uses(word));
+                    self.command(&arm.body, false);
+                }
+            },
+            ast::Command::Arith(expr) => {
+                let mut out = vec![];
+                arith_names(expr, &mut out);
+                self.used.extend(out.into_iter().map(|e| e.name));
+            },
+            ast::Command::Cond(expr) => {
+                let mut out = vec![];
+                scan(expr, 0, false, &mut out);
+                self.used.extend(out.into_iter().map(|e| e.name));
+            },
+            ast::Command::Fn { body, .. } => self.command(body, false),
+            ast::Command::Redirected { cmd, redirects } => {
+                self.command(cmd, checked);
+                self.redirects(redirects);
+            },
+            ast::Command::None => {},
+        }
+    }
+
+    fn redirects(&mut self, redirects: &[ast::Redirect]) {
+        for redirect in redirects {
+            self.uses(&redirect.file);
+            if let ast::RedirectMode::HereDoc { body, .. } = &redirect.mode {
+                let mut out = vec![];
+                scan(body, 0, true, &mut out);
+                self.used.extend(out.into_iter().map(|e| e.name));
+            }
+        }
+    }
+
+    fn simple(
+        &mut self,
+        assigns: &[ast::Assign],
+        redirects: &[ast::Redirect],
+        args: &[ast::Word],
+        checked: bool,
+    ) {
+        for assign in assigns {
+            self.uses(&assign.val);
+            // assignments before a command are used by the command
+            if args.is_empty() {
+                self.assigned.push((assign.var.clone(), assign.span));
+            }
+        }
+        self.redirects(redirects);
+
+        let Some(name) = args.first() else {
+            return;
+        };
+        match name.text.as_str() {
+            "cd" if !checked => {
+                let span = name.span.to(args[args.len() - 1].span);
+                self.diagnostics.push(Diagnostic::new(
+                    UNCHECKED_CD,
+                    span,
+                    "`cd` is not checked for errors",
+                ));
+            },
+            // variables named by these builtins are used by them
+            "export" | "readonly" | "local" | "unset" | "read" => {
+                for arg in &args[1..] {
+                    let name = arg.split('=').next().unwrap_or_default();
+                    self.used.insert(name.to_string());
+                }
+            },
+            _ => {},
+        }
+
+        let is_test = matches!(name.text.as_str(), "[" | "[[" | "test");
+        for arg in args {
+            for expansion in expansions(arg) {
+                if is_test && expansion.name == "?" {
+                    self.diagnostics.push(Diagnostic::new(
+                        INDIRECT_STATUS_CHECK,
+                        expansion.span,
+                        "the exit status is tested through `$?`",
+                    ));
+                }
+                // words inside `[[ ]]` are not split
+                if !expansion.quoted && !is_safe_unquoted(&expansion.name) && name != "[[" {
+                    let what = match expansion.name.as_str() {
+                        "" => String::from("command substitution"),
+                        name => format!("`${name}`"),
+                    };
+                    self.diagnostics.push(Diagnostic::new(
+                        UNQUOTED_EXPANSION,
+                        expansion.span,
+                        format!("{what} is not quoted"),
+                    ));
+                }
+                self.used.insert(expansion.name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint, INDIRECT_STATUS_CHECK, UNCHECKED_CD, UNQUOTED_EXPANSION, UNUSED_ASSIGNMENT};
+    use crate::Span;
+
+    /// Ids and locations of the diagnostics for a script
+    fn rules(source: &str) -> Vec<(&'static str, Span)> {
+        lint(source)
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.rule.id, d.span))
+            .collect()
+    }
+
+    #[test]
+    fn unquoted_expansion() {
+        assert_eq!(
+            rules("echo $a \"$b\" ${c}x $(ls) '$d' $? $((e + 1))\na=1 b=2 c=3 e=4"),
+            vec![
+                (UNQUOTED_EXPANSION.id, Span::new(5, 7)),
+                (UNQUOTED_EXPANSION.id, Span::new(13, 17)),
+                (UNQUOTED_EXPANSION.id, Span::new(19, 24)),
+            ]
+        );
+        assert!(rules("[[ -n $x ]]; x=1").is_empty());
+    }
+
+    #[test]
+    fn unchecked_cd() {
+        assert_eq!(
+            rules("cd build\ncd src || exit\nif cd x; then :; fi"),
+            vec![(UNCHECKED_CD.id, Span::new(0, 8))]
+        );
+    }
+
+    #[test]
+    fn unused_assignment() {
+        assert_eq!(
+            rules("a=1\nb=2\nPATH=/bin\necho \"${b:-x}\"\nc=1 env"),
+            vec![(UNUSED_ASSIGNMENT.id, Span::new(0, 3))]
+        );
+        assert!(rules("i=0; ((i += 1)); n=1; export n").is_empty());
+    }
+
+    #[test]
+    fn status_check() {
+        assert_eq!(
+            rules("make\nif [ $? -eq 0 ]; then :; fi"),
+            vec![(INDIRECT_STATUS_CHECK.id, Span::new(10, 12))]
+        );
+    }
+
+    #[test]
+    fn suppressions() {
+        let source = "# shrs-lint disable=SH002\ncd a\ncd b # shrs-lint disable=all\ncd c";
+        assert_eq!(rules(source), vec![(UNCHECKED_CD.id, Span::new(60, 64))]);
+    }
+
+    #[test]
+    fn render() {
+        let source = "cd build";
+        let diagnostic = lint(source).unwrap().remove(0);
+        assert!(diagnostic
+            .render(source)
+            .starts_with("warning[SH002]: `cd` is not checked for errors\n --> 1:1\n"));
+    }
+}
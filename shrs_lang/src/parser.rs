@@ -1,16 +1,188 @@
 //! Generated parser
 
+use lalrpop_util::ParseError;
+use shrs_core::Alias;
 use thiserror::Error;
 
-use crate::{ast, grammar, lexer::Lexer};
+use crate::{
+    ast, expand, expand_aliases, grammar, lexer,
+    lexer::{Lexer, Spanned, Token},
+    Span,
+};
 
-// TODO better errors for unsuccessful parses
-#[derive(Error, Debug)]
-pub enum Error {
-    #[error("unsuccessful parse")]
-    UnsuccessfulParse,
+/// What went wrong while parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Token that is not allowed at this point, holds the text of the token
+    UnexpectedToken(String),
+    /// Input ended while a command was still incomplete
+    UnexpectedEof,
+    /// Character that does not start any token
+    UnrecognizedChar(char),
+    /// Word with a quote or expansion that is not terminated, or with a syntax error in the
+    /// command of a substitution
+    BadWord(Box<expand::Error>),
 }
 
+/// Syntax error along with where it happened
+///
+/// Use [Error::render] to show the error along with the offending line of the source.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{}", self.message())]
+pub struct Error {
+    pub kind: ErrorKind,
+    /// Location of the offending token, empty if the input ended unexpectedly
+    pub span: Span,
+    /// Descriptions of the tokens that would have been accepted instead
+    pub expected: Vec<String>,
+    /// Suggestion on how to fix the error
+    pub hint: Option<String>,
+}
+
+impl Error {
+    fn new(kind: ErrorKind, span: Span, expected: Vec<String>) -> Self {
+        Error {
+            kind,
+            span,
+            expected: describe_expected(expected),
+            hint: None,
+        }
+    }
+
+    /// Short description of the error on a single line
+    pub fn message(&self) -> String {
+        let mut message = match &self.kind {
+            ErrorKind::UnexpectedToken(token) if token == "\n" => {
+                String::from("unexpected newline")
+            },
+            ErrorKind::UnexpectedToken(token) => format!("unexpected `{token}`"),
+            ErrorKind::UnexpectedEof => String::from("unexpected end of input"),
+            ErrorKind::UnrecognizedChar(ch) => format!("unrecognized character `{ch}`"),
+            ErrorKind::BadWord(e) => e.to_string(),
+        };
+        // long lists of alternatives are not helpful
+        if !self.expected.is_empty() && self.expected.len() <= 5 {
+            let expected = self.expected.join(", ");
+            message.push_str(&format!(", expected {expected}"));
+        }
+        message
+    }
+
+    /// Show the error along with the line it occurred on, with the offending token underlined
+    ///
+    /// ```text
+    /// error: unexpected end of input, expected `fi`
+    ///  --> 1:22
+    ///   |
+    /// 1 | if true; then echo hi
+    ///   |                      ^
+    ///   = hint: missing `fi` to close the `if`
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let header = format!("error: {}", self.message());
+        let hint = self.hint.as_ref().map(|hint| format!("hint: {hint}"));
+        self.span.render(source, &header, hint.as_deref())
+    }
+}
+
+/// Turn the names of terminals used by the grammar into something readable
+fn describe_expected(expected: Vec<String>) -> Vec<String> {
+    let mut described: Vec<String> = vec![];
+    for terminal in expected {
+        let terminal = terminal.trim_matches('"');
+        let description = match terminal {
+            "WORD" | "NAME" | "FNAME" | "ASSIGNMENT_WORD" => String::from("a word"),
+            "IO_NUMBER" => String::from("a file descriptor"),
+            "HEREDOC" => String::from("a here-document"),
+            "ARITH" => String::from("an arithmetic command"),
+            "COND" => String::from("a conditional command"),
+            "\\n" => String::from("a newline"),
+            terminal => format!("`{}`", terminal.replace("\\\\", "\\").replace("\\\"", "\"")),
+        };
+        if !described.contains(&description) {
+            described.push(description);
+        }
+    }
+    described
+}
+
+/// Compound command that has been opened but not closed yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Open {
+    If,
+    Loop,
+    Case,
+    Brace,
+    Paren,
+}
+
+/// Compound commands that are still open at `location`, innermost last
+fn open_commands(tokens: &[(usize, Token, usize)], location: usize) -> Vec<Open> {
+    let mut open = vec![];
+    let close = |open: &mut Vec<Open>, command| {
+        if open.last() == Some(&command) {
+            open.pop();
+        }
+    };
+    // here-document bodies come out of order, so every token before the location is looked at
+    for (_, token, _) in tokens.iter().filter(|(start, ..)| *start < location) {
+        match token {
+            Token::IF => open.push(Open::If),
+            Token::WHILE | Token::UNTIL | Token::FOR | Token::SELECT => open.push(Open::Loop),
+            Token::CASE => open.push(Open::Case),
+            Token::LBRACE => open.push(Open::Brace),
+            Token::LPAREN => open.push(Open::Paren),
+            Token::FI => close(&mut open, Open::If),
+            Token::DONE => close(&mut open, Open::Loop),
+            Token::ESAC => close(&mut open, Open::Case),
+            Token::RBRACE => close(&mut open, Open::Brace),
+            // a `)` without `(` ends the pattern of a case arm
+            Token::RPAREN => close(&mut open, Open::Paren),
+            _ => {},
+        }
+    }
+    open
+}
+
+/// Suggest a fix for the innermost compound command that is still open
+///
+/// A missing end is only suggested when the input ended or another command was closed instead.
+fn hint(kind: &ErrorKind, expected: &[String], open: Option<Open>) -> Option<String> {
+    let has = |token: &str| expected.iter().any(|e| e == &format!("`{token}`"));
+    let closing = match kind {
+        ErrorKind::UnexpectedEof => true,
+        ErrorKind::UnexpectedToken(token) => ["fi", "done", "esac", "}", ")"].contains(&&**token),
+        ErrorKind::UnrecognizedChar(_) | ErrorKind::BadWord(_) => false,
+    };
+    let hint = match open? {
+        Open::If if closing && has("fi") => "missing `fi` to close the `if`",
+        Open::If if has("then") => "conditions of `if` and `elif` are followed by `then`",
+        Open::Loop if closing && has("done") => "missing `done` to close the loop",
+        Open::Loop if has("do") => "loop conditions and word lists are followed by `do`",
+        Open::Case if closing && has("esac") => "missing `esac` to close the `case`",
+        Open::Case if has("in") => "the word of a `case` is followed by `in`",
+        Open::Brace if closing && has("}") => "missing `}` to close the brace group",
+        Open::Paren if closing && has(")") => "missing `)`",
+        _ => return None,
+    };
+    Some(hint.to_string())
+}
+
+/// Result of [Parser::parse_partial]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialParse {
+    /// Best effort AST, unexpected tokens are left out and unfinished commands are closed
+    pub cmd: ast::Command,
+    /// Errors that were recovered from, the input ending early is not counted as an error
+    pub errors: Vec<Error>,
+    /// The input ended in the middle of a command, for example an `if` without `fi` or an
+    /// unterminated quote
+    pub incomplete: bool,
+}
+
+/// Upper bound on the number of tokens inserted at the end of the input while recovering
+const MAX_INSERTED: usize = 32;
+
 pub struct Parser {}
 
 impl Parser {
@@ -18,11 +190,247 @@ impl Parser {
         Parser {}
     }
 
-    pub fn parse(&self, lexer: Lexer) -> Result<ast::Command, Error> {
-        grammar::ProgramParser::new()
-            .parse(lexer.input(), lexer)
-            .map_err(|_e| Error::UnsuccessfulParse)
+    pub fn parse(&self, mut lexer: Lexer) -> Result<ast::Command, Error> {
+        let tokens = lexer.by_ref().collect();
+        self.parse_tokens(&lexer, tokens)
+    }
+
+    /// Parse with the aliases replaced, see [crate::expand_aliases]
+    ///
+    /// Locations in the AST and in errors refer to the input before aliases were replaced.
+    pub fn parse_with_aliases<'a>(
+        &self,
+        mut lexer: Lexer<'a>,
+        aliases: &'a Alias,
+    ) -> Result<ast::Command, Error> {
+        let tokens = expand_aliases(&mut lexer, aliases);
+        self.parse_tokens(&lexer, tokens)
+    }
+
+    /// Parse the tokens that were taken from `lexer`
+    fn parse_tokens<'a>(
+        &self,
+        lexer: &Lexer<'a>,
+        tokens: Vec<Spanned<Token<'a>, usize, lexer::Error>>,
+    ) -> Result<ast::Command, Error> {
+        let input = lexer.input();
+        let res = grammar::ProgramParser::new().parse(input, tokens.iter().cloned());
+        res.map_err(|e| {
+            let tokens: Vec<_> = tokens.into_iter().flatten().collect();
+            let mut error = convert_error(input, &tokens, e);
+            if lexer.unterminated_quote() {
+                error.hint = Some(String::from(
+                    "a quote or expansion is missing its closing character",
+                ));
+            } else if lexer.unterminated_heredoc() {
+                error.hint = Some(String::from(
+                    "a here-document is missing its delimiter line",
+                ));
+            }
+            error
+        })
+    }
+
+    /// Parse without giving up on the first error, for tools such as the highlighter that work
+    /// with input that is still being typed
+    ///
+    /// Unexpected tokens are skipped and reported as errors. If the input ends early, the tokens
+    /// needed to close the unfinished commands are inserted, with a placeholder empty word where
+    /// a command or argument is missing.
+    pub fn parse_partial(&self, mut lexer: Lexer) -> PartialParse {
+        let input = lexer.input();
+        let mut errors = vec![];
+        let mut tokens = vec![];
+        for token in lexer.by_ref() {
+            match token {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(convert_error(input, &[], ParseError::User { error })),
+            }
+        }
+        let mut incomplete = lexer.unterminated_quote() || lexer.unterminated_heredoc();
+
+        let end = input.len();
+        let mut real = tokens.len();
+        let mut inserted = 0;
+        loop {
+            let stream = tokens.iter().cloned().map(Ok::<_, lexer::Error>);
+            let e = match grammar::ProgramParser::new().parse(input, stream) {
+                Ok(cmd) => {
+                    return PartialParse {
+                        cmd,
+                        errors,
+                        incomplete,
+                    }
+                },
+                Err(e) => e,
+            };
+
+            // a word that can not be parsed is left empty, the input ending inside a quote or
+            // expansion is not an error here either
+            if let ParseError::User {
+                error: lexer::Error::BadWord(start, word_error, _),
+            } = &e
+            {
+                let unterminated = matches!(word_error, expand::Error::Unterminated(_));
+                let start = *start;
+                let Some(i) = tokens.iter().position(|(s, ..)| *s == start) else {
+                    break;
+                };
+                tokens[i].1 = match tokens[i].1 {
+                    Token::HEREDOC(_) => Token::HEREDOC(""),
+                    _ => Token::WORD(""),
+                };
+                if !(incomplete && unterminated) {
+                    errors.push(convert_error(input, &tokens, e));
+                }
+                continue;
+            }
+
+            let (position, expected) = match &e {
+                ParseError::UnrecognizedEOF { expected, .. } => (None, expected.clone()),
+                ParseError::UnrecognizedToken { token, expected } => {
+                    (tokens.iter().position(|t| t == token), expected.clone())
+                },
+                ParseError::ExtraToken { token } => {
+                    (tokens.iter().position(|t| t == token), vec![])
+                },
+                _ => break,
+            };
+
+            match position {
+                // leave out the unexpected token and try again
+                Some(i) if i < real => {
+                    tokens.remove(i);
+                    real -= 1;
+                    errors.push(convert_error(input, &tokens, e));
+                },
+                // the input ended early, or a token inserted to finish it was wrong. The expected
+                // set is only exact in the latter case, so a wrong guess gets corrected here
+                _ if inserted < MAX_INSERTED => {
+                    incomplete = true;
+                    if let Some(i) = position {
+                        tokens.truncate(i);
+                    }
+                    let after_placeholder = real < tokens.len()
+                        && matches!(tokens.last(), Some((_, Token::WORD(""), _)));
+                    match recovery_token(&expected, after_placeholder) {
+                        Some(token) => {
+                            tokens.push((end, token, end));
+                            inserted += 1;
+                        },
+                        None if real == 0 => break,
+                        None => {
+                            real -= 1;
+                            tokens.truncate(real);
+                        },
+                    }
+                },
+                _ => break,
+            }
+        }
+
+        PartialParse {
+            cmd: ast::Command::None,
+            errors,
+            incomplete,
+        }
+    }
+}
+
+/// Choose a token to insert at the end of incomplete input
+///
+/// Closing keywords are preferred so that compound commands are finished as soon as possible.
+fn recovery_token(expected: &[String], after_placeholder: bool) -> Option<Token<'static>> {
+    let has = |terminal: &str| expected.iter().any(|e| e.trim_matches('"') == terminal);
+
+    let closers = [
+        ("fi", Token::FI),
+        ("done", Token::DONE),
+        ("esac", Token::ESAC),
+        ("}", Token::RBRACE),
+        (")", Token::RPAREN),
+    ];
+    let openers = [("then", Token::THEN), ("do", Token::DO), ("in", Token::IN)];
+    let separators = [(";", Token::SEMI), ("\\n", Token::NEWLINE)];
+
+    // a single placeholder is enough, end the command after it
+    let candidates = match after_placeholder {
+        true => [&closers[..], &separators, &openers].concat(),
+        false => [&closers[..], &openers].concat(),
+    };
+    if let Some((_, token)) = candidates.into_iter().find(|(terminal, _)| has(terminal)) {
+        return Some(token);
+    }
+    if has("WORD") {
+        return Some(Token::WORD(""));
     }
+    separators
+        .into_iter()
+        .find(|(terminal, _)| has(terminal))
+        .map(|(_, token)| token)
+}
+
+/// Convert an error from the generated parser into an [Error], `tokens` are the ones the parser
+/// was given and decide the hint
+fn convert_error(
+    input: &str,
+    tokens: &[(usize, Token, usize)],
+    e: ParseError<usize, Token, lexer::Error>,
+) -> Error {
+    let mut error = match e {
+        ParseError::InvalidToken { location } => {
+            let ch = input[location..].chars().next().unwrap_or_default();
+            let span = Span::new(location, location + ch.len_utf8());
+            Error::new(ErrorKind::UnrecognizedChar(ch), span, vec![])
+        },
+        ParseError::UnrecognizedEOF { location, expected } => {
+            // point at the end of the last line rather than past the trailing newline
+            let location = location.min(input.trim_end().len());
+            Error::new(
+                ErrorKind::UnexpectedEof,
+                Span::new(location, location),
+                expected,
+            )
+        },
+        ParseError::UnrecognizedToken {
+            token: (start, _, end),
+            expected,
+        } => {
+            let token = input[start..end].to_string();
+            Error::new(
+                ErrorKind::UnexpectedToken(token),
+                Span::new(start, end),
+                expected,
+            )
+        },
+        ParseError::ExtraToken {
+            token: (start, _, end),
+        } => {
+            let token = input[start..end].to_string();
+            Error::new(
+                ErrorKind::UnexpectedToken(token),
+                Span::new(start, end),
+                vec![],
+            )
+        },
+        ParseError::User {
+            error: lexer::Error::UnrecognizedChar(start, ch, end),
+        } => Error::new(
+            ErrorKind::UnrecognizedChar(ch),
+            Span::new(start, end),
+            vec![],
+        ),
+        ParseError::User {
+            error: lexer::Error::BadWord(start, e, end),
+        } => Error::new(
+            ErrorKind::BadWord(Box::new(e)),
+            Span::new(start, end),
+            vec![],
+        ),
+    };
+    let open = open_commands(tokens, error.span.start);
+    error.hint = hint(&error.kind, &error.expected, open.last().copied());
+    error
 }
 
 /*
@@ -44,3 +452,208 @@ mod tests {
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::{hint, open_commands, ErrorKind, Open, Parser};
+    use crate::{ast, expand::WordPart, Lexer, Span};
+
+    #[test]
+    fn heredoc() {
+        let cmd = Parser::new()
+            .parse(Lexer::new("cat <<-EOF\n\thello\n\tEOF\n"))
+            .unwrap();
+        let ast::Command::Simple { redirects, .. } = cmd else {
+            panic!("expected simple command, got {cmd:?}");
+        };
+        assert_eq!(
+            redirects,
+            vec![ast::Redirect {
+                n: None,
+                file: ast::Word::new("EOF", Span::new(7, 10)),
+                mode: ast::RedirectMode::HereDoc {
+                    body: ast::Word::here_doc("\thello\n", Span::new(11, 18), "EOF", true).unwrap(),
+                    strip_tabs: true
+                },
+                span: Span::new(4, 10),
+            }]
+        );
+        let ast::RedirectMode::HereDoc { body, .. } = &redirects[0].mode else {
+            unreachable!();
+        };
+        assert_eq!(body.parts, vec![WordPart::Literal(String::from("hello\n"))]);
+
+        // the body is parsed along with the command
+        let error = Parser::new()
+            .parse(Lexer::new("cat <<EOF\n$(fi)\nEOF\n"))
+            .unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::BadWord(_)), "{error:?}");
+        assert_eq!(error.span, Span::new(10, 16));
+    }
+
+    #[test]
+    fn assignments() {
+        let cmd = Parser::new()
+            .parse(Lexer::new("a=1 _b='x y' ls c=3 2=x"))
+            .unwrap();
+        let ast::Command::Simple { assigns, args, .. } = cmd else {
+            panic!("expected simple command, got {cmd:?}");
+        };
+        assert_eq!(
+            assigns,
+            vec![
+                ast::Assign {
+                    var: String::from("a"),
+                    val: ast::Word::new("1", Span::new(2, 3)),
+                    span: Span::new(0, 3),
+                },
+                ast::Assign {
+                    var: String::from("_b"),
+                    val: ast::Word::new("'x y'", Span::new(7, 12)),
+                    span: Span::new(4, 12),
+                },
+            ]
+        );
+        assert_eq!(args, vec!["ls", "c=3", "2=x"]);
+        assert_eq!(args[1].span, Span::new(16, 19));
+    }
+
+    #[test]
+    fn newline_separated_list() {
+        let cmd = Parser::new().parse(Lexer::new("{ a\nb\n}")).unwrap();
+        assert!(matches!(cmd, ast::Command::SeqList(_, Some(_))), "{cmd:?}");
+    }
+
+    #[test]
+    fn background_list() {
+        // `&` only applies to the and-or list right before it
+        let cmd = Parser::new().parse(Lexer::new("a; b && c & d")).unwrap();
+        let ast::Command::SeqList(_, Some(rest)) = cmd else {
+            panic!("expected sequential list, got {cmd:?}");
+        };
+        assert!(
+            matches!(*rest, ast::Command::AsyncList(ref b, Some(_)) if matches!(**b, ast::Command::And(..))),
+            "{rest:?}"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let source = "if true; then\n  echo hi\n";
+        let error = Parser::new().parse(Lexer::new(source)).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::UnexpectedEof);
+        assert_eq!(error.span, Span::new(23, 23));
+        assert_eq!(
+            error.hint.as_deref(),
+            Some("missing `fi` to close the `if`")
+        );
+        assert_eq!(
+            error.render(source),
+            [
+                format!("error: {}", error.message()),
+                String::from(" --> 2:10"),
+                String::from("  |"),
+                String::from("2 |   echo hi"),
+                String::from("  |          ^"),
+                String::from("  = hint: missing `fi` to close the `if`"),
+            ]
+            .join("\n")
+        );
+
+        let source = "while true; done";
+        let error = Parser::new().parse(Lexer::new(source)).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::UnexpectedToken(String::from("done")));
+        assert_eq!(error.span, Span::new(12, 16));
+        assert_eq!(
+            error.hint.as_deref(),
+            Some("loop conditions and word lists are followed by `do`")
+        );
+        assert!(error.message().starts_with("unexpected `done`"));
+
+        // commands inside substitutions are parsed along with the rest
+        let source = "echo $(if true) ok";
+        let error = Parser::new().parse(Lexer::new(source)).unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::BadWord(_)), "{error:?}");
+        assert_eq!(error.span, Span::new(5, 15));
+        assert!(error
+            .message()
+            .starts_with("if true: syntax error in command substitution"));
+        assert!(Parser::new().parse(Lexer::new("echo `fi`")).is_err());
+        assert!(Parser::new().parse(Lexer::new("cat <(done)")).is_err());
+    }
+
+    #[test]
+    fn hint_for_open_command() {
+        // the expected set is not enough to tell which command is missing its end
+        let expected = vec![String::from("`fi`"), String::from("`}`")];
+        assert_eq!(hint(&ErrorKind::UnexpectedEof, &expected, None), None);
+
+        let tokens: Vec<_> = Lexer::new("{ if true; then x; fi")
+            .map(|token| token.unwrap())
+            .collect();
+        let open = open_commands(&tokens, 21);
+        assert_eq!(open, vec![Open::Brace]);
+        assert_eq!(
+            hint(&ErrorKind::UnexpectedEof, &expected, open.last().copied()).as_deref(),
+            Some("missing `}` to close the brace group")
+        );
+
+        let source = "if true; then { echo hi; fi";
+        let error = Parser::new().parse(Lexer::new(source)).unwrap_err();
+        assert_eq!(
+            error.hint.as_deref(),
+            Some("missing `}` to close the brace group")
+        );
+
+        // nothing is left unclosed, the reserved word is the problem
+        let error = Parser::new()
+            .parse(Lexer::new("f() { echo in; }"))
+            .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::UnexpectedToken(String::from("in")));
+        assert_eq!(error.hint, None);
+    }
+
+    #[test]
+    fn partial() {
+        let parse = Parser::new().parse_partial(Lexer::new("if true; then echo hi"));
+        assert!(parse.incomplete);
+        assert!(parse.errors.is_empty());
+        assert!(
+            matches!(parse.cmd, ast::Command::If { .. }),
+            "{:?}",
+            parse.cmd
+        );
+
+        // missing words are filled in with an empty placeholder
+        let parse = Parser::new().parse_partial(Lexer::new("for i"));
+        assert!(parse.incomplete);
+        assert!(
+            matches!(parse.cmd, ast::Command::For { .. }),
+            "{:?}",
+            parse.cmd
+        );
+
+        let parse = Parser::new().parse_partial(Lexer::new("echo 'hi"));
+        assert!(parse.incomplete);
+        assert!(parse.errors.is_empty());
+
+        // a word that can not be parsed is reported and left empty
+        let parse = Parser::new().parse_partial(Lexer::new("echo $(fi) x"));
+        assert_eq!(parse.errors.len(), 1);
+        assert!(matches!(parse.cmd, ast::Command::Simple { ref args, .. } if args[1] == ""));
+
+        // unexpected tokens are reported and left out
+        let parse = Parser::new().parse_partial(Lexer::new("echo hi\nfi\nls"));
+        assert!(!parse.incomplete);
+        assert_eq!(parse.errors.len(), 1);
+        assert_eq!(parse.errors[0].span, Span::new(8, 10));
+        let ast::Command::SeqList(first, Some(rest)) = parse.cmd else {
+            panic!("expected list, got {:?}", parse.cmd);
+        };
+        assert!(matches!(*first, ast::Command::Simple { ref args, .. } if args[0] == "echo"));
+        assert!(matches!(*rest, ast::Command::Simple { ref args, .. } if args[0] == "ls"));
+
+        let parse = Parser::new().parse_partial(Lexer::new("ls | grep x"));
+        assert!(!parse.incomplete && parse.errors.is_empty());
+    }
+}
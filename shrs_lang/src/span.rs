@@ -0,0 +1,82 @@
+//! Locations in the source text
+
+/// Byte range in the source text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// Smallest span that covers both spans
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    /// One-based line and column of the start of the span
+    ///
+    /// Columns are counted in characters rather than bytes.
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let start = self.start.min(source.len());
+        let before = &source[..start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let col = source[line_start..start].chars().count() + 1;
+        (line, col)
+    }
+
+    /// Show a message along with the line the span starts on, with the span underlined
+    ///
+    /// The note is added as a line of its own below the underline.
+    pub(crate) fn render(&self, source: &str, header: &str, note: Option<&str>) -> String {
+        let (line, col) = self.line_col(source);
+        let start = self.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let text = &source[line_start..line_end];
+
+        // keep tabs so the underline lines up with the source
+        let indent = source[line_start..start]
+            .chars()
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let width = source[start..self.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+
+        let gutter = " ".repeat(line.to_string().len());
+        let mut out = format!("{header}\n");
+        out.push_str(&format!("{gutter}--> {line}:{col}\n"));
+        out.push_str(&format!("{gutter} |\n"));
+        out.push_str(&format!("{line} | {text}\n"));
+        out.push_str(&format!("{gutter} | {indent}{}", "^".repeat(width)));
+        if let Some(note) = note {
+            out.push_str(&format!("\n{gutter} = {note}"));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Span;
+
+    #[test]
+    fn line_col() {
+        let source = "echo a\nif true; thne\n";
+        assert_eq!(Span::new(0, 4).line_col(source), (1, 1));
+        assert_eq!(Span::new(16, 20).line_col(source), (2, 10));
+        assert_eq!(Span::new(21, 21).line_col(source), (3, 1));
+    }
+}
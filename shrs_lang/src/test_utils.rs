@@ -0,0 +1,91 @@
+//! Helpers shared by the unit tests
+
+use std::{
+    cell::RefCell,
+    io::{stdout, BufWriter},
+    time::Instant,
+};
+
+use shrs_core::{
+    builtin::{BuiltinCmd, BuiltinStatus, Builtins},
+    hooks::Hooks,
+    trap::Traps,
+    Alias, Context, Env, Functions, Jobs, Options, Runtime, Shell, Signals, State, Theme,
+};
+use shrs_job::JobManager;
+
+use crate::PosixLang;
+
+/// Write a line of text straight to a file descriptor, `emit FD TEXT`
+///
+/// Unlike the output of the other builtins, this is not intercepted by the test harness.
+struct EmitBuiltin;
+
+impl BuiltinCmd for EmitBuiltin {
+    fn run(
+        &self,
+        _sh: &Shell,
+        _ctx: &mut Context,
+        _rt: &mut Runtime,
+        args: &Vec<String>,
+    ) -> anyhow::Result<BuiltinStatus> {
+        let fd = args[0].parse::<i32>()?;
+        let line = format!("{}\n", args[1]);
+        Ok(match nix::unistd::write(fd, line.as_bytes()) {
+            Ok(_) => BuiltinStatus::success(),
+            Err(_) => BuiltinStatus::error(),
+        })
+    }
+}
+
+/// Shell with the default builtins and hooks, along with `emit`
+pub fn shell() -> Shell {
+    let mut builtins = Builtins::default();
+    builtins.insert("emit", EmitBuiltin);
+    Shell {
+        job_manager: RefCell::new(JobManager::default()),
+        hooks: Hooks::new(),
+        builtins,
+        theme: Theme::default(),
+        lang: Box::new(PosixLang {}),
+        signals: Signals::new().unwrap(),
+    }
+}
+
+pub fn context() -> Context {
+    Context {
+        alias: Alias::new(),
+        out: BufWriter::new(stdout()),
+        state: State::new(),
+        jobs: Jobs::new(),
+        startup_time: Instant::now(),
+        proc_substs: vec![],
+    }
+}
+
+/// Runtime with a few variables and positional parameters set
+pub fn runtime() -> Runtime {
+    let mut env = Env::new();
+    env.set("FILE", "archive.tar.gz").unwrap();
+    env.set("EMPTY", "").unwrap();
+    env.set("HOME", "/home/user").unwrap();
+    Runtime {
+        working_dir: std::env::temp_dir(),
+        env,
+        name: String::from("shrs"),
+        args: vec![String::from("one"), String::from("two")],
+        exit_status: 3,
+        pipe_status: vec![],
+        rematch: vec![],
+        pid: std::process::id(),
+        last_background: None,
+        options: Options::default(),
+        functions: Functions::new(),
+        frames: vec![],
+        control_flow: None,
+        loops: 0,
+        sources: 0,
+        conditions: 0,
+        traps: Traps::new(),
+    }
+}
@@ -0,0 +1,429 @@
+//! Traverse the AST
+//!
+//! [Visitor] walks the AST by reference and [VisitorMut] by mutable reference, so it can also be
+//! rewritten. Every method is called on the node of its type and by default goes on to visit the
+//! children of the node, using the `walk_*` function of the same name. Overriding a method lets a
+//! visitor act on the nodes it is interested in, calling the `walk_*` function from the override
+//! continues the traversal into the children. Words are children too, the commands of the
+//! command and process substitutions in them are visited by [walk_word].
+//!
+//! ```
+//! use shrs_lang::{ast::Command, visit::{self, Visitor}, Lexer, Parser};
+//!
+//! /// Collect the name of every command that is run
+//! #[derive(Default)]
+//! struct Programs(Vec<String>);
+//!
+//! impl Visitor for Programs {
+//!     fn visit_command(&mut self, cmd: &Command) {
+//!         if let Command::Simple { args, .. } = cmd {
+//!             self.0.extend(args.first().map(|arg| arg.to_string()));
+//!         }
+//!         visit::walk_command(self, cmd);
+//!     }
+//! }
+//!
+//! let cmd = Parser::new().parse(Lexer::new("ls | wc -l && echo done")).unwrap();
+//! let mut programs = Programs::default();
+//! programs.visit_command(&cmd);
+//! assert_eq!(programs.0, vec!["ls", "wc", "echo"]);
+//! ```
+
+use crate::{
+    ast::{Assign, CaseArm, Command, Condition, Redirect, RedirectMode, Word},
+    expand::{Index, Param, ParamOp, WordPart},
+};
+
+/// Walk the AST by reference
+pub trait Visitor {
+    fn visit_command(&mut self, cmd: &Command) {
+        walk_command(self, cmd);
+    }
+
+    fn visit_condition(&mut self, cond: &Condition) {
+        walk_condition(self, cond);
+    }
+
+    fn visit_case_arm(&mut self, arm: &CaseArm) {
+        walk_case_arm(self, arm);
+    }
+
+    fn visit_redirect(&mut self, redirect: &Redirect) {
+        walk_redirect(self, redirect);
+    }
+
+    fn visit_assign(&mut self, assign: &Assign) {
+        walk_assign(self, assign);
+    }
+
+    fn visit_word(&mut self, word: &Word) {
+        walk_word(self, word);
+    }
+}
+
+/// Walk the AST by mutable reference, which allows rewriting it
+pub trait VisitorMut {
+    fn visit_command_mut(&mut self, cmd: &mut Command) {
+        walk_command_mut(self, cmd);
+    }
+
+    fn visit_condition_mut(&mut self, cond: &mut Condition) {
+        walk_condition_mut(self, cond);
+    }
+
+    fn visit_case_arm_mut(&mut self, arm: &mut CaseArm) {
+        walk_case_arm_mut(self, arm);
+    }
+
+    fn visit_redirect_mut(&mut self, redirect: &mut Redirect) {
+        walk_redirect_mut(self, redirect);
+    }
+
+    fn visit_assign_mut(&mut self, assign: &mut Assign) {
+        walk_assign_mut(self, assign);
+    }
+
+    fn visit_word_mut(&mut self, word: &mut Word) {
+        walk_word_mut(self, word);
+    }
+}
+
+/// Visit the children of a command, in the order they appear in the source
+pub fn walk_command<V: Visitor + ?Sized>(v: &mut V, cmd: &Command) {
+    match cmd {
+        Command::Simple {
+            assigns,
+            redirects,
+            args,
+        } => {
+            assigns.iter().for_each(|assign| v.visit_assign(assign));
+            args.iter().for_each(|arg| v.visit_word(arg));
+            redirects
+                .iter()
+                .for_each(|redirect| v.visit_redirect(redirect));
+        },
+        Command::Pipeline(a, b) | Command::And(a, b) | Command::Or(a, b) => {
+            v.visit_command(a);
+            v.visit_command(b);
+        },
+        Command::AsyncList(a, b) | Command::SeqList(a, b) => {
+            v.visit_command(a);
+            if let Some(b) = b {
+                v.visit_command(b);
+            }
+        },
+        Command::Not(cmd) | Command::Subshell(cmd) => v.visit_command(cmd),
+        Command::If { conds, else_part } => {
+            conds.iter().for_each(|cond| v.visit_condition(cond));
+            if let Some(else_part) = else_part {
+                v.visit_command(else_part);
+            }
+        },
+        Command::While { cond, body } | Command::Until { cond, body } => {
+            v.visit_command(cond);
+            v.visit_command(body);
+        },
+        Command::For {
+            name,
+            wordlist,
+            body,
+        }
+        | Command::Select {
+            name,
+            wordlist,
+            body,
+        } => {
+            v.visit_word(name);
+            wordlist
+                .iter()
+                .flatten()
+                .for_each(|word| v.visit_word(word));
+            v.visit_command(body);
+        },
+        Command::Case { word, arms } => {
+            v.visit_word(word);
+            arms.iter().for_each(|arm| v.visit_case_arm(arm));
+        },
+        Command::Fn { fname, body } => {
+            v.visit_word(fname);
+            v.visit_command(body);
+        },
+        Command::Redirected { cmd, redirects } => {
+            v.visit_command(cmd);
+            redirects
+                .iter()
+                .for_each(|redirect| v.visit_redirect(redirect));
+        },
+        Command::Arith(_) | Command::Cond(_) | Command::None => {},
+    }
+}
+
+pub fn walk_condition<V: Visitor + ?Sized>(v: &mut V, cond: &Condition) {
+    v.visit_command(&cond.cond);
+    v.visit_command(&cond.body);
+}
+
+pub fn walk_case_arm<V: Visitor + ?Sized>(v: &mut V, arm: &CaseArm) {
+    arm.pattern.iter().for_each(|word| v.visit_word(word));
+    v.visit_command(&arm.body);
+}
+
+pub fn walk_redirect<V: Visitor + ?Sized>(v: &mut V, redirect: &Redirect) {
+    v.visit_word(&redirect.file);
+    if let RedirectMode::HereDoc { body, .. } = &redirect.mode {
+        v.visit_word(body);
+    }
+}
+
+pub fn walk_assign<V: Visitor + ?Sized>(v: &mut V, assign: &Assign) {
+    v.visit_word(&assign.val);
+}
+
+/// Visit the commands of the command and process substitutions in a word
+pub fn walk_word<V: Visitor + ?Sized>(v: &mut V, word: &Word) {
+    walk_parts(v, &word.parts);
+}
+
+fn walk_parts<V: Visitor + ?Sized>(v: &mut V, parts: &[WordPart]) {
+    for part in parts {
+        match part {
+            WordPart::CommandSubst(cmd) | WordPart::ProcessSubst { cmd, .. } => {
+                v.visit_command(cmd);
+            },
+            WordPart::DoubleQuoted(parts) | WordPart::Arith(parts) => walk_parts(v, parts),
+            WordPart::Param(Param { index, op, .. }) => {
+                if let Some(Index::Expr(parts)) = index {
+                    walk_parts(v, parts);
+                }
+                match op {
+                    ParamOp::Default { word, .. }
+                    | ParamOp::Assign { word, .. }
+                    | ParamOp::Error { word, .. }
+                    | ParamOp::Alternative { word, .. }
+                    | ParamOp::RemoveSmallestSuffix(word)
+                    | ParamOp::RemoveLargestSuffix(word)
+                    | ParamOp::RemoveSmallestPrefix(word)
+                    | ParamOp::RemoveLargestPrefix(word) => walk_parts(v, word),
+                    ParamOp::Value | ParamOp::Length => {},
+                }
+            },
+            WordPart::Literal(_) | WordPart::Quoted(_) | WordPart::Tilde(_) => {},
+        }
+    }
+}
+
+/// Visit the children of a command, in the order they appear in the source
+pub fn walk_command_mut<V: VisitorMut + ?Sized>(v: &mut V, cmd: &mut Command) {
+    match cmd {
+        Command::Simple {
+            assigns,
+            redirects,
+            args,
+        } => {
+            assigns
+                .iter_mut()
+                .for_each(|assign| v.visit_assign_mut(assign));
+            args.iter_mut().for_each(|arg| v.visit_word_mut(arg));
+            redirects
+                .iter_mut()
+                .for_each(|redirect| v.visit_redirect_mut(redirect));
+        },
+        Command::Pipeline(a, b) | Command::And(a, b) | Command::Or(a, b) => {
+            v.visit_command_mut(a);
+            v.visit_command_mut(b);
+        },
+        Command::AsyncList(a, b) | Command::SeqList(a, b) => {
+            v.visit_command_mut(a);
+            if let Some(b) = b {
+                v.visit_command_mut(b);
+            }
+        },
+        Command::Not(cmd) | Command::Subshell(cmd) => v.visit_command_mut(cmd),
+        Command::If { conds, else_part } => {
+            conds
+                .iter_mut()
+                .for_each(|cond| v.visit_condition_mut(cond));
+            if let Some(else_part) = else_part {
+                v.visit_command_mut(else_part);
+            }
+        },
+        Command::While { cond, body } | Command::Until { cond, body } => {
+            v.visit_command_mut(cond);
+            v.visit_command_mut(body);
+        },
+        Command::For {
+            name,
+            wordlist,
+            body,
+        }
+        | Command::Select {
+            name,
+            wordlist,
+            body,
+        } => {
+            v.visit_word_mut(name);
+            wordlist
+                .iter_mut()
+                .flatten()
+                .for_each(|word| v.visit_word_mut(word));
+            v.visit_command_mut(body);
+        },
+        Command::Case { word, arms } => {
+            v.visit_word_mut(word);
+            arms.iter_mut().for_each(|arm| v.visit_case_arm_mut(arm));
+        },
+        Command::Fn { fname, body } => {
+            v.visit_word_mut(fname);
+            v.visit_command_mut(body);
+        },
+        Command::Redirected { cmd, redirects } => {
+            v.visit_command_mut(cmd);
+            redirects
+                .iter_mut()
+                .for_each(|redirect| v.visit_redirect_mut(redirect));
+        },
+        Command::Arith(_) | Command::Cond(_) | Command::None => {},
+    }
+}
+
+pub fn walk_condition_mut<V: VisitorMut + ?Sized>(v: &mut V, cond: &mut Condition) {
+    v.visit_command_mut(&mut cond.cond);
+    v.visit_command_mut(&mut cond.body);
+}
+
+pub fn walk_case_arm_mut<V: VisitorMut + ?Sized>(v: &mut V, arm: &mut CaseArm) {
+    arm.pattern
+        .iter_mut()
+        .for_each(|word| v.visit_word_mut(word));
+    v.visit_command_mut(&mut arm.body);
+}
+
+pub fn walk_redirect_mut<V: VisitorMut + ?Sized>(v: &mut V, redirect: &mut Redirect) {
+    v.visit_word_mut(&mut redirect.file);
+    if let RedirectMode::HereDoc { body, .. } = &mut redirect.mode {
+        v.visit_word_mut(body);
+    }
+}
+
+pub fn walk_assign_mut<V: VisitorMut + ?Sized>(v: &mut V, assign: &mut Assign) {
+    v.visit_word_mut(&mut assign.val);
+}
+
+/// Visit the commands of the command and process substitutions in a word
+///
+/// The text of the word is left as it was written, changes to the commands only affect how the
+/// word is expanded.
+pub fn walk_word_mut<V: VisitorMut + ?Sized>(v: &mut V, word: &mut Word) {
+    walk_parts_mut(v, &mut word.parts);
+}
+
+fn walk_parts_mut<V: VisitorMut + ?Sized>(v: &mut V, parts: &mut [WordPart]) {
+    for part in parts {
+        match part {
+            WordPart::CommandSubst(cmd) | WordPart::ProcessSubst { cmd, .. } => {
+                v.visit_command_mut(cmd);
+            },
+            WordPart::DoubleQuoted(parts) | WordPart::Arith(parts) => walk_parts_mut(v, parts),
+            WordPart::Param(Param { index, op, .. }) => {
+                if let Some(Index::Expr(parts)) = index {
+                    walk_parts_mut(v, parts);
+                }
+                match op {
+                    ParamOp::Default { word, .. }
+                    | ParamOp::Assign { word, .. }
+                    | ParamOp::Error { word, .. }
+                    | ParamOp::Alternative { word, .. }
+                    | ParamOp::RemoveSmallestSuffix(word)
+                    | ParamOp::RemoveLargestSuffix(word)
+                    | ParamOp::RemoveSmallestPrefix(word)
+                    | ParamOp::RemoveLargestPrefix(word) => walk_parts_mut(v, word),
+                    ParamOp::Value | ParamOp::Length => {},
+                }
+            },
+            WordPart::Literal(_) | WordPart::Quoted(_) | WordPart::Tilde(_) => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{walk_command, walk_command_mut, Visitor, VisitorMut};
+    use crate::{
+        ast::{Command, Word},
+        Lexer, Parser,
+    };
+
+    fn parse(input: &str) -> Command {
+        Parser::new().parse(Lexer::new(input)).unwrap()
+    }
+
+    /// Every word in the order it is visited
+    #[derive(Default)]
+    struct Words(Vec<String>);
+
+    impl Visitor for Words {
+        fn visit_word(&mut self, word: &Word) {
+            self.0.push(word.to_string());
+        }
+    }
+
+    /// Ask before removing anything
+    struct Interactive;
+
+    impl VisitorMut for Interactive {
+        fn visit_command_mut(&mut self, cmd: &mut Command) {
+            if let Command::Simple { args, .. } = cmd {
+                if args.first().is_some_and(|arg| arg == "rm") {
+                    let span = args[0].span;
+                    args.insert(1, Word::new("-i", span));
+                }
+            }
+            walk_command_mut(self, cmd);
+        }
+    }
+
+    #[test]
+    fn visit_words() {
+        let cmd = parse(
+            "for f in a b; do case $f in a|c) x <file;; esac; done; if t; then f() { g; }; fi",
+        );
+        let mut words = Words::default();
+        words.visit_command(&cmd);
+        let expected = vec!["f", "a", "b", "$f", "a", "c", "x", "file", "t", "f", "g"];
+        assert_eq!(words.0, expected);
+    }
+
+    /// Name of every command that is run, including the ones in substitutions
+    #[derive(Default)]
+    struct Programs(Vec<String>);
+
+    impl Visitor for Programs {
+        fn visit_command(&mut self, cmd: &Command) {
+            if let Command::Simple { args, .. } = cmd {
+                self.0.extend(args.first().map(|arg| arg.to_string()));
+            }
+            walk_command(self, cmd);
+        }
+    }
+
+    #[test]
+    fn visit_substitutions() {
+        let cmd = parse(
+            "x=$(id) echo \"`ls`\" <(cat) ${y:-$(pwd)} $(( $(n) + 1 )) <<EOF\n$(date)\nEOF\n",
+        );
+        let mut programs = Programs::default();
+        programs.visit_command(&cmd);
+        let expected = vec!["echo", "id", "ls", "cat", "pwd", "n", "date"];
+        assert_eq!(programs.0, expected);
+    }
+
+    #[test]
+    fn rewrite() {
+        let mut cmd = parse("rm a && { ls | rm b; } > log");
+        Interactive.visit_command_mut(&mut cmd);
+        let mut words = Words::default();
+        words.visit_command(&cmd);
+        let expected = vec!["rm", "-i", "a", "ls", "rm", "-i", "b", "log"];
+        assert_eq!(words.0, expected);
+    }
+}
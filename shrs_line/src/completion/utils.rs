@@ -2,6 +2,8 @@
 
 use std::path::{Path, PathBuf};
 
+use shrs_lang::{Lexer, Parser, Token};
+
 // also provide some commonly used completion lists
 // - directories
 // - executables
@@ -84,9 +86,49 @@ pub(crate) fn path_end(path: &str) -> String {
     end.chars().rev().collect::<String>()
 }
 
+/// Split the text before the cursor into the words of the command that is being typed
+///
+/// The text is parsed to find where the last command starts, so earlier commands in a pipeline
+/// or list, and keywords such as `then`, are not part of the result. An empty word is added
+/// when the cursor is at the start of a new word.
+pub(crate) fn current_args(line: &str) -> Vec<String> {
+    let parse = Parser::new().parse_partial(Lexer::new(line));
+    let cmd_start = parse
+        .cmd
+        .simple_commands()
+        .iter()
+        .filter_map(|args| args.first().map(|word| word.span.start))
+        .max()
+        .unwrap_or(0);
+
+    let mut args = vec![];
+    let mut end = cmd_start;
+    for (start, token, token_end) in Lexer::new(line).flatten() {
+        if start < cmd_start {
+            continue;
+        }
+        match token {
+            Token::WORD(word) => args.push(word.to_string()),
+            // a new command starts after a separator that the parser did not need to complete
+            Token::SEMI
+            | Token::AMP
+            | Token::NEWLINE
+            | Token::PIPE
+            | Token::AND_IF
+            | Token::OR_IF => args.clear(),
+            _ => {},
+        }
+        end = token_end;
+    }
+    if args.is_empty() || end < line.len() {
+        args.push(String::new());
+    }
+    args
+}
+
 #[cfg(test)]
 mod tests {
-    use super::drop_path_end;
+    use super::{current_args, drop_path_end};
 
     #[test]
     fn test_drop_path_end() {
@@ -94,4 +136,15 @@ mod tests {
         assert_eq!(drop_path_end("Downloads/"), "Downloads/".to_owned());
         assert_eq!(drop_path_end("Downloads"), "".to_owned());
     }
+
+    #[test]
+    fn test_current_args() {
+        assert_eq!(current_args("ls -a"), vec!["ls", "-a"]);
+        assert_eq!(current_args("ls -a "), vec!["ls", "-a", ""]);
+        assert_eq!(current_args("ls | gr"), vec!["gr"]);
+        assert_eq!(current_args("ls |"), vec![""]);
+        assert_eq!(current_args("echo hi; "), vec![""]);
+        assert_eq!(current_args("if true; then ec"), vec!["ec"]);
+        assert_eq!(current_args(""), vec![""]);
+    }
 }
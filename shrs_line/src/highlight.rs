@@ -1,7 +1,7 @@
 use std::{collections::HashMap, usize};
 
 use crossterm::style::{Color, ContentStyle, StyledContent};
-use shrs_lang::{ast, Lexer, Parser, Token, RESERVED_WORDS};
+use shrs_lang::{Lexer, Parser, Token};
 
 use crate::painter::StyledBuf;
 
@@ -69,6 +69,11 @@ impl Default for SyntaxTheme {
                 ..Default::default()
             };
 
+            let error_style = ContentStyle {
+                foreground_color: Some(Color::Red),
+                ..Default::default()
+            };
+
             let mut c_style: HashMap<usize, ContentStyle> = HashMap::new();
             let mut range_insert = |start: usize, end: usize, style: ContentStyle| {
                 (start..end).into_iter().for_each(|u| {
@@ -76,34 +81,17 @@ impl Default for SyntaxTheme {
                 })
             };
 
+            // command names come from the parsed buffer so that incomplete input still works
+            let parse = Parser::new().parse_partial(Lexer::new(buf));
+            for args in parse.cmd.simple_commands() {
+                if let Some(name) = args.first() {
+                    range_insert(name.span.start, name.span.end, cmd_style);
+                }
+            }
+
             let lexer = Lexer::new(buf);
-            let mut is_cmd = true;
             for t in lexer {
                 if let Ok(token) = t {
-                    match token.1.clone() {
-                        Token::WORD(_) => {
-                            if is_cmd {
-                                range_insert(token.0, token.2, cmd_style);
-                                is_cmd = false;
-                            }
-                        },
-                        //Tokens that make next word command
-                        Token::IF
-                        | Token::THEN
-                        | Token::ELSE
-                        | Token::ELIF
-                        | Token::DO
-                        | Token::CASE
-                        | Token::AND_IF
-                        | Token::OR_IF
-                        | Token::SEMI
-                        | Token::DSEMI
-                        | Token::AMP
-                        | Token::PIPE => {
-                            is_cmd = true;
-                        },
-                        _ => (),
-                    }
                     match token.1 {
                         Token::IF
                         | Token::ELSE
@@ -117,6 +105,7 @@ impl Default for SyntaxTheme {
                         | Token::WHILE
                         | Token::UNTIL
                         | Token::FOR
+                        | Token::SELECT
                         | Token::IN => {
                             range_insert(token.0, token.2, reserved_style);
                         },
@@ -129,6 +118,9 @@ impl Default for SyntaxTheme {
                     }
                 }
             }
+            for error in parse.errors {
+                range_insert(error.span.start, error.span.end, error_style);
+            }
             c_style
         };
         rules.push(shrs_rule);
@@ -1,4 +1,4 @@
-use std::{borrow::BorrowMut, io::Write, time::Duration, vec};
+use std::{borrow::BorrowMut, io::Write, time::Duration};
 
 use crossterm::{
     cursor::SetCursorStyle,
@@ -6,14 +6,14 @@ use crossterm::{
     style::{Color, ContentStyle, StyledContent},
     terminal::{disable_raw_mode, enable_raw_mode},
 };
-use shrs_core::{Context, Runtime, Shell};
-use shrs_lang::{Lexer, Token};
+use shrs_core::{trap::exit_shell, Context, EditMode, Runtime, Shell};
+use shrs_lang::Lexer;
 use shrs_utils::cursor_buffer::{CursorBuffer, Location};
 use shrs_vi::{Action, Command, Motion, Parser};
 
 use crate::{
     buffer_history::{BufferHistory, DefaultBufferHistory},
-    completion::{Completer, Completion, CompletionCtx, DefaultCompleter},
+    completion::{current_args, Completer, Completion, CompletionCtx, DefaultCompleter},
     cursor::CursorStyle,
     highlight::{DefaultHighlighter, Highlighter},
     history::{DefaultHistory, History},
@@ -352,8 +352,6 @@ impl Line {
         Ok(())
     }
     fn needs_multiline(&self, ctx: &mut LineCtx) -> bool {
-        //TODO check if open quotes or brackets
-
         if let Some(last_char) = ctx
             .cb
             .char_at(Location::Abs(ctx.cb.len().saturating_sub(1)))
@@ -363,65 +361,11 @@ impl Line {
             }
         };
 
-        let mut brackets: Vec<Token> = vec![];
+        // keep reading lines until compound commands, quotes and here-documents are closed
         let command = self.get_full_command(ctx);
-        let lexer = Lexer::new(command.as_str());
-
-        for t in lexer {
-            if let Ok(token) = t {
-                match token.1 {
-                    Token::LBRACE => brackets.push(token.1),
-                    Token::LPAREN => brackets.push(token.1),
-                    Token::RPAREN => {
-                        if let Some(bracket) = brackets.last() {
-                            if bracket == &Token::LPAREN {
-                                brackets.pop();
-                            } else {
-                                return false;
-                            }
-                        }
-                    },
-                    Token::RBRACE => {
-                        if let Some(bracket) = brackets.last() {
-                            if bracket == &Token::LBRACE {
-                                brackets.pop();
-                            } else {
-                                return false;
-                            }
-                        }
-                    },
-                    Token::WORD(w) => {
-                        if let Some(c) = w.chars().next() {
-                            if c == '\'' {
-                                if w.len() == 1 {
-                                    return true;
-                                }
-                                if let Some(e) = w.chars().last() {
-                                    return e != '\'';
-                                } else {
-                                    return true;
-                                }
-                            }
-                            if c == '\"' {
-                                if w.len() == 1 {
-                                    return true;
-                                }
-
-                                if let Some(e) = w.chars().last() {
-                                    return e != '\"';
-                                } else {
-                                    return true;
-                                }
-                            }
-                        }
-                    },
-
-                    _ => (),
-                }
-            }
-        }
-
-        !brackets.is_empty()
+        shrs_lang::Parser::new()
+            .parse_partial(Lexer::new(&command))
+            .incomplete
     }
     fn get_full_command(&self, ctx: &mut LineCtx) -> String {
         let mut res: String = ctx.lines.clone();
@@ -469,7 +413,7 @@ impl Line {
                     // TODO maybe unify exiting the shell
                     disable_raw_mode(); // TODO this is temp fix, should be more graceful way of
                                         // handling cleanup code
-                    std::process::exit(0);
+                    exit_shell(ctx.sh, ctx.ctx, ctx.rt, 0);
                 } else {
                     self.buffer_history.clear();
                     self.painter.newline()?;
@@ -535,10 +479,17 @@ impl Line {
             },
             Event::Key(KeyEvent {
                 code: KeyCode::Esc, ..
-            }) => {
+            }) if ctx.rt.options.edit_mode == EditMode::Vi => {
                 self.to_normal_mode(ctx)?;
                 self.buffer_history.add(&ctx.cb);
             },
+            Event::Key(KeyEvent {
+                code: KeyCode::Char(c @ ('a' | 'e' | 'b' | 'f' | 'k' | 'u')),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }) if ctx.rt.options.edit_mode == EditMode::Emacs => {
+                self.handle_emacs_keys(ctx, c)?;
+            },
             Event::Key(KeyEvent {
                 code: KeyCode::Backspace,
                 modifiers: KeyModifiers::NONE,
@@ -575,6 +526,20 @@ impl Line {
         Ok(())
     }
 
+    /// Movement and editing bindings of emacs mode, the key is pressed together with `Ctrl`
+    fn handle_emacs_keys(&mut self, ctx: &mut LineCtx, key: char) -> anyhow::Result<()> {
+        match key {
+            'a' => ctx.cb.move_cursor(Location::Front())?,
+            'e' => ctx.cb.move_cursor(Location::Back(&ctx.cb))?,
+            'b' if ctx.cb.cursor() > 0 => ctx.cb.move_cursor(Location::Before())?,
+            'f' if ctx.cb.cursor() < ctx.cb.len() => ctx.cb.move_cursor(Location::After())?,
+            'k' => ctx.cb.delete(Location::Cursor(), Location::Back(&ctx.cb))?,
+            'u' => ctx.cb.delete(Location::Front(), Location::Cursor())?,
+            _ => {},
+        }
+        Ok(())
+    }
+
     fn handle_normal_keys(&mut self, ctx: &mut LineCtx, event: Event) -> anyhow::Result<()> {
         // TODO write better system toString support key combinations
         match event {
@@ -624,11 +589,11 @@ impl Line {
 
     // recalculate the current completions
     fn populate_completions(&mut self, ctx: &mut LineCtx) -> anyhow::Result<()> {
-        // TODO IFS
-        let args = ctx.cb.slice(..ctx.cb.cursor()).as_str().unwrap().split(' ');
-        ctx.current_word = args.clone().last().unwrap_or("").to_string();
+        let before_cursor = ctx.cb.slice(..ctx.cb.cursor()).as_str().unwrap();
+        let args = current_args(before_cursor);
+        ctx.current_word = args.last().cloned().unwrap_or_default();
 
-        let comp_ctx = CompletionCtx::new(args.map(|s| s.to_owned()).collect::<Vec<_>>());
+        let comp_ctx = CompletionCtx::new(args);
 
         let completions = self.completer.complete(&comp_ctx);
         let completions = completions